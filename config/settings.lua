@@ -6,4 +6,5 @@ Settings {
     keyboard_ticks_repeat_delay = 2,
     keyboard_ticks_repeat_rate = 2,
     update_interval = Duration.from_millis(100),
+    watch_config = false,
 }
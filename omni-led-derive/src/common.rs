@@ -65,6 +65,28 @@ pub fn is_option(ty: &Type) -> bool {
     }
 }
 
+#[cfg(any(feature = "from-lua-value", feature = "lua-enum"))]
+pub fn lua_variants<'a>(
+    variants: impl Iterator<Item = (&'a Ident, &'a str, Option<&'a TokenStream>, bool)>,
+) -> TokenStream {
+    use quote::quote;
+
+    let variants = variants.map(|(ident, ty, alias, implicit)| {
+        let ty = ty.replace(' ', "");
+        let alias = alias.map(|alias| quote! { (#alias, #ty, #implicit), });
+        quote! { (stringify!(#ident), #ty, #implicit), #alias }
+    });
+
+    quote! {
+        // Variant names, types of their values and whether they can be constructed implicitly,
+        // for type definitions
+        #[allow(dead_code)]
+        pub const LUA_VARIANTS: &'static [(&'static str, &'static str, bool)] = &[
+            #(#variants)*
+        ];
+    }
+}
+
 #[derive(Debug)]
 pub enum EnumFieldType {
     // Named,
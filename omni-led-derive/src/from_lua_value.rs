@@ -1,11 +1,11 @@
 use convert_case::Casing;
 use proc_macro2::{Ident, TokenStream};
-use quote::{format_ident, quote};
-use syn::{Attribute, Data, DeriveInput};
+use quote::{ToTokens, format_ident, quote};
+use syn::{Attribute, Data, DeriveInput, Type};
 
 use crate::common::{
     EnumFieldType, collect_enum_variants, get_attribute, get_attribute_with_default_value,
-    is_option, parse_attributes,
+    is_option, lua_variants, parse_attributes,
 };
 
 pub fn expand_lua_value_derive(input: DeriveInput) -> proc_macro::TokenStream {
@@ -84,6 +84,7 @@ fn generate_initializer(
                 let mut mask_defs: Vec<TokenStream> = Vec::new();
                 let mut mask_refs: Vec<TokenStream> = Vec::new();
                 let mut field_names: Vec<TokenStream> = Vec::new();
+                let mut lua_fields: Vec<TokenStream> = Vec::new();
 
                 for (index, f) in fields.named.iter().enumerate() {
                     let index = index as u64;
@@ -130,6 +131,17 @@ fn generate_initializer(
                         }
                     });
 
+                    // for `LUA_FIELDS`
+                    let lua_type = match attrs.lua_type {
+                        Some(lua_type) => lua_type,
+                        None => {
+                            let ty = type_name(&f.ty);
+                            quote! { #ty }
+                        }
+                    };
+                    let optional = attrs.default.is_some() || is_option(&f.ty);
+                    lua_fields.push(quote! { (stringify!(#field), #lua_type, #optional) });
+
                     // for `init_defaults`
                     let default = match (attrs.default, is_option(&f.ty)) {
                         (Some(default), _) => Some(default),
@@ -184,6 +196,12 @@ fn generate_initializer(
                         #mask_all;
                         #mask_map;
 
+                        // Field names, types and whether they are optional, for type definitions
+                        #[allow(dead_code)]
+                        pub const LUA_FIELDS: &'static [(&'static str, &'static str, bool)] = &[
+                            #(#lua_fields),*
+                        ];
+
                         fn init_field(
                             ptr: *mut Self,
                             initialized: &mut u64,
@@ -289,6 +307,15 @@ fn generate_initializer(
 
         Data::Enum(ref data) => {
             let fields = collect_enum_variants(data, get_enum_attributes);
+            let variants =
+                lua_variants(fields.iter().map(|(_, ident, ty, attrs)| {
+                    (*ident, ty.as_str(), attrs.alias.as_ref(), false)
+                }));
+            let helper_impl = quote! {
+                impl #name {
+                    #variants
+                }
+            };
 
             let names = fields.iter().map(|(_, ident, _ty, attrs)| {
                 let alias = attrs.alias.as_ref().map(|alias| quote! { #alias, });
@@ -353,7 +380,7 @@ fn generate_initializer(
                 }
             };
 
-            (initializer, None, None)
+            (initializer, None, Some(helper_impl))
         }
         Data::Union(_) => unimplemented!(),
     }
@@ -376,6 +403,7 @@ fn get_struct_attributes(attributes: &Vec<Attribute>) -> StructAttributes {
 struct FieldAttributes {
     default: Option<TokenStream>,
     transform: Option<TokenStream>,
+    lua_type: Option<TokenStream>,
 }
 
 fn get_field_attributes(attributes: &Vec<Attribute>) -> FieldAttributes {
@@ -388,6 +416,7 @@ fn get_field_attributes(attributes: &Vec<Attribute>) -> FieldAttributes {
             quote!(Default::default()),
         ),
         transform: get_attribute(&mut attributes, "transform"),
+        lua_type: get_attribute(&mut attributes, "lua_type"),
     }
 }
 
@@ -402,3 +431,8 @@ fn get_enum_attributes(attributes: &Vec<Attribute>) -> EnumAttributes {
         alias: get_attribute(&mut attributes, "alias"),
     }
 }
+
+// Type name without whitespace, e.g. `Vec<String>`
+fn type_name(ty: &Type) -> String {
+    ty.to_token_stream().to_string().replace(' ', "")
+}
@@ -6,16 +6,19 @@ use syn::{Attribute, Data, DeriveInput};
 
 use crate::common::{
     EnumFieldType, collect_enum_variants, get_attribute, get_attribute_with_default_value,
-    parse_attributes,
+    lua_variants, parse_attributes,
 };
 
 pub fn expand_lua_enum_derive(input: DeriveInput) -> proc_macro::TokenStream {
     let name = input.ident;
     let table_initializer = generate_initializers(&input.data);
     let (builtin, userdata) = generate_constructors(&input.data);
+    let variants = generate_variants(&input.data);
 
     let expanded = quote! {
         impl #name {
+            #variants
+
             pub fn set_lua_enum(lua: &mlua::Lua, env: &mlua::Table) -> mlua::Result<()> {
                 let table = lua.create_table()?;
                 #table_initializer
@@ -93,6 +96,19 @@ fn generate_initializers(data: &Data) -> TokenStream {
     }
 }
 
+fn generate_variants(data: &Data) -> TokenStream {
+    match *data {
+        Data::Enum(ref data) => {
+            let fields = collect_enum_variants(data, get_enum_attributes);
+            lua_variants(fields.iter().map(|(_, ident, ty, attrs)| {
+                let implicit = attrs.implicit_construct.is_some();
+                (*ident, ty.as_str(), attrs.alias.as_ref(), implicit)
+            }))
+        }
+        _ => panic!("Expected enum"),
+    }
+}
+
 fn generate_constructors(data: &Data) -> (TokenStream, TokenStream) {
     match *data {
         Data::Struct(_) => panic!("Expected enum"),
@@ -14,14 +14,16 @@ macro_rules! create_table_with_defaults {
             .load(chunk! {
                 new_table = $values
                 new_table.assert = assert
+                new_table.cbor = internal.table_copy(internal.cbor)
                 new_table.coroutine = internal.table_copy(coroutine)
                 new_table.dump = internal.dump
                 new_table.getmetatable = getmetatable
                 new_table.ipairs = ipairs
+                new_table.json = internal.table_copy(internal.json)
                 new_table.marked_table = internal.marked_table
                 new_table.math = internal.table_copy(math)
                 new_table.math.round = internal.round
-                new_table.next = next
+                new_table.next = internal.next
                 new_table.os = {
                     clock = os.clock,
                     date = os.date,
@@ -60,6 +62,19 @@ pub fn load_internal_functions(lua: &Lua) {
         .create_function(|lua, table: Table| crate::events::cbor_to_lua::marked_table(lua, table))
         .unwrap();
 
+    let (json, cbor) = crate::common::encoding::create_encoding_tables(lua);
+
+    // Event tables are proxies while dependencies of a layout are tracked
+    let next = lua
+        .create_function({
+            let next: mlua::Function = lua.globals().get("next").unwrap();
+            move |_, (table, key): (Table, Value)| {
+                let table = crate::script_handler::dependencies::unwrap_proxy(table)?;
+                next.call::<mlua::MultiValue>((table, key))
+            }
+        })
+        .unwrap();
+
     let round = lua
         .create_function(|_, value: f64| {
             let value = value.round() as i64;
@@ -82,8 +97,11 @@ pub fn load_internal_functions(lua: &Lua) {
         .set(
             "internal",
             create_table!(lua, {
+                cbor = $cbor,
                 dump = $dump,
+                json = $json,
                 marked_table = $marked_table,
+                next = $next,
                 round = $round,
                 table_copy = $table_copy
             }),
@@ -0,0 +1,128 @@
+use ciborium::Value as CborValue;
+use mlua::{Lua, LuaString, Table, Value, chunk};
+use serde_json::Value as JsonValue;
+
+use crate::create_table;
+use crate::events::cbor_to_lua::{cbor_to_lua_value, lua_to_cbor_value};
+
+pub fn json_encode(value: Value) -> mlua::Result<String> {
+    let value = lua_to_json_value(value)?;
+    serde_json::to_string(&value).map_err(mlua::Error::external)
+}
+
+pub fn json_decode(lua: &Lua, json: &str) -> mlua::Result<Value> {
+    let value: CborValue = serde_json::from_str(json).map_err(mlua::Error::external)?;
+    cbor_to_lua_value(lua, value)
+}
+
+pub fn lua_to_json_value(value: Value) -> mlua::Result<JsonValue> {
+    let value = lua_to_cbor_value(value)?;
+    check_json_value(&value)?;
+    serde_json::to_value(value).map_err(mlua::Error::external)
+}
+
+// Serde would silently turn these into nulls and arrays, which don't decode back to the same values
+fn check_json_value(value: &CborValue) -> mlua::Result<()> {
+    match value {
+        CborValue::Float(value) if !value.is_finite() => Err(mlua::Error::runtime(format!(
+            "Number '{value}' can't be encoded as JSON"
+        ))),
+        CborValue::Bytes(_) => Err(mlua::Error::runtime(
+            "Strings that aren't valid UTF-8 can't be encoded as JSON",
+        )),
+        CborValue::Array(values) => values.iter().try_for_each(check_json_value),
+        CborValue::Map(items) => items
+            .iter()
+            .try_for_each(|(_, value)| check_json_value(value)),
+        _ => Ok(()),
+    }
+}
+
+pub fn json_value_to_lua(lua: &Lua, value: JsonValue) -> mlua::Result<Value> {
+    let value: CborValue = serde_json::from_value(value).map_err(mlua::Error::external)?;
+    cbor_to_lua_value(lua, value)
+}
+
+pub fn cbor_encode(value: Value) -> mlua::Result<Vec<u8>> {
+    let value = lua_to_cbor_value(value)?;
+    let mut buffer = Vec::new();
+    ciborium::into_writer(&value, &mut buffer).map_err(mlua::Error::external)?;
+    Ok(buffer)
+}
+
+pub fn cbor_decode(lua: &Lua, bytes: &[u8]) -> mlua::Result<Value> {
+    let value: CborValue = ciborium::from_reader(bytes).map_err(mlua::Error::external)?;
+    cbor_to_lua_value(lua, value)
+}
+
+pub fn create_encoding_tables(lua: &Lua) -> (Table, Table) {
+    let json_encode = lua
+        .create_function(|_, value: Value| json_encode(value))
+        .unwrap();
+    let json_decode = lua
+        .create_function(|lua, json: LuaString| json_decode(lua, &json.to_str()?))
+        .unwrap();
+    let cbor_encode = lua
+        .create_function(|lua, value: Value| lua.create_string(cbor_encode(value)?))
+        .unwrap();
+    let cbor_decode = lua
+        .create_function(|lua, bytes: LuaString| cbor_decode(lua, &bytes.as_bytes()))
+        .unwrap();
+
+    let json = create_table!(lua, {
+        encode = $json_encode,
+        decode = $json_decode
+    });
+    let cbor = create_table!(lua, {
+        encode = $cbor_encode,
+        decode = $cbor_decode
+    });
+    (json, cbor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_round_trip() {
+        let lua = Lua::new();
+        let json = r#"{"list":[1,2.5,"three"],"nested":{"flag":true,"empty":{}},"none":null}"#;
+
+        let value = json_decode(&lua, json).unwrap();
+        let table = value.as_table().unwrap();
+        assert_eq!(table.get::<Table>("list").unwrap().raw_len(), 3);
+        assert!(table.get::<Value>("none").unwrap().is_nil());
+
+        let encoded = json_encode(value).unwrap();
+        let expected: serde_json::Value = serde_json::from_str(json).unwrap();
+        let actual: serde_json::Value = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn cbor_round_trip() {
+        let lua = Lua::new();
+        let value: Value = lua
+            .load("return { count = 3, ratio = 0.5, name = 'test', list = { 1, 2, 3 } }")
+            .eval()
+            .unwrap();
+
+        let bytes = cbor_encode(value).unwrap();
+        let value = cbor_decode(&lua, &bytes).unwrap();
+
+        let table = value.as_table().unwrap();
+        assert_eq!(table.get::<i64>("count").unwrap(), 3);
+        assert_eq!(table.get::<f64>("ratio").unwrap(), 0.5);
+        assert_eq!(table.get::<String>("name").unwrap(), "test");
+        assert_eq!(table.get::<Vec<i64>>("list").unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn invalid_input() {
+        let lua = Lua::new();
+        assert!(json_decode(&lua, "{ invalid").is_err());
+        assert!(cbor_decode(&lua, &[0xff]).is_err());
+        assert!(json_encode(Value::Number(f64::NAN)).is_err());
+    }
+}
@@ -5,23 +5,33 @@ use crate::{
     devices::device::MemoryLayout,
     logging::logger::LevelFilter,
     renderer::font_selector::{FamilyName, FontSelector, Stretch, Style, Weight},
+    script_handler::menu::Menu,
     script_handler::script_data_types::{
-        DurationWrapper, EventKey, FontSize, ImageFormat, Regex, Repeat, Widget,
+        Dither, DurationWrapper, EventKey, FontSize, GraphStyle, HorizontalAlign, ImageFilter,
+        ImageFit, ImageFormat, Regex, Repeat, TextWrap, TransitionEffect, VerticalAlign, Widget,
     },
     script_handler::script_handler::ScreenBuilder,
 };
 
 pub fn set_lua_enums(lua: &Lua, env: &Table) {
+    Dither::set_lua_enum(lua, env).unwrap();
     EventKey::set_lua_enum(lua, env).unwrap();
     FamilyName::set_lua_enum(lua, env).unwrap();
     FontSelector::set_lua_enum(lua, env).unwrap();
     FontSize::set_lua_enum(lua, env).unwrap();
+    GraphStyle::set_lua_enum(lua, env).unwrap();
+    HorizontalAlign::set_lua_enum(lua, env).unwrap();
+    ImageFilter::set_lua_enum(lua, env).unwrap();
+    ImageFit::set_lua_enum(lua, env).unwrap();
     ImageFormat::set_lua_enum(lua, env).unwrap();
     LevelFilter::set_lua_enum(lua, env).unwrap();
     MemoryLayout::set_lua_enum(lua, env).unwrap();
     Repeat::set_lua_enum(lua, env).unwrap();
     Stretch::set_lua_enum(lua, env).unwrap();
     Style::set_lua_enum(lua, env).unwrap();
+    TextWrap::set_lua_enum(lua, env).unwrap();
+    TransitionEffect::set_lua_enum(lua, env).unwrap();
+    VerticalAlign::set_lua_enum(lua, env).unwrap();
     Weight::set_lua_enum(lua, env).unwrap();
     Widget::set_lua_enum(lua, env).unwrap();
 }
@@ -29,5 +39,6 @@ pub fn set_lua_enums(lua: &Lua, env: &Table) {
 pub fn set_lua_types(lua: &Lua, env: &Table) {
     ScreenBuilder::register_members(lua, env).unwrap();
     DurationWrapper::register_members(lua, env).unwrap();
+    Menu::register_members(lua, env).unwrap();
     Regex::register_members(lua, env).unwrap();
 }
@@ -1,5 +1,7 @@
 pub mod common;
 pub mod defer;
+pub mod encoding;
 pub mod lua_register;
 pub mod lua_traits;
+pub mod type_definitions;
 pub mod user_data;
@@ -0,0 +1,826 @@
+use ciborium::Value as CborValue;
+use mlua::{Lua, Table, UserData, Value};
+use std::collections::BTreeMap;
+
+use crate::{
+    common::lua_register::set_lua_types,
+    common::lua_traits::LuaName,
+    devices::device::MemoryLayout,
+    devices::emulator::emulator::EmulatorSettings,
+    devices::steelseries_engine::steelseries_engine_device::SteelSeriesEngineDeviceSettings,
+    devices::usb_device::hid_device::{HidDeviceSettings, HidSettings},
+    devices::usb_device::raw_usb_device::{RawUsbDeviceSettings, RawUsbSettings},
+    devices::usb_device::transform::ExtraBytes,
+    events::event_handle::EventHandle,
+    events::events::Events,
+    events::shortcuts::Shortcuts,
+    logging::logger::{LevelFilter, Log},
+    plugin_loader::c_plugin::Config,
+    renderer::buffer::Buffer,
+    renderer::font_selector::{
+        BitmapSelector, FamilyName, FilesystemSelector, FontSelector, Stretch, Style,
+        SystemSelector, Weight,
+    },
+    renderer::screen_protection::ScreenProtectionSettings,
+    script_handler::carousel::CarouselSettings,
+    script_handler::menu::{Menu, MenuArea, MenuEntry, MenuKeys, MenuOptions},
+    script_handler::overlay::OverlayData,
+    script_handler::profiler::ProfilingSettings,
+    script_handler::script_data_types::{
+        Arc, Bar, Color, Dither, DurationWrapper, Ellipse, EventKey, FontSize, Graph, GraphStyle,
+        HorizontalAlign, Image, ImageData, ImageFilter, ImageFit, ImageFormat, Line, Modifiers,
+        Point, Polygon, Range, Rect, Rectangle, Regex, Repeat, Size, Text, TextWrap, Transition,
+        TransitionEffect, VerticalAlign, Widget,
+    },
+    script_handler::script_handler::{Layout, LayoutData, ScreenBuilder, Screens},
+    script_handler::storage::StorageNamespace,
+    settings::settings::Settings,
+};
+
+const HEADER: &str =
+    "---@meta\n-- Generated by `omni-led gen-types`, changes will be overwritten\n";
+
+// Signatures of functions and methods, that can't be deduced from the registered userdata
+const SIGNATURES: &[(&str, &str, &str)] = &[
+    ("Duration", "from_hours", "fun(value: integer): Duration"),
+    ("Duration", "from_micros", "fun(value: integer): Duration"),
+    ("Duration", "from_millis", "fun(value: integer): Duration"),
+    ("Duration", "from_mins", "fun(value: integer): Duration"),
+    ("Duration", "from_nanos", "fun(value: integer): Duration"),
+    ("Duration", "from_secs", "fun(value: integer): Duration"),
+    (
+        "Events",
+        "register",
+        "fun(key: EventKey, on_match: fun(event: string, value: any)): EventHandle",
+    ),
+    ("Events", "send", "fun(event: string, value: any)"),
+    ("Events", "unregister", "fun(handle: EventHandle)"),
+    ("Menu", "new", "fun(options: MenuOptions): Menu"),
+    ("Menu", "selected", "fun(self: Menu): string|nil"),
+    (
+        "Menu",
+        "widgets",
+        "fun(self: Menu, area: MenuArea): Widget[]",
+    ),
+    (
+        "Regex",
+        "matches",
+        "fun(self: Regex, string: string): boolean",
+    ),
+    ("Regex", "new", "fun(regex: string): Regex"),
+    (
+        "ScreenBuilder",
+        "new",
+        "fun(device: string|string[]): ScreenBuilder",
+    ),
+    (
+        "ScreenBuilder",
+        "register",
+        "fun(self: ScreenBuilder): Screens",
+    ),
+    (
+        "ScreenBuilder",
+        "with_carousel",
+        "fun(self: ScreenBuilder, carousel: CarouselSettings): ScreenBuilder",
+    ),
+    (
+        "ScreenBuilder",
+        "with_layout",
+        "fun(self: ScreenBuilder, layout: Layout): ScreenBuilder",
+    ),
+    (
+        "ScreenBuilder",
+        "with_layout_group",
+        "fun(self: ScreenBuilder, layouts: Layout[]): ScreenBuilder",
+    ),
+    (
+        "ScreenBuilder",
+        "with_layout_group_previous",
+        "fun(self: ScreenBuilder, keys: string[]): ScreenBuilder",
+    ),
+    (
+        "ScreenBuilder",
+        "with_layout_group_toggle",
+        "fun(self: ScreenBuilder, keys: string[]): ScreenBuilder",
+    ),
+    (
+        "ScreenBuilder",
+        "with_screensaver",
+        "fun(self: ScreenBuilder, screensaver: fun(): LayoutData): ScreenBuilder",
+    ),
+    (
+        "ScreenBuilder",
+        "with_zone",
+        "fun(self: ScreenBuilder, area: Rectangle): ScreenBuilder",
+    ),
+    (
+        "ScreenBuilder",
+        "with_transition",
+        "fun(self: ScreenBuilder, transition: Transition): ScreenBuilder",
+    ),
+    ("Screens", "current", "fun(self: Screens): integer"),
+    ("Screens", "select", "fun(self: Screens, screen: integer)"),
+    (
+        "Shortcuts",
+        "register",
+        "fun(self: Shortcuts, keys: string[], on_match: fun())",
+    ),
+    ("Size", "Height", "integer"),
+    ("Size", "Width", "integer"),
+    (
+        "StorageNamespace",
+        "delete",
+        "fun(self: StorageNamespace, key: string)",
+    ),
+    (
+        "StorageNamespace",
+        "get",
+        "fun(self: StorageNamespace, key: string): any",
+    ),
+    (
+        "StorageNamespace",
+        "namespace",
+        "fun(self: StorageNamespace, name: string): StorageNamespace",
+    ),
+    (
+        "StorageNamespace",
+        "set",
+        "fun(self: StorageNamespace, key: string, value: any)",
+    ),
+];
+
+// Globals of the scripts, settings, devices and plugins configs
+const GLOBALS: &[(&str, &str)] = &[
+    ("Events", "Events"),
+    ("Log", "Log"),
+    ("Overlay", "{ show: fun(overlay: OverlayData) }"),
+    ("PLATFORM", "string"),
+    (
+        "PREDICATE",
+        "{ Always: fun(): boolean, Never: fun(): boolean, Times: fun(n: integer): fun(): boolean }",
+    ),
+    ("Profiler", "{ toggle_overlay: fun() }"),
+    ("SCREEN", "Size"),
+    ("Settings", "fun(settings: Settings)"),
+    ("Shortcuts", "Shortcuts"),
+    ("Storage", "StorageNamespace"),
+    (
+        "cbor",
+        "{ encode: fun(value: any): string, decode: fun(data: string): any }",
+    ),
+    ("dump", "fun(value: any): string"),
+    ("emulator", "fun(settings: EmulatorSettings)"),
+    ("get_default_plugin_path", "fun(name: string): string"),
+    ("hid_device", "fun(settings: HidDeviceSettings)"),
+    (
+        "json",
+        "{ encode: fun(value: any): string, decode: fun(data: string): any }",
+    ),
+    ("load_plugin", "fun(config: Config)"),
+    ("marked_table", "fun(table: table): table"),
+    ("raw_usb_device", "fun(settings: RawUsbDeviceSettings)"),
+    ("require", "fun(name: string): any"),
+    (
+        "steelseries_engine_device",
+        "fun(settings: SteelSeriesEngineDeviceSettings)",
+    ),
+    (
+        "transform_data",
+        "fun(extra: ExtraBytes): fun(buffer: Buffer): integer[]",
+    ),
+];
+
+type Fields = BTreeMap<String, String>;
+
+#[derive(Default)]
+struct Class {
+    fields: Fields,
+    global: bool,
+}
+
+#[derive(Default)]
+struct TypeDefinitions {
+    classes: BTreeMap<String, Class>,
+    // Additional types accepted in place of enums, for implicitly constructed variants
+    implicit: BTreeMap<String, Vec<String>>,
+}
+
+impl TypeDefinitions {
+    fn class(&mut self, name: &str) -> &mut Class {
+        self.classes.entry(name.to_string()).or_default()
+    }
+
+    fn add_struct(&mut self, name: &str, fields: &[(&str, &str, bool)]) {
+        let class = self.class(name);
+        for (field, ty, optional) in fields {
+            let field = match optional {
+                true => format!("{field}?"),
+                false => field.to_string(),
+            };
+            class.fields.insert(field, lua_type(ty));
+        }
+    }
+
+    fn add_enum(&mut self, name: &str, variants: &[(&str, &str, bool)]) {
+        let class = self.class(name);
+        class.global = true;
+
+        let mut implicit = Vec::new();
+        for (variant, ty, implicit_construct) in variants {
+            let constructor = match ty.is_empty() {
+                true => name.to_string(),
+                false => format!("fun(value: {}): {}", lua_type(ty), name),
+            };
+            class.fields.insert(variant.to_string(), constructor);
+
+            if *implicit_construct {
+                implicit.push(lua_type(ty));
+            }
+        }
+
+        if !implicit.is_empty() {
+            self.implicit.insert(name.to_string(), implicit);
+        }
+    }
+
+    fn add_userdata<T: UserData + LuaName + 'static>(&mut self, lua: &Lua) -> mlua::Result<()> {
+        let proxy = lua.create_proxy::<T>()?;
+        let methods = match proxy.metatable()?.get::<Value>("__index")? {
+            Value::Table(methods) => methods,
+            _ => lua.create_table()?,
+        };
+
+        let class = self.class(T::NAME);
+        for pair in methods.pairs::<String, Value>() {
+            let (method, _) = pair?;
+            class.fields.insert(method, String::from("fun(...): any"));
+        }
+        Ok(())
+    }
+
+    fn add_static_members(&mut self, lua: &Lua) -> mlua::Result<()> {
+        let env = lua.create_table()?;
+        set_lua_types(lua, &env);
+
+        for pair in env.pairs::<String, Table>() {
+            let (name, members) = pair?;
+            let class = self.class(&name);
+            class.global = true;
+            for pair in members.pairs::<String, Value>() {
+                let (member, value) = pair?;
+                let ty = match value {
+                    Value::Function(_) => String::from("fun(...): any"),
+                    _ => name.clone(),
+                };
+                class.fields.insert(member, ty);
+            }
+        }
+        Ok(())
+    }
+
+    // Data sent by plugins, deduced from sample events
+    fn add_events(&mut self, events: &[CborValue]) {
+        for event in events {
+            let CborValue::Map(entries) = event else {
+                continue;
+            };
+
+            for (key, value) in entries {
+                let (CborValue::Text(name), CborValue::Map(fields)) = (key, value) else {
+                    continue;
+                };
+
+                let class = self.class(name);
+                class.global = true;
+                for (field, value) in fields {
+                    if let CborValue::Text(field) = field {
+                        class.fields.insert(field_name(field), event_type(value));
+                    }
+                }
+            }
+        }
+    }
+
+    fn apply_signatures(&mut self) {
+        for (class, member, ty) in SIGNATURES {
+            if let Some(class) = self.classes.get_mut(*class) {
+                class.fields.insert(member.to_string(), ty.to_string());
+            }
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut output = String::from(HEADER);
+
+        for (name, class) in &self.classes {
+            output += &format!("\n---@class {name}\n");
+            for (field, ty) in &class.fields {
+                // Enum variants are always of the enum type
+                let ty = match ty == name {
+                    true => ty.clone(),
+                    false => self.accepted_types(ty),
+                };
+                output += &format!("---@field {field} {ty}\n");
+            }
+            if class.global {
+                output += &format!("{name} = {{}}\n");
+            }
+        }
+
+        output += "\n";
+        for (name, ty) in GLOBALS {
+            output += &format!("---@type {}\n{} = nil\n", self.accepted_types(ty), name);
+        }
+
+        output
+    }
+
+    fn accepted_types(&self, ty: &str) -> String {
+        let (name, array) = match ty.strip_suffix("[]") {
+            Some(name) => (name, true),
+            None => (ty, false),
+        };
+
+        match (self.implicit.get(name), array) {
+            (Some(implicit), false) => format!("{}|{}", name, implicit.join("|")),
+            (Some(implicit), true) => format!("({}|{})[]", name, implicit.join("|")),
+            (None, _) => ty.to_string(),
+        }
+    }
+}
+
+pub fn generate(lua: &Lua, events: &[CborValue]) -> mlua::Result<String> {
+    let mut definitions = collect(lua)?;
+    definitions.add_events(events);
+    definitions.apply_signatures();
+
+    Ok(definitions.render())
+}
+
+fn collect(lua: &Lua) -> mlua::Result<TypeDefinitions> {
+    let mut definitions = TypeDefinitions::default();
+
+    macro_rules! structs {
+        ($($ty:ty),* $(,)?) => {
+            $(definitions.add_struct(&short_name(stringify!($ty)), <$ty>::LUA_FIELDS);)*
+        };
+    }
+
+    macro_rules! enums {
+        ($($ty:ty),* $(,)?) => {
+            $(definitions.add_enum(stringify!($ty), <$ty>::LUA_VARIANTS);)*
+        };
+    }
+
+    macro_rules! userdata {
+        ($($ty:ty),* $(,)?) => {
+            $(definitions.add_userdata::<$ty>(lua)?;)*
+        };
+    }
+
+    structs!(
+        Arc,
+        Bar,
+        BitmapSelector,
+        CarouselSettings,
+        Color,
+        Config,
+        Ellipse,
+        EmulatorSettings,
+        ExtraBytes,
+        FilesystemSelector,
+        Graph,
+        HidDeviceSettings,
+        HidSettings,
+        Image,
+        ImageData,
+        Layout,
+        LayoutData,
+        Line,
+        MenuArea,
+        MenuEntry,
+        MenuKeys,
+        MenuOptions,
+        Modifiers,
+        OverlayData,
+        Point,
+        Polygon,
+        ProfilingSettings,
+        Range,
+        RawUsbDeviceSettings,
+        RawUsbSettings,
+        Rect,
+        Rectangle,
+        ScreenProtectionSettings,
+        Settings,
+        Size,
+        SteelSeriesEngineDeviceSettings,
+        SystemSelector,
+        Text,
+        Transition,
+    );
+
+    enums!(
+        Dither,
+        EventKey,
+        FamilyName,
+        FontSelector,
+        FontSize,
+        GraphStyle,
+        HorizontalAlign,
+        ImageFilter,
+        ImageFit,
+        ImageFormat,
+        LevelFilter,
+        MemoryLayout,
+        Repeat,
+        Stretch,
+        Style,
+        TextWrap,
+        TransitionEffect,
+        VerticalAlign,
+        Weight,
+        Widget,
+    );
+
+    userdata!(
+        Buffer,
+        DurationWrapper,
+        EventHandle,
+        Events,
+        Log,
+        Menu,
+        Regex,
+        ScreenBuilder,
+        Screens,
+        Shortcuts,
+        StorageNamespace,
+    );
+
+    definitions.add_static_members(lua)?;
+
+    Ok(definitions)
+}
+
+fn short_name(path: &str) -> String {
+    path.rsplit("::").next().unwrap_or(path).trim().to_string()
+}
+
+// Translates rust type names, as emitted by the derive macros, to LuaLS types
+fn lua_type(ty: &str) -> String {
+    let (name, arguments) = match ty.split_once('<') {
+        Some((name, arguments)) => (name, split_arguments(&arguments[..arguments.len() - 1])),
+        None => (ty, Vec::new()),
+    };
+
+    match (short_name(name).as_str(), arguments.as_slice()) {
+        ("Option", [inner]) => lua_type(inner),
+        ("Vec" | "VecDeque", [inner]) => match lua_type(inner) {
+            inner if inner.contains(['|', ' ']) => format!("({inner})[]"),
+            inner => format!("{inner}[]"),
+        },
+        ("HashMap" | "BTreeMap", [key, value]) => {
+            format!("table<{}, {}>", lua_type(key), lua_type(value))
+        }
+        ("Duration" | "DurationWrapper", _) => String::from("Duration"),
+        ("String" | "PathBuf", _) => String::from("string"),
+        ("Function", _) => String::from("function"),
+        ("Table", _) => String::from("table"),
+        ("Value", _) => String::from("any"),
+        (name, _) => builtin_type(name).unwrap_or(name).to_string(),
+    }
+}
+
+fn builtin_type(ty: &str) -> Option<&'static str> {
+    match ty {
+        "bool" => Some("boolean"),
+        "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize" => {
+            Some("integer")
+        }
+        "f32" | "f64" => Some("number"),
+        "str" => Some("string"),
+        _ => None,
+    }
+}
+
+fn split_arguments(arguments: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in arguments.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(&arguments[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    result.push(&arguments[start..]);
+    result
+}
+
+fn field_name(name: &str) -> String {
+    let identifier = name.chars().next().is_some_and(|c| !c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    match identifier {
+        true => name.to_string(),
+        false => format!("[{name:?}]"),
+    }
+}
+
+fn event_type(value: &CborValue) -> String {
+    match value {
+        CborValue::Integer(_) => String::from("integer"),
+        CborValue::Float(_) => String::from("number"),
+        CborValue::Text(_) | CborValue::Bytes(_) => String::from("string"),
+        CborValue::Bool(_) => String::from("boolean"),
+        CborValue::Array(values) => match values.first() {
+            Some(value) => format!("{}[]", event_type(value)),
+            None => String::from("any[]"),
+        },
+        CborValue::Map(entries) => {
+            let fields: Vec<String> = entries
+                .iter()
+                .filter_map(|(key, value)| match key {
+                    CborValue::Text(key) => Some(format!("{}: {}", key, event_type(value))),
+                    _ => None,
+                })
+                .collect();
+            format!("{{ {} }}", fields.join(", "))
+        }
+        _ => String::from("any"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translate_types() {
+        assert_eq!(lua_type("usize"), "integer");
+        assert_eq!(lua_type("Option<Vec<String>>"), "string[]");
+        assert_eq!(lua_type("Vec<Option<f32>>"), "number[]");
+        assert_eq!(
+            lua_type("HashMap<String,Vec<u8>>"),
+            "table<string, integer[]>"
+        );
+        assert_eq!(lua_type("image::ImageFormat"), "ImageFormat");
+        assert_eq!(lua_type("Option<Function>"), "function");
+        assert_eq!(lua_type("fun(): LayoutData"), "fun(): LayoutData");
+    }
+
+    #[test]
+    fn generate_definitions() {
+        let lua = Lua::new();
+        let events = vec![CborValue::Map(vec![(
+            "CLOCK".into(),
+            CborValue::Map(vec![
+                ("Hours".into(), 12.into()),
+                ("Month Names".into(), CborValue::Array(vec!["Jan".into()])),
+            ]),
+        )])];
+
+        let definitions = generate(&lua, &events).unwrap();
+
+        assert!(definitions.contains("---@class Text\n"));
+        assert!(definitions.contains("---@field Text fun(value: Text): Widget\n"));
+        assert!(definitions.contains("---@field run_on? (EventKey|Regex|string)[]\n"));
+        assert!(definitions.contains("---@field with_layout fun(self: ScreenBuilder"));
+        assert!(definitions.contains("---@field as_millis fun(...): any\n"));
+        assert!(definitions.contains("---@field Hours integer\n"));
+        assert!(definitions.contains("---@field [\"Month Names\"] string[]\n"));
+        assert!(definitions.contains("---@type Size\nSCREEN = nil\n"));
+    }
+
+    #[test]
+    fn signatures_match_registered_functions() {
+        let lua = Lua::new();
+        let definitions = collect(&lua).unwrap();
+
+        // Signatures of functions that no longer exist would silently add them back
+        for (class, member, ty) in SIGNATURES {
+            if ty.starts_with("fun(") {
+                let fields = &definitions.classes[*class].fields;
+                assert!(
+                    fields.contains_key(*member),
+                    "Unknown function '{class}.{member}'"
+                );
+            }
+        }
+    }
+
+    // Calls of every function with a signature, evaluated with the objects created in `SETUP`
+    const CALLS: &[(&str, &str, &str)] = &[
+        ("Duration", "from_hours", "Duration.from_hours(1)"),
+        ("Duration", "from_micros", "Duration.from_micros(1)"),
+        ("Duration", "from_millis", "Duration.from_millis(1)"),
+        ("Duration", "from_mins", "Duration.from_mins(1)"),
+        ("Duration", "from_nanos", "Duration.from_nanos(1)"),
+        ("Duration", "from_secs", "Duration.from_secs(1)"),
+        (
+            "Events",
+            "register",
+            "Events.register('TEST', function() end)",
+        ),
+        ("Events", "send", "Events.send('TEST', 1)"),
+        ("Events", "unregister", "Events.unregister(handle)"),
+        ("Menu", "new", "Menu.new(options)"),
+        ("Menu", "selected", "menu:selected()"),
+        ("Menu", "widgets", "menu:widgets(area)"),
+        ("Regex", "matches", "Regex.new('a'):matches('abc')"),
+        ("Regex", "new", "Regex.new('a')"),
+        ("ScreenBuilder", "new", "ScreenBuilder.new('A')"),
+        (
+            "ScreenBuilder",
+            "register",
+            "ScreenBuilder.new('B'):register()",
+        ),
+        (
+            "ScreenBuilder",
+            "with_carousel",
+            "ScreenBuilder.new('A'):with_carousel({ interval = Duration.from_secs(1) })",
+        ),
+        (
+            "ScreenBuilder",
+            "with_layout",
+            "ScreenBuilder.new('A'):with_layout(layout)",
+        ),
+        (
+            "ScreenBuilder",
+            "with_layout_group",
+            "ScreenBuilder.new('A'):with_layout_group({ layout })",
+        ),
+        (
+            "ScreenBuilder",
+            "with_layout_group_previous",
+            "ScreenBuilder.new('A'):with_layout_group_previous({ 'KEY(A)' })",
+        ),
+        (
+            "ScreenBuilder",
+            "with_layout_group_toggle",
+            "ScreenBuilder.new('A'):with_layout_group_toggle({ 'KEY(A)' })",
+        ),
+        (
+            "ScreenBuilder",
+            "with_screensaver",
+            "ScreenBuilder.new('A'):with_screensaver(function() return { widgets = {} } end)",
+        ),
+        (
+            "ScreenBuilder",
+            "with_zone",
+            "ScreenBuilder.new('A'):with_zone({ position = { x = 0, y = 0 }, size = SCREEN })",
+        ),
+        (
+            "ScreenBuilder",
+            "with_transition",
+            "ScreenBuilder.new('A'):with_transition({ effect = TransitionEffect.Wipe })",
+        ),
+        ("Screens", "current", "screens:current()"),
+        ("Screens", "select", "screens:select(1)"),
+        (
+            "Shortcuts",
+            "register",
+            "Shortcuts:register({ 'KEY(A)' }, function() end)",
+        ),
+        ("StorageNamespace", "delete", "Storage:delete('key')"),
+        ("StorageNamespace", "get", "Storage:get('key')"),
+        ("StorageNamespace", "namespace", "Storage:namespace('name')"),
+        ("StorageNamespace", "set", "Storage:set('key', 1)"),
+    ];
+
+    const LUA_BUILTINS: &[&str] = &[
+        "assert",
+        "coroutine",
+        "getmetatable",
+        "ipairs",
+        "math",
+        "next",
+        "os",
+        "pairs",
+        "pcall",
+        "print",
+        "string",
+        "table",
+        "tonumber",
+        "tostring",
+        "type",
+        "utf8",
+    ];
+
+    const SETUP: &str = r#"
+        SCREEN = { width = 8, height = 8 }
+        handle = Events.register('TEST', function() end)
+        options = { name = 'MENU', entries = { { name = 'A' }, { name = 'B' } } }
+        menu = Menu.new(options)
+        area = { position = { x = 0, y = 0 }, size = SCREEN }
+        layout = { layout = function() return { widgets = {} } end, run_on = { 'TEST' } }
+        screens = ScreenBuilder.new('A'):with_layout_group({ layout }):register()
+    "#;
+
+    fn return_type(signature: &str) -> Option<&str> {
+        let mut depth = 0;
+        for (index, c) in signature.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return signature[index + 1..].strip_prefix(": ");
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn matches_type(value: &Value, ty: &str) -> bool {
+        ty.split('|').any(|ty| match (ty, value) {
+            ("any", _) => true,
+            ("nil", Value::Nil) => true,
+            ("boolean", Value::Boolean(_)) => true,
+            ("integer", Value::Integer(_)) => true,
+            ("number", Value::Integer(_) | Value::Number(_)) => true,
+            ("string", Value::String(_)) => true,
+            (ty, Value::Table(_)) => ty.ends_with("[]"),
+            (ty, Value::UserData(user_data)) => user_data
+                .type_name()
+                .is_ok_and(|name| lua_type(&name.to_string_lossy()) == ty),
+            _ => false,
+        })
+    }
+
+    #[test]
+    fn signatures_match_return_types() {
+        use crate::common::user_data::{UserDataRef, set_unique_user_data};
+        use crate::devices::device::Size;
+        use crate::devices::virtual_device::virtual_device::load_virtual_devices;
+        use crate::script_handler::script_handler::ScriptHandler;
+        use crate::script_handler::storage::Storage;
+
+        let lua = Lua::new();
+        let size = Size {
+            width: 8,
+            height: 8,
+        };
+        load_virtual_devices(&lua, [("A", size), ("B", size)]);
+        set_unique_user_data(
+            &lua,
+            Storage::new(std::env::temp_dir().join("omni-led-signatures.json")),
+        );
+        ScriptHandler::load(&lua, String::new(), std::env::temp_dir()).unwrap();
+
+        // Calls are made in the same sandbox as user scripts
+        let env = UserDataRef::<ScriptHandler>::load(&lua)
+            .get()
+            .environment()
+            .clone();
+
+        // Every global of the sandbox needs a definition, unless it's a part of the standard library
+        let definitions = collect(&lua).unwrap();
+        for pair in env.pairs::<String, Value>() {
+            let (name, _) = pair.unwrap();
+            assert!(
+                LUA_BUILTINS.contains(&name.as_str())
+                    || GLOBALS.iter().any(|(global, _)| *global == name)
+                    || definitions
+                        .classes
+                        .get(&name)
+                        .is_some_and(|class| class.global),
+                "No definition for global '{name}'"
+            );
+        }
+
+        lua.load(SETUP).set_environment(env.clone()).exec().unwrap();
+
+        for (class, member, signature) in SIGNATURES {
+            let Some(expected) = signature
+                .starts_with("fun(")
+                .then(|| return_type(signature))
+            else {
+                continue;
+            };
+
+            let call = CALLS
+                .iter()
+                .find(|(call_class, call_member, _)| call_class == class && call_member == member)
+                .map(|(_, _, call)| call)
+                .unwrap_or_else(|| panic!("No test call for '{class}.{member}'"));
+
+            let value: Value = lua
+                .load(format!("return {call}"))
+                .set_environment(env.clone())
+                .eval()
+                .unwrap_or_else(|err| panic!("Failed to call '{class}.{member}': {err}"));
+            assert!(
+                matches_type(&value, expected.unwrap_or("nil")),
+                "'{class}.{member}' returned {value:?}, expected '{}'",
+                expected.unwrap_or("nil")
+            );
+        }
+    }
+}
@@ -14,7 +14,7 @@ pub enum ConfigType {
 }
 
 impl ConfigType {
-    fn get_filename(&self) -> &'static str {
+    pub fn get_filename(&self) -> &'static str {
         match self {
             ConfigType::Devices => "devices.lua",
             ConfigType::Plugins => "plugins.lua",
@@ -0,0 +1,203 @@
+use log::{debug, warn};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::constants::config::ConfigType;
+use crate::constants::constants::Constants;
+use crate::events::events::Events;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const DEBOUNCE_TIME: Duration = Duration::from_millis(500);
+
+type Snapshot = HashMap<PathBuf, SystemTime>;
+
+// Watcher runs on its own thread, it is started and stopped whenever the `watch_config` setting changes
+#[derive(Default)]
+pub struct ConfigWatcher {
+    thread: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+impl ConfigWatcher {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        match (enabled, self.thread.is_some()) {
+            (true, false) => {
+                let running = Arc::new(AtomicBool::new(true));
+                let thread = std::thread::spawn({
+                    let running = running.clone();
+                    move || watch_config(&running)
+                });
+                self.thread = Some((running, thread));
+            }
+            (false, true) => self.stop(),
+            _ => {}
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some((running, thread)) = self.thread.take() {
+            running.store(false, Ordering::Relaxed);
+            _ = thread.join();
+            debug!("Stopped watching config");
+        }
+    }
+}
+
+fn watch_config(running: &AtomicBool) {
+    let config_dir = Constants::config_dir();
+    let mut snapshot = take_snapshot(&config_dir);
+    let mut pending = HashSet::new();
+    let mut last_change = Instant::now();
+
+    debug!("Watching '{}' for changes", config_dir.display());
+
+    while running.load(Ordering::Relaxed) {
+        std::thread::sleep(POLL_INTERVAL);
+
+        let current = take_snapshot(&config_dir);
+        let changed = changed_files(&snapshot, &current);
+        if !changed.is_empty() {
+            pending.extend(changed);
+            last_change = Instant::now();
+        }
+        snapshot = current;
+
+        // Editors tend to write files in multiple steps, wait until things settle down
+        if !pending.is_empty() && last_change.elapsed() >= DEBOUNCE_TIME {
+            queue_reloads(&config_dir, std::mem::take(&mut pending));
+        }
+    }
+}
+
+fn take_snapshot(dir: &Path) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    collect_files(dir, &mut snapshot);
+    snapshot
+}
+
+fn collect_files(dir: &Path, snapshot: &mut Snapshot) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, snapshot);
+        } else if path.extension().is_some_and(|extension| extension == "lua")
+            && let Ok(modified) = entry.metadata().and_then(|metadata| metadata.modified())
+        {
+            snapshot.insert(path, modified);
+        }
+    }
+}
+
+fn changed_files(previous: &Snapshot, current: &Snapshot) -> HashSet<PathBuf> {
+    let modified = current
+        .iter()
+        .filter(|(path, modified)| previous.get(*path) != Some(modified))
+        .map(|(path, _)| path.clone());
+    let removed = previous
+        .keys()
+        .filter(|path| !current.contains_key(*path))
+        .cloned();
+
+    modified.chain(removed).collect()
+}
+
+fn queue_reloads(config_dir: &Path, changed: HashSet<PathBuf>) {
+    let mut reload_devices = false;
+    let mut reload_scripts = false;
+    let mut reload_settings = false;
+
+    for path in changed {
+        debug!("Detected changes in '{}'", path.display());
+
+        match config_type(config_dir, &path) {
+            ConfigType::Devices => reload_devices = true,
+            ConfigType::Scripts => reload_scripts = true,
+            ConfigType::Settings => reload_settings = true,
+            other => warn!(
+                "Changes to '{}' will be applied after restart",
+                other.get_filename()
+            ),
+        }
+    }
+
+    // Scripts depend on both devices and settings, so they are reloaded last
+    if reload_settings {
+        Events::reload_settings();
+    }
+    if reload_devices {
+        Events::reload_devices();
+    }
+    if reload_scripts {
+        Events::reload_scripts();
+    }
+}
+
+fn config_type(config_dir: &Path, path: &Path) -> ConfigType {
+    // Anything that is not one of the main config files can only be a module used by user scripts
+    match path.strip_prefix(config_dir).ok().and_then(Path::to_str) {
+        Some("devices.lua") => ConfigType::Devices,
+        Some("plugins.lua") => ConfigType::Plugins,
+        Some("settings.lua") => ConfigType::Settings,
+        _ => ConfigType::Scripts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_modified_added_and_removed_files() {
+        let now = SystemTime::now();
+        let later = now + Duration::from_secs(1);
+
+        let previous = Snapshot::from([
+            (PathBuf::from("unchanged.lua"), now),
+            (PathBuf::from("modified.lua"), now),
+            (PathBuf::from("removed.lua"), now),
+        ]);
+        let current = Snapshot::from([
+            (PathBuf::from("unchanged.lua"), now),
+            (PathBuf::from("modified.lua"), later),
+            (PathBuf::from("added.lua"), now),
+        ]);
+
+        let changed = changed_files(&previous, &current);
+        let expected = HashSet::from([
+            PathBuf::from("modified.lua"),
+            PathBuf::from("added.lua"),
+            PathBuf::from("removed.lua"),
+        ]);
+        assert_eq!(changed, expected);
+    }
+
+    #[test]
+    fn classifies_config_files() {
+        let dir = PathBuf::from("config");
+
+        assert!(matches!(
+            config_type(&dir, &dir.join("devices.lua")),
+            ConfigType::Devices
+        ));
+        assert!(matches!(
+            config_type(&dir, &dir.join("settings.lua")),
+            ConfigType::Settings
+        ));
+        assert!(matches!(
+            config_type(&dir, &dir.join("scripts.lua")),
+            ConfigType::Scripts
+        ));
+        assert!(matches!(
+            config_type(&dir, &dir.join("lib").join("devices.lua")),
+            ConfigType::Scripts
+        ));
+    }
+}
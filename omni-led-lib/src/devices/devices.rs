@@ -14,23 +14,74 @@ use crate::devices::steelseries_engine::steelseries_engine_device::SteelSeriesEn
 use crate::devices::usb_device;
 use crate::devices::usb_device::hid_device::HidDeviceSettings;
 use crate::devices::usb_device::raw_usb_device::RawUsbDeviceSettings;
+use crate::devices::virtual_device::virtual_device::{VirtualDevice, VirtualDeviceSettings};
 
 type Constructor = fn(&Lua, Value) -> mlua::Result<Box<dyn Device>>;
 
+const VIRTUAL_DEVICE: &str = "virtual_device";
+
 #[derive(LuaName)]
 pub struct Devices {
     devices: HashMap<String, DeviceEntry>,
     constructors: HashMap<String, Constructor>,
+    environment: Table,
 }
 
 impl Devices {
     pub fn load(lua: &Lua, config: String) {
         let (constructors, env) = Self::create_loaders(lua);
         usb_device::transform::load_common_functions(lua, &env);
-        set_unique_user_data(lua, Self::new(constructors));
+        set_unique_user_data(lua, Self::new(constructors, env.clone()));
         load_config(lua, ConfigType::Devices, &config, env).unwrap();
     }
 
+    // Returns names of devices that are currently in use, but their configuration has changed
+    pub fn reload_config(lua: &Lua, config: String) -> mlua::Result<Vec<String>> {
+        debug!("Reloading devices");
+
+        let (previous, env) = {
+            let mut this = UserDataRef::<Self>::load(lua);
+            let mut this = this.get_mut();
+            (std::mem::take(&mut this.devices), this.environment.clone())
+        };
+
+        // Devices must not be borrowed here, loaders call back into it
+        let result = load_config(lua, ConfigType::Devices, &config, env);
+
+        let mut this = UserDataRef::<Self>::load(lua);
+        let mut this = this.get_mut();
+        if let Err(err) = result {
+            this.devices = previous;
+            return Err(err);
+        }
+
+        let mut changed = Vec::new();
+        for (name, old_entry) in previous {
+            if old_entry.available {
+                continue;
+            }
+
+            match this.devices.get_mut(&name) {
+                Some(new_entry) if new_entry.initializer == old_entry.initializer => {
+                    new_entry.available = false;
+                }
+                _ => changed.push(name),
+            }
+        }
+        Ok(changed)
+    }
+
+    // Virtual devices can't be configured from `devices.lua`, they are only used for headless rendering
+    pub fn add_virtual_device(
+        &mut self,
+        lua: &Lua,
+        settings: VirtualDeviceSettings,
+    ) -> mlua::Result<()> {
+        let name = settings.name.clone();
+        let settings = Value::UserData(lua.create_userdata(settings)?);
+        self.add_configuration(name, VIRTUAL_DEVICE.to_string(), settings)
+    }
+
     pub fn load_device(&mut self, lua: &Lua, name: String) -> mlua::Result<Box<dyn Device>> {
         let entry = self.devices.entry(name.clone());
         match entry {
@@ -71,10 +122,11 @@ impl Devices {
         Ok(())
     }
 
-    fn new(constructors: HashMap<String, Constructor>) -> Self {
+    fn new(constructors: HashMap<String, Constructor>, environment: Table) -> Self {
         Self {
             devices: HashMap::new(),
             constructors,
+            environment,
         }
     }
 
@@ -97,6 +149,10 @@ impl Devices {
             env.set(name, loader).unwrap();
         }
 
+        let virtual_device: Constructor =
+            |lua, settings| Ok(Box::new(VirtualDevice::init(lua, settings)?));
+        constructors.insert(VIRTUAL_DEVICE.to_string(), virtual_device);
+
         (constructors, env)
     }
 
@@ -161,6 +217,7 @@ impl Devices {
 
                 entry.insert(DeviceEntry {
                     initializer: Initializer {
+                        kind,
                         settings,
                         constructor,
                     },
@@ -176,11 +233,78 @@ impl Devices {
 impl UserData for Devices {}
 
 struct Initializer {
+    kind: String,
     settings: Value,
     constructor: Constructor,
 }
 
+impl PartialEq for Initializer {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && values_equal(&self.settings, &other.settings)
+    }
+}
+
 struct DeviceEntry {
     initializer: Initializer,
     available: bool,
 }
+
+fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Table(left), Value::Table(right)) => {
+            let contains_all = |left: &Table, right: &Table| {
+                left.pairs::<Value, Value>().all(|pair| match pair {
+                    Ok((key, value)) => right
+                        .raw_get::<Value>(key)
+                        .is_ok_and(|other| values_equal(&value, &other)),
+                    Err(_) => false,
+                })
+            };
+            left == right || (contains_all(left, right) && contains_all(right, left))
+        }
+        // Functions and user data are compared by reference, configs that use them will
+        // reopen the device unless the same object is reused
+        (left, right) => left == right,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_settings() {
+        let lua = Lua::new();
+        let function = lua.create_function(|_, _: ()| Ok(())).unwrap();
+        let make_settings = |name: &str, width: i64, function: Function| {
+            let size = lua.create_table().unwrap();
+            size.set("width", width).unwrap();
+            let settings = lua.create_table().unwrap();
+            settings.set("name", name).unwrap();
+            settings.set("screen_size", size).unwrap();
+            settings.set("transform", function).unwrap();
+            Value::Table(settings)
+        };
+
+        let base = make_settings("device", 128, function.clone());
+        assert!(values_equal(&base, &base));
+        assert!(values_equal(
+            &base,
+            &make_settings("device", 128, function.clone())
+        ));
+        assert!(!values_equal(
+            &base,
+            &make_settings("device", 64, function.clone())
+        ));
+        assert!(!values_equal(
+            &base,
+            &make_settings("other", 128, function.clone())
+        ));
+
+        let other_function = lua.create_function(|_, _: ()| Ok(())).unwrap();
+        assert!(!values_equal(
+            &base,
+            &make_settings("device", 128, other_function)
+        ));
+    }
+}
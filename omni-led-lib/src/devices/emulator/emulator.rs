@@ -34,7 +34,7 @@ impl Device for Emulator {
     }
 
     fn memory_layout(&mut self, _lua: &Lua) -> mlua::Result<MemoryLayout> {
-        Ok(MemoryLayout::BytePerPixel)
+        Ok(MemoryLayout::Rgb888)
     }
 }
 
@@ -1,6 +1,7 @@
 pub mod device;
 pub mod devices;
 
-mod emulator;
-mod steelseries_engine;
-mod usb_device;
+pub(crate) mod emulator;
+pub(crate) mod steelseries_engine;
+pub(crate) mod usb_device;
+pub mod virtual_device;
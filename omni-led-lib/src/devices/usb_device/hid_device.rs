@@ -103,10 +103,13 @@ impl UserData for HidDeviceSettings {}
 #[derive(FromLuaValue, Clone)]
 pub struct HidSettings {
     #[mlua(transform = from_hex)]
+    #[mlua(lua_type = "string")]
     pub vendor_id: u16,
     #[mlua(transform = from_hex)]
+    #[mlua(lua_type = "string")]
     pub product_id: u16,
     #[mlua(transform = from_hex)]
+    #[mlua(lua_type = "string")]
     pub interface: u8,
 }
 
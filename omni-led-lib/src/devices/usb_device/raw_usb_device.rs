@@ -152,20 +152,28 @@ impl UserData for RawUsbDeviceSettings {}
 #[derive(FromLuaValue, Clone)]
 pub struct RawUsbSettings {
     #[mlua(transform = from_hex)]
+    #[mlua(lua_type = "string")]
     pub vendor_id: u16,
     #[mlua(transform = from_hex)]
+    #[mlua(lua_type = "string")]
     pub product_id: u16,
     #[mlua(transform = from_hex)]
+    #[mlua(lua_type = "string")]
     pub interface: u8,
     #[mlua(transform = from_hex)]
+    #[mlua(lua_type = "string")]
     pub alternate_setting: u8,
     #[mlua(transform = from_hex)]
+    #[mlua(lua_type = "string")]
     pub request_type: u8,
     #[mlua(transform = from_hex)]
+    #[mlua(lua_type = "string")]
     pub request: u8,
     #[mlua(transform = from_hex)]
+    #[mlua(lua_type = "string")]
     pub value: u16,
     #[mlua(transform = from_hex)]
+    #[mlua(lua_type = "string")]
     pub index: u16,
 }
 
@@ -1,10 +1,11 @@
-use mlua::{Lua, Table};
+use mlua::{Function, Lua, Table};
 use omni_led_derive::FromLuaValue;
+use std::collections::HashMap;
 
 use crate::devices::device::Buffer;
 
-#[derive(Clone, FromLuaValue)]
-struct ExtraBytes {
+#[derive(Clone, PartialEq, Eq, Hash, FromLuaValue)]
+pub(crate) struct ExtraBytes {
     #[mlua(default)]
     prepend: Vec<u8>,
     #[mlua(default)]
@@ -12,19 +13,31 @@ struct ExtraBytes {
 }
 
 pub fn load_common_functions(lua: &Lua, env: &Table) {
+    // Same arguments return the same function, this way reloaded device configs compare equal
+    let mut cache: HashMap<ExtraBytes, Function> = HashMap::new();
+
     let transform_data = lua
-        .create_function(|lua, extra: ExtraBytes| {
+        .create_function_mut(move |lua, extra: ExtraBytes| {
+            if let Some(function) = cache.get(&extra) {
+                return Ok(function.clone());
+            }
+
             let extra_total = extra.prepend.len() + extra.append.len();
+            let function = lua.create_function({
+                let extra = extra.clone();
+                move |_, buffer: Buffer| {
+                    let slice = buffer.bytes();
 
-            lua.create_function(move |_, buffer: Buffer| {
-                let slice = buffer.bytes();
+                    let mut bytes = Vec::with_capacity(slice.len() + extra_total);
+                    bytes.extend_from_slice(&extra.prepend);
+                    bytes.extend_from_slice(slice);
+                    bytes.extend_from_slice(&extra.append);
+                    Ok(bytes)
+                }
+            })?;
 
-                let mut bytes = Vec::with_capacity(slice.len() + extra_total);
-                bytes.extend_from_slice(&extra.prepend);
-                bytes.extend_from_slice(slice);
-                bytes.extend_from_slice(&extra.append);
-                Ok(bytes)
-            })
+            cache.insert(extra, function.clone());
+            Ok(function)
         })
         .unwrap();
 
@@ -0,0 +1,185 @@
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame, GrayImage, ImageReader, Luma, Rgb, RgbImage};
+use mlua::{Lua, UserData, Value};
+use std::cell::RefCell;
+use std::fs::File;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::devices::device::{Buffer, Device, MemoryLayout, Size};
+
+// Device without any hardware behind it, that keeps the last presented frame in memory
+pub struct VirtualDevice {
+    settings: VirtualDeviceSettings,
+}
+
+impl Device for VirtualDevice {
+    fn init(_lua: &Lua, settings: Value) -> mlua::Result<Self> {
+        let settings = match settings {
+            Value::UserData(user_data) => user_data.borrow::<VirtualDeviceSettings>()?.clone(),
+            other => {
+                return Err(mlua::Error::runtime(format!(
+                    "Expected virtual device settings, got '{}'",
+                    other.type_name()
+                )));
+            }
+        };
+        Ok(Self { settings })
+    }
+
+    fn size(&mut self, _lua: &Lua) -> mlua::Result<Size> {
+        Ok(self.settings.size)
+    }
+
+    fn update(&mut self, _lua: &Lua, buffer: Buffer) -> mlua::Result<()> {
+        *self.settings.frame.borrow_mut() = Some(buffer);
+        Ok(())
+    }
+
+    fn name(&mut self, _lua: &Lua) -> mlua::Result<String> {
+        Ok(self.settings.name.clone())
+    }
+
+    fn memory_layout(&mut self, _lua: &Lua) -> mlua::Result<MemoryLayout> {
+        Ok(self.settings.memory_layout)
+    }
+}
+
+#[derive(Clone)]
+pub struct VirtualDeviceSettings {
+    pub name: String,
+    pub size: Size,
+    pub memory_layout: MemoryLayout,
+    pub frame: Rc<RefCell<Option<Buffer>>>,
+}
+
+impl UserData for VirtualDeviceSettings {}
+
+// Loads everything that devices depend on, with a virtual device added for every name and size
+#[cfg(test)]
+pub fn load_virtual_devices<const N: usize>(
+    lua: &Lua,
+    devices: [(&str, Size); N],
+) -> [Rc<RefCell<Option<Buffer>>>; N] {
+    use crate::common::common::load_internal_functions;
+    use crate::devices::devices::Devices;
+    use crate::events::events::Events;
+    use crate::events::shortcuts::Shortcuts;
+    use crate::logging::logger::{Log, NoopLogHandle};
+    use crate::settings::settings::Settings;
+
+    load_internal_functions(lua);
+    Log::load(lua, NoopLogHandle);
+    Settings::load(lua, String::from("Settings {}"));
+    Events::load(lua);
+    Shortcuts::load(lua);
+    Devices::load(lua, String::new());
+
+    devices.map(|(name, size)| add_virtual_device(lua, name, size))
+}
+
+// Adds a virtual device to already loaded devices, returning the frame presented on it
+#[cfg(test)]
+pub fn add_virtual_device(lua: &Lua, name: &str, size: Size) -> Rc<RefCell<Option<Buffer>>> {
+    use crate::common::user_data::UserDataRef;
+    use crate::devices::devices::Devices;
+
+    let frame = Rc::new(RefCell::new(None));
+    let settings = VirtualDeviceSettings {
+        name: name.to_string(),
+        size,
+        memory_layout: MemoryLayout::BitPerPixel,
+        frame: Rc::clone(&frame),
+    };
+    UserDataRef::<Devices>::load(lua)
+        .get_mut()
+        .add_virtual_device(lua, settings)
+        .unwrap();
+    frame
+}
+
+// Color buffers are saved as RGB images, all others as grayscale
+pub fn to_image(buffer: &Buffer) -> DynamicImage {
+    let (width, height) = (buffer.width() as u32, buffer.height() as u32);
+    match buffer.is_color() {
+        true => DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let color = buffer.get_color(x as usize, y as usize);
+            Rgb([color.r, color.g, color.b])
+        })),
+        false => DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+            Luma([buffer.get_level(x as usize, y as usize)])
+        })),
+    }
+}
+
+pub fn save_gif(
+    path: &Path,
+    frames: &[DynamicImage],
+    frame_time: Duration,
+) -> image::ImageResult<()> {
+    let mut encoder = GifEncoder::new(File::create(path)?);
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    let delay = Delay::from_saturating_duration(frame_time);
+    encoder.encode_frames(
+        frames
+            .iter()
+            .map(|frame| Frame::from_parts(frame.to_rgba8(), 0, 0, delay)),
+    )
+}
+
+// Returns the number of pixels that differ from the reference image, either can be grayscale or RGB
+pub fn compare(frame: &DynamicImage, reference: &Path) -> image::ImageResult<usize> {
+    let frame = frame.to_rgb8();
+    let reference = ImageReader::open(reference)?.decode()?.into_rgb8();
+    if reference.dimensions() != frame.dimensions() {
+        let pixels = |(width, height): (u32, u32)| (width * height) as usize;
+        return Ok(pixels(frame.dimensions()).max(pixels(reference.dimensions())));
+    }
+
+    let differences = frame
+        .pixels()
+        .zip(reference.pixels())
+        .filter(|(pixel, reference)| pixel != reference)
+        .count();
+    Ok(differences)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script_handler::script_data_types::Color;
+
+    #[test]
+    fn detects_pixel_differences() {
+        let size = Size {
+            width: 4,
+            height: 2,
+        };
+        let mut buffer = Buffer::new(size, MemoryLayout::BitPerPixel);
+        buffer.set_pixel(1, 1, true);
+
+        let path = std::env::temp_dir().join("omni-led-virtual-device-reference.png");
+        to_image(&buffer).save(&path).unwrap();
+        assert_eq!(compare(&to_image(&buffer), &path).unwrap(), 0);
+
+        buffer.set_pixel(0, 0, true);
+        buffer.set_pixel(1, 1, false);
+        assert_eq!(compare(&to_image(&buffer), &path).unwrap(), 2);
+    }
+
+    #[test]
+    fn saves_color_buffers_as_rgb() {
+        let size = Size {
+            width: 2,
+            height: 1,
+        };
+        let mut buffer = Buffer::new(size, MemoryLayout::Rgb888);
+        buffer.set_color(1, 0, Color { r: 255, g: 0, b: 0 });
+
+        let image = to_image(&buffer).into_rgb8();
+        assert_eq!(image.get_pixel(0, 0), &Rgb([0, 0, 0]));
+        assert_eq!(image.get_pixel(1, 0), &Rgb([255, 0, 0]));
+    }
+}
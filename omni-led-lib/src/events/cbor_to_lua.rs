@@ -3,9 +3,11 @@ use mlua::{Lua, Table, Value as LuaValue};
 use omni_led_api::types::{Image, Tagged};
 use std::hash::{DefaultHasher, Hash, Hasher};
 
+use crate::script_handler::dependencies::unwrap_proxy;
 use crate::script_handler::script_data_types::ImageData;
 
 const CLEANUP_ENTRIES: &str = "__cleanup_entries";
+const MAX_DEPTH: usize = 32;
 
 pub fn get_cleanup_entries_metatable(table: &Table) -> mlua::Result<Option<Table>> {
     match table.metatable() {
@@ -93,6 +95,76 @@ pub fn cbor_to_lua_value(lua: &Lua, value: CborValue) -> mlua::Result<LuaValue>
     }
 }
 
+pub fn lua_to_cbor_value(value: LuaValue) -> mlua::Result<CborValue> {
+    lua_to_cbor_value_impl(value, 0)
+}
+
+fn lua_to_cbor_value_impl(value: LuaValue, depth: usize) -> mlua::Result<CborValue> {
+    if depth > MAX_DEPTH {
+        return Err(mlua::Error::runtime(
+            "Value is nested too deep, it might contain a reference cycle",
+        ));
+    }
+
+    match value {
+        LuaValue::Nil => Ok(CborValue::Null),
+        LuaValue::Boolean(bool) => Ok(CborValue::Bool(bool)),
+        LuaValue::Integer(integer) => Ok(CborValue::Integer(integer.into())),
+        LuaValue::Number(float) => Ok(CborValue::Float(float)),
+        LuaValue::String(string) => match string.to_str() {
+            Ok(string) => Ok(CborValue::Text(string.to_string())),
+            Err(_) => Ok(CborValue::Bytes(string.as_bytes().to_vec())),
+        },
+        LuaValue::Table(table) => table_to_cbor_value(table, depth),
+        other => Err(mlua::Error::runtime(format!(
+            "Unexpected value of type '{}'",
+            other.type_name()
+        ))),
+    }
+}
+
+fn table_to_cbor_value(table: Table, depth: usize) -> mlua::Result<CborValue> {
+    let table = unwrap_proxy(table)?;
+    let cleanup_entries = get_cleanup_entries_metatable(&table)?;
+    let length = table.raw_len();
+
+    // Marked tables are always maps, this keeps the decoded maps intact, even if they are empty
+    if cleanup_entries.is_none() && length == table.pairs::<LuaValue, LuaValue>().count() {
+        let values = table
+            .sequence_values::<LuaValue>()
+            .map(|value| lua_to_cbor_value_impl(value?, depth + 1))
+            .collect::<mlua::Result<Vec<_>>>()?;
+        return Ok(CborValue::Array(values));
+    }
+
+    let mut items = Vec::new();
+    for pair in table.pairs::<LuaValue, LuaValue>() {
+        let (key, value) = pair?;
+        items.push((text_key(key)?, lua_to_cbor_value_impl(value, depth + 1)?));
+    }
+
+    if let Some(cleanup_entries) = cleanup_entries {
+        for pair in cleanup_entries.pairs::<LuaValue, LuaValue>() {
+            let (key, _) = pair?;
+            if table.raw_get::<LuaValue>(key.clone())?.is_nil() {
+                items.push((text_key(key)?, CborValue::Null));
+            }
+        }
+    }
+
+    Ok(CborValue::Map(items))
+}
+
+fn text_key(key: LuaValue) -> mlua::Result<CborValue> {
+    match key {
+        LuaValue::String(key) => Ok(CborValue::Text(key.to_str()?.to_string())),
+        other => Err(mlua::Error::runtime(format!(
+            "Expected string key, got '{}'",
+            other.type_name()
+        ))),
+    }
+}
+
 fn hash<T: Hash>(t: &T) -> u64 {
     let mut s = DefaultHasher::new();
     t.hash(&mut s);
@@ -205,4 +277,35 @@ mod tests {
         assert_eq!(result.get::<bool>("c").unwrap(), true);
         assert_eq!(result.get::<f64>("d").unwrap(), 1.23);
     }
+
+    #[test]
+    fn convert_back_marked_table() {
+        let lua = Lua::new();
+        let value = cbor!({
+            "a" => [1, 2],
+            "b" => null,
+            "c" => {},
+        })
+        .unwrap();
+
+        let result = cbor_to_lua_value(&lua, value.clone()).unwrap();
+        let mut result = lua_to_cbor_value(result).unwrap().into_map().unwrap();
+        result.sort_by_key(|(key, _)| key.as_text().unwrap().to_string());
+
+        assert_eq!(CborValue::Map(result), value);
+    }
+
+    #[test]
+    fn convert_back_invalid() {
+        let lua = Lua::new();
+        for code in [
+            "return function() end",
+            "return { [true] = 1 }",
+            "return { 1, 2, x = 3 }",
+            "local t = {}; t.t = t; return t",
+        ] {
+            let value: LuaValue = lua.load(code).eval().unwrap();
+            assert!(lua_to_cbor_value(value).is_err(), "Accepted '{code}'");
+        }
+    }
 }
@@ -1,13 +1,17 @@
-use log::warn;
+use log::{error, warn};
 use mlua::{ErrorContext, Lua, Value};
 
+use crate::common::user_data::UserDataRef;
 use crate::constants::config::{ConfigType, read_config};
+use crate::devices::devices::Devices;
 use crate::events::cbor_to_lua::{cbor_to_lua_value, get_cleanup_entries_metatable};
 use crate::events::event_handle::EventHandle;
 use crate::events::event_queue::Event;
 use crate::events::events::EventEntry;
+use crate::events::shortcuts::Shortcuts;
 use crate::keyboard::keyboard::{KeyboardEvent, KeyboardEventEventType};
 use crate::script_handler::script_handler::ScriptHandler;
+use crate::settings::settings::Settings;
 
 pub struct Dispatcher {
     entries: Vec<EventEntry>,
@@ -33,10 +37,22 @@ impl Dispatcher {
             Event::Keyboard(event) => self.dispatch_keyboard_event(lua, event),
             Event::Register(event_entry) => self.register(event_entry),
             Event::Unregister(event_handle) => self.unregister(event_handle),
+            Event::ReloadDevices => {
+                if let Err(err) = Self::reload_devices(lua) {
+                    error!("Failed to reload devices: {err}");
+                }
+                Ok(())
+            }
             Event::ReloadScripts => {
                 self.clear_non_persistent();
-                let config = read_config(ConfigType::Scripts).unwrap();
-                ScriptHandler::reload_config(lua, config)
+                ScriptHandler::reload(lua);
+                Ok(())
+            }
+            Event::ReloadSettings => {
+                if let Err(err) = Self::reload_settings(lua) {
+                    error!("Failed to reload settings: {err}");
+                }
+                Ok(())
             }
             Event::Script(script_event) => {
                 self.dispatch_application_event(Some(&script_event.event), script_event.value, None)
@@ -44,6 +60,27 @@ impl Dispatcher {
         }
     }
 
+    fn reload_devices(lua: &Lua) -> mlua::Result<()> {
+        let config = read_config(ConfigType::Devices)?;
+        let changed = Devices::reload_config(lua, config)?;
+
+        let mut script_handler = UserDataRef::<ScriptHandler>::load(lua);
+        script_handler.get_mut().reopen_devices(lua, &changed)
+    }
+
+    fn reload_settings(lua: &Lua) -> mlua::Result<()> {
+        let config = read_config(ConfigType::Settings)?;
+        Settings::reload(lua, config)?;
+
+        let mut shortcuts = UserDataRef::<Shortcuts>::load(lua);
+        shortcuts.get_mut().load_settings(lua);
+
+        let mut script_handler = UserDataRef::<ScriptHandler>::load(lua);
+        script_handler.get_mut().load_settings(lua);
+
+        Ok(())
+    }
+
     fn register(&mut self, entry: EventEntry) -> mlua::Result<()> {
         self.counter += 1;
         entry.handle.assign_id(self.counter);
@@ -11,19 +11,15 @@ impl EventLoop {
         Self {}
     }
 
-    pub fn run<F: FnMut(Vec<Event>)>(
-        &self,
-        interval: Duration,
-        running: &AtomicBool,
-        mut handler: F,
-    ) {
+    // Handler returns the interval until the next update, it can change when settings are reloaded
+    pub fn run<F: FnMut(Vec<Event>) -> Duration>(&self, running: &AtomicBool, mut handler: F) {
         while running.load(Ordering::Relaxed) {
             let begin = Instant::now();
 
             let event_queue = EventQueue::instance();
             let events = event_queue.lock().unwrap().get_events();
 
-            handler(events);
+            let interval = handler(events);
 
             let end = Instant::now();
             let update_duration = end - begin;
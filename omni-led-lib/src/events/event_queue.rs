@@ -11,7 +11,9 @@ pub enum Event {
     Keyboard(KeyboardEvent),
     Register(EventEntry),
     Unregister(EventHandle),
+    ReloadDevices,
     ReloadScripts,
+    ReloadSettings,
     Script(ScriptEvent),
 }
 
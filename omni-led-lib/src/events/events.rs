@@ -34,10 +34,18 @@ impl Events {
         Self::queue_event(Event::Unregister(handle));
     }
 
+    pub fn reload_devices() {
+        Self::queue_event(Event::ReloadDevices);
+    }
+
     pub fn reload_scripts() {
         Self::queue_event(Event::ReloadScripts);
     }
 
+    pub fn reload_settings() {
+        Self::queue_event(Event::ReloadSettings);
+    }
+
     pub fn send(event: String, value: Value) {
         if Plugin::is_valid_identifier(&event) {
             Self::queue_event(Event::Script(ScriptEvent { event, value }));
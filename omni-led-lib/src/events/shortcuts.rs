@@ -14,16 +14,13 @@ use crate::settings::settings::Settings;
 
 #[derive(LuaName)]
 pub struct Shortcuts {
-    delay: usize,
-    rate: usize,
+    repeat: Rc<RefCell<RepeatSettings>>,
     current_tick: Rc<RefCell<usize>>,
 }
 
 impl Shortcuts {
     pub fn load(lua: &Lua) {
-        let settings = UserDataRef::<Settings>::load(lua);
-        let delay = settings.get().keyboard_ticks_repeat_delay;
-        let rate = settings.get().keyboard_ticks_repeat_rate;
+        let repeat = Rc::new(RefCell::new(RepeatSettings::new(lua)));
         let current_tick = Rc::new(RefCell::new(0));
 
         let function = lua
@@ -45,18 +42,23 @@ impl Shortcuts {
         set_unique_user_data(
             lua,
             Self {
-                delay,
-                rate,
+                repeat,
                 current_tick,
             },
         );
     }
 
+    pub fn load_settings(&mut self, lua: &Lua) {
+        // Already registered shortcuts share these settings, so they get updated as well
+        *self.repeat.borrow_mut() = RepeatSettings::new(lua);
+    }
+
     fn process_key(
         entry: &mut ShortcutEntry,
         key_name: &str,
         action: &str,
         current_tick: usize,
+        repeat: RepeatSettings,
     ) -> mlua::Result<()> {
         let key_state = entry.keys.iter_mut().find(|s| s.key == key_name).unwrap();
         key_state.pressed = action == "Pressed";
@@ -66,8 +68,8 @@ impl Shortcuts {
         let press = all_pressed && !entry.last_all_pressed;
         let hold = all_pressed && entry.last_all_pressed;
         let required_ticks = match entry.hold_updates {
-            0 => entry.delay,
-            _ => entry.rate,
+            0 => repeat.delay,
+            _ => repeat.rate,
         };
         let delta_ticks = current_tick - entry.last_update_tick;
         let update = (current_tick != entry.last_update_tick)
@@ -140,15 +142,15 @@ impl Shortcuts {
             last_all_pressed: false,
             last_update_tick: 0,
             hold_updates: 0,
-            delay: self.delay,
-            rate: self.rate,
         };
 
         let current_tick = Rc::clone(&self.current_tick);
+        let repeat = Rc::clone(&self.repeat);
         let function =
             lua.create_function_mut(move |_: &Lua, (key, action): (String, String)| {
                 let current_tick = *current_tick.borrow();
-                Self::process_key(&mut entry, &key, &action, current_tick)
+                let repeat = *repeat.borrow();
+                Self::process_key(&mut entry, &key, &action, current_tick, repeat)
             })?;
 
         for key in keys {
@@ -176,10 +178,24 @@ struct ShortcutEntry {
     last_all_pressed: bool,
     last_update_tick: usize,
     hold_updates: usize,
+}
+
+#[derive(Clone, Copy)]
+struct RepeatSettings {
     delay: usize,
     rate: usize,
 }
 
+impl RepeatSettings {
+    fn new(lua: &Lua) -> Self {
+        let settings = UserDataRef::<Settings>::load(lua);
+        Self {
+            delay: settings.get().keyboard_ticks_repeat_delay,
+            rate: settings.get().keyboard_ticks_repeat_rate,
+        }
+    }
+}
+
 struct KeyState {
     key: String,
     pressed: bool,
@@ -8,6 +8,15 @@ pub trait LogHandle {
     fn set_level_filter(&self, level_filter: log::LevelFilter);
 }
 
+// Handle for tests, where no logger is installed
+#[cfg(test)]
+pub struct NoopLogHandle;
+
+#[cfg(test)]
+impl LogHandle for NoopLogHandle {
+    fn set_level_filter(&self, _level_filter: log::LevelFilter) {}
+}
+
 #[derive(LuaName)]
 pub struct Log {
     handle: Box<dyn LogHandle>,
@@ -1,3 +1,3 @@
 pub mod plugin_loader;
 
-mod c_plugin;
+pub(crate) mod c_plugin;
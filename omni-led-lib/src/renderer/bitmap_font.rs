@@ -0,0 +1,539 @@
+use flate2::read::GzDecoder;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Read;
+
+use crate::renderer::font_manager::{Bitmap, Character, Metrics};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const PCF_MAGIC: &[u8] = b"\x01fcp";
+
+const PCF_ACCELERATORS: u32 = 1 << 1;
+const PCF_METRICS: u32 = 1 << 2;
+const PCF_BITMAPS: u32 = 1 << 3;
+const PCF_BDF_ENCODINGS: u32 = 1 << 5;
+const PCF_BDF_ACCELERATORS: u32 = 1 << 8;
+
+const PCF_COMPRESSED_METRICS: u32 = 0x100;
+const PCF_BYTE_MASK: u32 = 1 << 2;
+const PCF_BIT_MASK: u32 = 1 << 3;
+
+// Font with a single native size, parsed from BDF or PCF files
+#[derive(Default)]
+pub struct BitmapFont {
+    pub ascent: usize,
+    pub descent: usize,
+    glyphs: HashMap<char, Glyph>,
+    default_char: Option<char>,
+}
+
+// Rows are padded to whole bytes, with the most significant bit first
+#[derive(Clone, Default)]
+struct Glyph {
+    advance: isize,
+    offset_x: isize,
+    offset_y: isize,
+    cols: usize,
+    rows: usize,
+    buffer: Vec<u8>,
+}
+
+impl BitmapFont {
+    pub fn parse(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if data.starts_with(GZIP_MAGIC) {
+            let mut decompressed = Vec::new();
+            GzDecoder::new(data).read_to_end(&mut decompressed)?;
+            return Self::parse(&decompressed);
+        }
+
+        let font = match data.starts_with(PCF_MAGIC) {
+            true => parse_pcf(data)?,
+            false => parse_bdf(std::str::from_utf8(data)?)?,
+        };
+
+        if font.glyphs.is_empty() {
+            return Err("Font doesn't contain any glyphs".into());
+        }
+        if font.line_height() == 0 {
+            return Err("Font has zero height".into());
+        }
+        Ok(font)
+    }
+
+    pub fn line_height(&self) -> usize {
+        self.ascent + self.descent
+    }
+
+    // Glyphs are scaled by repeating pixels, so they stay sharp at multiples of the native size
+    pub fn render(&self, character: char, scale: usize) -> Character {
+        let glyph = self
+            .glyphs
+            .get(&character)
+            .or_else(|| self.glyphs.get(&self.default_char?));
+        let Some(glyph) = glyph else {
+            return Character {
+                metrics: Metrics { advance: 0 },
+                bitmap: Bitmap::mono(0, 0, 0, 0, Vec::new()),
+            };
+        };
+
+        let (cols, rows) = (glyph.cols * scale, glyph.rows * scale);
+        let stride = cols.div_ceil(8);
+        let mut buffer = vec![0; stride * rows];
+        for y in 0..rows {
+            for x in 0..cols {
+                if glyph.get(x / scale, y / scale) {
+                    buffer[y * stride + x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+
+        let scale = scale as isize;
+        Character {
+            metrics: Metrics {
+                advance: glyph.advance * scale,
+            },
+            bitmap: Bitmap::mono(
+                glyph.offset_x * scale,
+                glyph.offset_y * scale,
+                rows,
+                cols,
+                buffer,
+            ),
+        }
+    }
+}
+
+impl Glyph {
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.buffer[y * self.cols.div_ceil(8) + x / 8] & (0x80 >> (x % 8)) != 0
+    }
+}
+
+fn parse_bdf(text: &str) -> Result<BitmapFont, Box<dyn Error>> {
+    let mut font = BitmapFont::default();
+    let mut bounding_box = None;
+    let mut glyph = Glyph::default();
+    let mut encoding = None;
+
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let mut values = line.split_whitespace();
+        let Some(keyword) = values.next() else {
+            continue;
+        };
+
+        match keyword {
+            "FONTBOUNDINGBOX" => bounding_box = Some(numbers::<4>(values)?),
+            "FONT_ASCENT" => font.ascent = numbers::<1>(values)?[0].max(0) as usize,
+            "FONT_DESCENT" => font.descent = numbers::<1>(values)?[0].max(0) as usize,
+            "DEFAULT_CHAR" => font.default_char = char::from_u32(numbers::<1>(values)?[0] as u32),
+            "STARTCHAR" => {
+                glyph = Glyph::default();
+                encoding = None;
+            }
+            // Negative values are used for glyphs without a standard encoding
+            "ENCODING" => {
+                let [code] = numbers(values)?;
+                encoding = u32::try_from(code).ok().and_then(char::from_u32);
+            }
+            "DWIDTH" => glyph.advance = numbers::<1>(values)?[0],
+            "BBX" => {
+                let [cols, rows, offset_x, offset_y] = numbers(values)?;
+                glyph.cols = cols.max(0) as usize;
+                glyph.rows = rows.max(0) as usize;
+                glyph.offset_x = offset_x;
+                glyph.offset_y = offset_y + rows;
+            }
+            "BITMAP" => {
+                let stride = glyph.cols.div_ceil(8);
+                for _ in 0..glyph.rows {
+                    let row = lines.next().ok_or("Glyph bitmap is shorter than its BBX")?;
+                    for index in 0..stride {
+                        let byte = row.trim().get(2 * index..2 * index + 2).unwrap_or("00");
+                        glyph.buffer.push(u8::from_str_radix(byte, 16)?);
+                    }
+                }
+            }
+            "ENDCHAR" => {
+                // Missing bitmap, or BBX given after it, would leave the buffer short
+                if glyph.buffer.len() != glyph.rows * glyph.cols.div_ceil(8) {
+                    return Err("Glyph bitmap doesn't match its BBX".into());
+                }
+                if let Some(character) = encoding {
+                    font.glyphs.insert(character, std::mem::take(&mut glyph));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Older fonts may not have the ascent and descent properties
+    if font.line_height() == 0
+        && let Some([_, height, _, offset_y]) = bounding_box
+    {
+        font.ascent = (height + offset_y).max(0) as usize;
+        font.descent = (-offset_y).max(0) as usize;
+    }
+    Ok(font)
+}
+
+fn numbers<const N: usize>(
+    values: std::str::SplitWhitespace,
+) -> Result<[isize; N], Box<dyn Error>> {
+    let values = values
+        .map(|value| value.parse())
+        .collect::<Result<Vec<isize>, _>>()?;
+    values
+        .get(..N)
+        .and_then(|values| values.try_into().ok())
+        .ok_or_else(|| format!("Expected {} values, got {}", N, values.len()).into())
+}
+
+fn parse_pcf(data: &[u8]) -> Result<BitmapFont, Box<dyn Error>> {
+    let mut header = Reader::new(data, PCF_MAGIC.len(), 0);
+    let mut tables = HashMap::new();
+    for _ in 0..header.u32()? {
+        let (kind, _format, _size, offset) =
+            (header.u32()?, header.u32()?, header.u32()?, header.u32()?);
+        tables.insert(kind, offset as usize);
+    }
+
+    // Each table starts with its own format, which is always little endian
+    let table = |kind: u32| -> Result<Reader, Box<dyn Error>> {
+        let offset = *tables
+            .get(&kind)
+            .ok_or_else(|| format!("Missing PCF table {:#x}", kind))?;
+        let format = Reader::new(data, offset, 0).u32()?;
+        Ok(Reader::new(data, offset + 4, format))
+    };
+
+    let mut accelerators = table(PCF_BDF_ACCELERATORS).or_else(|_| table(PCF_ACCELERATORS))?;
+    accelerators.skip(8);
+    let ascent = accelerators.i32()?.max(0) as usize;
+    let descent = accelerators.i32()?.max(0) as usize;
+
+    let mut metrics = table(PCF_METRICS)?;
+    let compressed = metrics.format & 0xffffff00 == PCF_COMPRESSED_METRICS;
+    let count = match compressed {
+        true => metrics.u16()? as usize,
+        false => metrics.u32()? as usize,
+    };
+    // Count isn't used to reserve memory, corrupt files could request more than is available
+    let mut glyphs = Vec::new();
+    for _ in 0..count {
+        // Compressed metrics are stored as bytes with an offset of 0x80
+        let mut values = [0; 5];
+        for value in &mut values {
+            *value = match compressed {
+                true => metrics.u8()? as isize - 0x80,
+                false => metrics.i16()? as isize,
+            };
+        }
+        if !compressed {
+            metrics.skip(2);
+        }
+
+        let [left, right, width, ascent, descent] = values;
+        glyphs.push(Glyph {
+            advance: width,
+            offset_x: left,
+            offset_y: ascent,
+            cols: (right - left).max(0) as usize,
+            rows: (ascent + descent).max(0) as usize,
+            buffer: Vec::new(),
+        });
+    }
+
+    let mut bitmaps = table(PCF_BITMAPS)?;
+    let format = bitmaps.format;
+    if bitmaps.u32()? as usize != glyphs.len() {
+        return Err("PCF bitmap and metrics tables have different glyph counts".into());
+    }
+    let offsets = (0..glyphs.len())
+        .map(|_| bitmaps.u32().map(|offset| offset as usize))
+        .collect::<Result<Vec<_>, _>>()?;
+    bitmaps.skip(16);
+
+    let padding = 1 << (format & 3);
+    let scan_unit = 1 << ((format >> 4) & 3);
+    let lsb_first = format & PCF_BIT_MASK == 0;
+    let swap_bytes = (format & PCF_BYTE_MASK == 0) != lsb_first;
+    for (glyph, offset) in glyphs.iter_mut().zip(offsets) {
+        let stride = glyph.cols.div_ceil(8);
+        let padded_stride = stride.next_multiple_of(padding);
+        for y in 0..glyph.rows {
+            let start = bitmaps.position + offset + y * padded_stride;
+            let mut row = data
+                .get(start..start + padded_stride)
+                .ok_or("PCF glyph bitmap is out of bounds")?
+                .to_vec();
+            if lsb_first {
+                row.iter_mut().for_each(|byte| *byte = byte.reverse_bits());
+            }
+            if swap_bytes && scan_unit > 1 {
+                row.chunks_mut(scan_unit).for_each(|unit| unit.reverse());
+            }
+            glyph.buffer.extend_from_slice(&row[..stride]);
+        }
+    }
+
+    let mut encodings = table(PCF_BDF_ENCODINGS)?;
+    let (min_byte2, max_byte2) = (encodings.u16()? as u32, encodings.u16()? as u32);
+    let (min_byte1, max_byte1) = (encodings.u16()? as u32, encodings.u16()? as u32);
+    let default_char = char::from_u32(encodings.u16()? as u32);
+
+    let mut font = BitmapFont {
+        ascent,
+        descent,
+        glyphs: HashMap::new(),
+        default_char,
+    };
+    for byte1 in min_byte1..=max_byte1 {
+        for byte2 in min_byte2..=max_byte2 {
+            let index = encodings.u16()? as usize;
+            if let (Some(glyph), Some(character)) =
+                (glyphs.get(index), char::from_u32((byte1 << 8) | byte2))
+            {
+                font.glyphs.insert(character, glyph.clone());
+            }
+        }
+    }
+    Ok(font)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+    format: u32,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], position: usize, format: u32) -> Self {
+        Self {
+            data,
+            position,
+            format,
+        }
+    }
+
+    fn skip(&mut self, count: usize) {
+        self.position += count;
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], Box<dyn Error>> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + N)
+            .ok_or("Unexpected end of PCF file")?;
+        self.position += N;
+
+        let mut bytes: [u8; N] = bytes.try_into()?;
+        if self.format & PCF_BYTE_MASK == 0 {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Box<dyn Error>> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Box<dyn Error>> {
+        Ok(u16::from_be_bytes(self.bytes()?))
+    }
+
+    fn i16(&mut self) -> Result<i16, Box<dyn Error>> {
+        Ok(i16::from_be_bytes(self.bytes()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn Error>> {
+        Ok(u32::from_be_bytes(self.bytes()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, Box<dyn Error>> {
+        Ok(i32::from_be_bytes(self.bytes()?))
+    }
+}
+
+// Font with 'j', 'é' and '?' as the default character, for tests that need a bitmap font
+#[cfg(test)]
+pub const TEST_FONT: &str = "STARTFONT 2.1
+FONT -test-fixture
+SIZE 9 75 75
+FONTBOUNDINGBOX 4 9 0 -2
+STARTPROPERTIES 3
+FONT_ASCENT 7
+FONT_DESCENT 2
+DEFAULT_CHAR 63
+ENDPROPERTIES
+CHARS 3
+STARTCHAR question
+ENCODING 63
+DWIDTH 6 0
+BBX 3 7 0 0
+BITMAP
+E0
+20
+20
+40
+40
+00
+40
+ENDCHAR
+STARTCHAR j
+ENCODING 106
+DWIDTH 6 0
+BBX 4 9 0 -2
+BITMAP
+20
+00
+20
+20
+20
+20
+20
+90
+60
+ENDCHAR
+STARTCHAR eacute
+ENCODING 233
+DWIDTH 5 0
+BBX 4 7 0 0
+BITMAP
+20
+40
+60
+90
+F0
+80
+70
+ENDCHAR
+ENDFONT
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixels(character: &Character) -> Vec<String> {
+        let bitmap = &character.bitmap;
+        (0..bitmap.rows)
+            .map(|y| {
+                (0..bitmap.cols)
+                    .map(|x| match bitmap.get_level(x, y) {
+                        0 => '.',
+                        _ => '#',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bdf() {
+        let font = BitmapFont::parse(TEST_FONT.as_bytes()).unwrap();
+        assert_eq!((font.ascent, font.descent), (7, 2));
+        assert_eq!(font.glyphs.len(), 3);
+
+        let character = font.render('j', 1);
+        assert_eq!(character.metrics.advance, 6);
+        assert_eq!(character.bitmap.offset_y, 7);
+        assert_eq!(pixels(&character)[7..], ["#..#", ".##."]);
+
+        // Missing glyphs are replaced with the default character
+        let character = font.render('ä', 2);
+        assert_eq!(character.metrics.advance, 12);
+        assert_eq!(pixels(&character)[..2], ["######", "######"]);
+    }
+
+    #[test]
+    fn bdf_latin1_glyph() {
+        let font = BitmapFont::parse(TEST_FONT.as_bytes()).unwrap();
+
+        let character = font.render('é', 1);
+        assert_eq!(character.metrics.advance, 5);
+        assert_eq!(pixels(&character)[..2], ["..#.", ".#.."]);
+    }
+
+    #[test]
+    fn bdf_glyph_without_bitmap() {
+        let bdf = |glyph: &str| {
+            let text =
+                format!("STARTFONT 2.1\nSTARTCHAR A\nENCODING 65\n{glyph}ENDCHAR\nENDFONT\n");
+            parse_bdf(&text)
+        };
+
+        assert!(bdf("BBX 3 2 0 0\nBITMAP\nA0\n40\n").is_ok());
+        assert!(bdf("BBX 3 2 0 0\n").is_err());
+        assert!(bdf("BITMAP\nBBX 3 2 0 0\n").is_err());
+    }
+
+    fn pcf_file(tables: &[(u32, Vec<u8>)]) -> Vec<u8> {
+        let format = 2u32.to_le_bytes();
+        let mut data = [PCF_MAGIC, &(tables.len() as u32).to_le_bytes()].concat();
+        let mut offset = data.len() + tables.len() * 16;
+        for (kind, table) in tables {
+            let size = (table.len() + format.len()) as u32;
+            data.extend([kind.to_le_bytes(), format, size.to_le_bytes()].concat());
+            data.extend((offset as u32).to_le_bytes());
+            offset += size as usize;
+        }
+        for (_, table) in tables {
+            data.extend(format);
+            data.extend(table);
+        }
+        data
+    }
+
+    #[test]
+    fn pcf() {
+        // Single 3x2 glyph with little endian integers, least significant bit first and rows
+        // padded to 4 bytes
+        let tables: [(u32, Vec<u8>); 4] = [
+            (
+                PCF_ACCELERATORS,
+                [&[0; 8][..], &2i32.to_le_bytes(), &1i32.to_le_bytes()].concat(),
+            ),
+            (
+                PCF_METRICS,
+                [
+                    &1u32.to_le_bytes()[..],
+                    &[0, 0, 3, 0, 4, 0, 2, 0, 0, 0, 0, 0],
+                ]
+                .concat(),
+            ),
+            (
+                PCF_BITMAPS,
+                [
+                    &1u32.to_le_bytes()[..],
+                    &[0; 4],
+                    &[0; 16],
+                    &[0b101, 0, 0, 0, 0b010, 0, 0, 0],
+                ]
+                .concat(),
+            ),
+            (
+                PCF_BDF_ENCODINGS,
+                vec![65, 0, 65, 0, 0, 0, 0, 0, 65, 0, 0, 0],
+            ),
+        ];
+
+        let font = BitmapFont::parse(&pcf_file(&tables)).unwrap();
+        assert_eq!((font.ascent, font.descent), (2, 1));
+
+        let character = font.render('A', 2);
+        assert_eq!(character.metrics.advance, 8);
+        assert_eq!(character.bitmap.offset_y, 4);
+        assert_eq!(pixels(&character), ["##..##", "##..##", "..##..", "..##.."]);
+    }
+
+    #[test]
+    fn pcf_truncated_metrics() {
+        let tables = [
+            (PCF_ACCELERATORS, vec![0; 16]),
+            (PCF_METRICS, u32::MAX.to_le_bytes().to_vec()),
+        ];
+        assert!(BitmapFont::parse(&pcf_file(&tables)).is_err());
+    }
+}
@@ -1,10 +1,12 @@
 use mlua::{FromLua, Lua, UserData, UserDataMethods, Value};
+use omni_led_derive::LuaName;
 
 use crate::devices::device::MemoryLayout;
 use crate::renderer::bit::{Bit, BitMut};
-use crate::script_handler::script_data_types::Modifiers;
+use crate::script_handler::script_data_types::{Color, Modifiers};
 use crate::script_handler::script_data_types::{Rectangle, Size};
 
+#[derive(LuaName)]
 pub struct Buffer {
     buffer: Box<dyn BufferTrait>,
 }
@@ -15,26 +17,106 @@ impl Buffer {
             MemoryLayout::BitPerPixel => Box::new(BitBuffer::new(size)),
             MemoryLayout::BytePerPixel => Box::new(ByteBuffer::new(size)),
             MemoryLayout::BitPerPixelVertical => Box::new(VerticalBitBuffer::new(size)),
+            MemoryLayout::TwoBitsPerPixel => Box::new(GrayBuffer::new(size, 2)),
+            MemoryLayout::FourBitsPerPixel => Box::new(GrayBuffer::new(size, 4)),
+            MemoryLayout::Rgb565 => Box::new(ColorBuffer::new(size, 2)),
+            MemoryLayout::Rgb888 => Box::new(ColorBuffer::new(size, 3)),
         };
 
         Self { buffer }
     }
 
     pub fn set(&mut self, x: isize, y: isize, area: &Rectangle, modifiers: &Modifiers) {
-        self.set_value(true, x, y, area, modifiers);
+        self.set_value(true, u8::MAX, x, y, area, modifiers);
     }
 
     pub fn reset(&mut self, x: isize, y: isize, area: &Rectangle, modifiers: &Modifiers) {
-        self.set_value(false, x, y, area, modifiers);
+        self.set_value(false, u8::MAX, x, y, area, modifiers);
+    }
+
+    // Partially covers the pixel, e.g. for anti-aliased text edges
+    pub fn blend(
+        &mut self,
+        x: isize,
+        y: isize,
+        area: &Rectangle,
+        modifiers: &Modifiers,
+        coverage: u8,
+    ) {
+        self.set_value(true, coverage, x, y, area, modifiers);
+    }
+
+    // Draws a pixel of a given color, e.g. from a full-color image
+    pub fn set_color_at(
+        &mut self,
+        x: isize,
+        y: isize,
+        area: &Rectangle,
+        modifiers: &Modifiers,
+        color: Color,
+    ) {
+        if let Some((x, y)) = self.translate(x, y, area, modifiers) {
+            let color = color.scale(modifiers.brightness);
+            let color = match modifiers.negative {
+                true => color.invert(),
+                false => color,
+            };
+            self.buffer.set_color(x, y, color);
+        }
+    }
+
+    // Buffers with only two levels keep rendering binary, so 1-bit output doesn't change
+    pub fn is_grayscale(&self) -> bool {
+        self.buffer.levels() > 2
+    }
+
+    pub fn is_color(&self) -> bool {
+        self.buffer.is_color()
     }
 
     pub fn bytes(&self) -> &[u8] {
         self.buffer.bytes().as_slice()
     }
 
+    pub fn width(&self) -> usize {
+        self.buffer.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.buffer.height()
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.buffer.get(x, y).unwrap_or(false)
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, value: bool) {
+        match value {
+            true => self.buffer.set(x, y),
+            false => self.buffer.reset(x, y),
+        }
+    }
+
+    pub fn get_level(&self, x: usize, y: usize) -> u8 {
+        self.buffer.get_level(x, y).unwrap_or(0)
+    }
+
+    pub fn set_level(&mut self, x: usize, y: usize, level: u8) {
+        self.buffer.set_level(x, y, level);
+    }
+
+    pub fn get_color(&self, x: usize, y: usize) -> Color {
+        self.buffer.get_color(x, y).unwrap_or(Color::BLACK)
+    }
+
+    pub fn set_color(&mut self, x: usize, y: usize, color: Color) {
+        self.buffer.set_color(x, y, color);
+    }
+
     fn set_value(
         &mut self,
         value: bool,
+        coverage: u8,
         x: isize,
         y: isize,
         area: &Rectangle,
@@ -47,10 +129,17 @@ impl Buffer {
             }
         };
 
-        match value ^ modifiers.negative {
-            true => self.buffer.set(x, y),
-            false => self.buffer.reset(x, y),
-        }
+        // Binary buffers show any non-zero brightness at full intensity
+        let target = match value ^ modifiers.negative {
+            true if self.is_grayscale() => modifiers.color.scale(modifiers.brightness),
+            true if modifiers.brightness > 0 => modifiers.color,
+            _ => modifiers.background,
+        };
+        let color = match coverage {
+            u8::MAX => target,
+            _ => self.get_color(x, y).blend(target, coverage),
+        };
+        self.buffer.set_color(x, y, color);
     }
 
     fn translate(
@@ -82,6 +171,14 @@ impl Buffer {
     }
 }
 
+impl Clone for Buffer {
+    fn clone(&self) -> Self {
+        Self {
+            buffer: self.buffer.clone_box(),
+        }
+    }
+}
+
 impl UserData for Buffer {
     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
         methods.add_method("bytes", |_lua, buffer, _: ()| Ok(buffer.bytes().to_vec()));
@@ -105,12 +202,49 @@ pub trait BufferTrait {
     fn width(&self) -> usize;
     fn height(&self) -> usize;
     fn bytes(&self) -> &Vec<u8>;
-    #[allow(unused)]
     fn get(&self, x: usize, y: usize) -> Option<bool>;
     fn set(&mut self, x: usize, y: usize);
     fn reset(&mut self, x: usize, y: usize);
+    fn clone_box(&self) -> Box<dyn BufferTrait>;
+
+    // Number of distinct intensities a pixel can have
+    fn levels(&self) -> usize {
+        2
+    }
+
+    fn get_level(&self, x: usize, y: usize) -> Option<u8> {
+        self.get(x, y).map(|value| match value {
+            true => u8::MAX,
+            false => 0,
+        })
+    }
+
+    fn set_level(&mut self, x: usize, y: usize, level: u8) {
+        match level > 0 {
+            true => self.set(x, y),
+            false => self.reset(x, y),
+        }
+    }
+
+    fn is_color(&self) -> bool {
+        false
+    }
+
+    fn get_color(&self, x: usize, y: usize) -> Option<Color> {
+        self.get_level(x, y).map(Color::gray)
+    }
+
+    // Binary buffers turn on pixels that are at least half as bright as white
+    fn set_color(&mut self, x: usize, y: usize, color: Color) {
+        let level = color.luminance();
+        match self.levels() {
+            2 => self.set_level(x, y, if level >= 128 { u8::MAX } else { 0 }),
+            _ => self.set_level(x, y, level),
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct ByteBuffer {
     width_px: usize,
     height_px: usize,
@@ -174,8 +308,27 @@ impl BufferTrait for ByteBuffer {
             *value = 0x00;
         }
     }
+
+    fn clone_box(&self) -> Box<dyn BufferTrait> {
+        Box::new(self.clone())
+    }
+
+    fn levels(&self) -> usize {
+        256
+    }
+
+    fn get_level(&self, x: usize, y: usize) -> Option<u8> {
+        self.get_byte(x, y).copied()
+    }
+
+    fn set_level(&mut self, x: usize, y: usize, level: u8) {
+        if let Some(value) = self.get_byte_mut(x, y) {
+            *value = level;
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct BitBuffer {
     width_px: usize,
     height_px: usize,
@@ -244,8 +397,13 @@ impl BufferTrait for BitBuffer {
             bit.reset();
         }
     }
+
+    fn clone_box(&self) -> Box<dyn BufferTrait> {
+        Box::new(self.clone())
+    }
 }
 
+#[derive(Clone)]
 pub struct VerticalBitBuffer {
     width_px: usize,
     height_px: usize,
@@ -314,4 +472,329 @@ impl BufferTrait for VerticalBitBuffer {
             bit.reset();
         }
     }
+
+    fn clone_box(&self) -> Box<dyn BufferTrait> {
+        Box::new(self.clone())
+    }
+}
+
+// Packed grayscale with 2 or 4 bits per pixel, the left-most pixel is stored in the most
+// significant bits of a byte. Rows are padded to full bytes.
+#[derive(Clone)]
+pub struct GrayBuffer {
+    width_px: usize,
+    height_px: usize,
+    width_bytes: usize,
+    bits_per_pixel: usize,
+    data: Vec<u8>,
+}
+
+impl GrayBuffer {
+    pub fn new(size: Size, bits_per_pixel: usize) -> Self {
+        let width_bytes = (size.width * bits_per_pixel).div_ceil(8);
+        Self {
+            width_px: size.width,
+            height_px: size.height,
+            width_bytes,
+            bits_per_pixel,
+            data: vec![0; size.height * width_bytes],
+        }
+    }
+
+    fn max_value(&self) -> u32 {
+        (1 << self.bits_per_pixel) - 1
+    }
+
+    fn pixel_position(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        if x >= self.width_px || y >= self.height_px {
+            return None;
+        }
+
+        let bit_offset = x * self.bits_per_pixel;
+        let index = y * self.width_bytes + bit_offset / 8;
+        let shift = 8 - self.bits_per_pixel - bit_offset % 8;
+
+        Some((index, shift))
+    }
+
+    fn get_value(&self, x: usize, y: usize) -> Option<u32> {
+        self.pixel_position(x, y)
+            .map(|(index, shift)| (self.data[index] >> shift) as u32 & self.max_value())
+    }
+
+    fn set_value(&mut self, x: usize, y: usize, value: u32) {
+        if let Some((index, shift)) = self.pixel_position(x, y) {
+            let mask = (self.max_value() << shift) as u8;
+            self.data[index] = (self.data[index] & !mask) | ((value << shift) as u8 & mask);
+        }
+    }
+}
+
+impl BufferTrait for GrayBuffer {
+    fn width(&self) -> usize {
+        self.width_px
+    }
+
+    fn height(&self) -> usize {
+        self.height_px
+    }
+
+    fn bytes(&self) -> &Vec<u8> {
+        &self.data
+    }
+
+    fn get(&self, x: usize, y: usize) -> Option<bool> {
+        self.get_value(x, y).map(|value| value > 0)
+    }
+
+    fn set(&mut self, x: usize, y: usize) {
+        self.set_value(x, y, self.max_value());
+    }
+
+    fn reset(&mut self, x: usize, y: usize) {
+        self.set_value(x, y, 0);
+    }
+
+    fn clone_box(&self) -> Box<dyn BufferTrait> {
+        Box::new(self.clone())
+    }
+
+    fn levels(&self) -> usize {
+        1 << self.bits_per_pixel
+    }
+
+    fn get_level(&self, x: usize, y: usize) -> Option<u8> {
+        self.get_value(x, y)
+            .map(|value| (value * 255 / self.max_value()) as u8)
+    }
+
+    fn set_level(&mut self, x: usize, y: usize, level: u8) {
+        let value = (level as u32 * self.max_value() + 127) / 255;
+        self.set_value(x, y, value);
+    }
+}
+
+// Full color with 16 (RGB565, big-endian) or 24 (RGB888) bits per pixel
+#[derive(Clone)]
+pub struct ColorBuffer {
+    width_px: usize,
+    height_px: usize,
+    bytes_per_pixel: usize,
+    data: Vec<u8>,
+}
+
+impl ColorBuffer {
+    pub fn new(size: Size, bytes_per_pixel: usize) -> Self {
+        Self {
+            width_px: size.width,
+            height_px: size.height,
+            bytes_per_pixel,
+            data: vec![0; size.width * size.height * bytes_per_pixel],
+        }
+    }
+
+    fn byte_position(&self, x: usize, y: usize) -> Option<usize> {
+        if x >= self.width_px || y >= self.height_px {
+            return None;
+        }
+
+        Some((y * self.width_px + x) * self.bytes_per_pixel)
+    }
+}
+
+impl BufferTrait for ColorBuffer {
+    fn width(&self) -> usize {
+        self.width_px
+    }
+
+    fn height(&self) -> usize {
+        self.height_px
+    }
+
+    fn bytes(&self) -> &Vec<u8> {
+        &self.data
+    }
+
+    fn get(&self, x: usize, y: usize) -> Option<bool> {
+        self.get_color(x, y).map(|color| color != Color::BLACK)
+    }
+
+    fn set(&mut self, x: usize, y: usize) {
+        self.set_color(x, y, Color::WHITE);
+    }
+
+    fn reset(&mut self, x: usize, y: usize) {
+        self.set_color(x, y, Color::BLACK);
+    }
+
+    fn clone_box(&self) -> Box<dyn BufferTrait> {
+        Box::new(self.clone())
+    }
+
+    fn levels(&self) -> usize {
+        256
+    }
+
+    fn get_level(&self, x: usize, y: usize) -> Option<u8> {
+        self.get_color(x, y).map(|color| color.luminance())
+    }
+
+    fn set_level(&mut self, x: usize, y: usize, level: u8) {
+        self.set_color(x, y, Color::gray(level));
+    }
+
+    fn is_color(&self) -> bool {
+        true
+    }
+
+    fn get_color(&self, x: usize, y: usize) -> Option<Color> {
+        let index = self.byte_position(x, y)?;
+        let bytes = &self.data[index..index + self.bytes_per_pixel];
+        let color = match bytes {
+            [r, g, b] => Color {
+                r: *r,
+                g: *g,
+                b: *b,
+            },
+            _ => {
+                let value = u16::from_be_bytes([bytes[0], bytes[1]]);
+                let (r, g, b) = (value >> 11, (value >> 5) & 0x3F, value & 0x1F);
+                Color {
+                    r: ((r << 3) | (r >> 2)) as u8,
+                    g: ((g << 2) | (g >> 4)) as u8,
+                    b: ((b << 3) | (b >> 2)) as u8,
+                }
+            }
+        };
+        Some(color)
+    }
+
+    fn set_color(&mut self, x: usize, y: usize, color: Color) {
+        let Some(index) = self.byte_position(x, y) else {
+            return;
+        };
+
+        let bytes = &mut self.data[index..index + self.bytes_per_pixel];
+        match bytes.len() {
+            3 => bytes.copy_from_slice(&[color.r, color.g, color.b]),
+            _ => {
+                let value = ((color.r as u16 >> 3) << 11)
+                    | ((color.g as u16 >> 2) << 5)
+                    | (color.b as u16 >> 3);
+                bytes.copy_from_slice(&value.to_be_bytes());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script_handler::script_data_types::Point;
+
+    #[test]
+    fn packed_grayscale() {
+        let size = Size {
+            width: 3,
+            height: 2,
+        };
+        let mut buffer = Buffer::new(size, MemoryLayout::FourBitsPerPixel);
+        buffer.set_level(0, 0, 255);
+        buffer.set_level(1, 0, 128);
+        buffer.set_level(2, 1, 17);
+        assert_eq!(buffer.bytes(), [0xF8, 0x00, 0x00, 0x10]);
+        assert_eq!(buffer.get_level(1, 0), 136);
+
+        let mut buffer = Buffer::new(size, MemoryLayout::TwoBitsPerPixel);
+        buffer.set_level(0, 0, 255);
+        buffer.set_level(2, 1, 100);
+        assert_eq!(buffer.bytes(), [0xC0, 0x04]);
+    }
+
+    #[test]
+    fn brightness_and_coverage() {
+        let size = Size {
+            width: 2,
+            height: 1,
+        };
+        let area = Rectangle {
+            position: Point { x: 0, y: 0 },
+            size,
+        };
+        let modifiers = Modifiers {
+            brightness: 128,
+            ..Default::default()
+        };
+
+        let mut buffer = Buffer::new(size, MemoryLayout::BytePerPixel);
+        buffer.set(0, 0, &area, &modifiers);
+        buffer.blend(1, 0, &area, &Default::default(), 64);
+        assert_eq!(buffer.bytes(), [128, 64]);
+
+        let negative = Modifiers {
+            negative: true,
+            ..modifiers
+        };
+        buffer.reset(1, 0, &area, &negative);
+        assert_eq!(buffer.bytes(), [128, 128]);
+
+        // Binary buffers only store full intensity
+        let mut buffer = Buffer::new(size, MemoryLayout::BitPerPixel);
+        buffer.blend(0, 0, &area, &modifiers, 255);
+        assert_eq!(buffer.bytes(), [0x80]);
+    }
+
+    #[test]
+    fn colors() {
+        let size = Size {
+            width: 2,
+            height: 1,
+        };
+        let area = Rectangle {
+            position: Point { x: 0, y: 0 },
+            size,
+        };
+        let modifiers = Modifiers {
+            clear_background: true,
+            color: Color {
+                r: 255,
+                g: 128,
+                b: 0,
+            },
+            background: Color { r: 0, g: 0, b: 64 },
+            ..Default::default()
+        };
+
+        let mut buffer = Buffer::new(size, MemoryLayout::Rgb888);
+        buffer.set(0, 0, &area, &modifiers);
+        buffer.reset(1, 0, &area, &modifiers);
+        assert_eq!(buffer.bytes(), [255, 128, 0, 0, 0, 64]);
+
+        let mut buffer = Buffer::new(size, MemoryLayout::Rgb565);
+        buffer.set(0, 0, &area, &modifiers);
+        buffer.reset(1, 0, &area, &modifiers);
+        assert_eq!(buffer.bytes(), [0xFC, 0x00, 0x00, 0x08]);
+        assert_eq!(
+            buffer.get_color(0, 0),
+            Color {
+                r: 255,
+                g: 130,
+                b: 0
+            }
+        );
+
+        // Binary buffers compare the luminance with a threshold
+        let mut buffer = Buffer::new(size, MemoryLayout::BitPerPixel);
+        buffer.set_color(
+            0,
+            0,
+            Color {
+                r: 255,
+                g: 128,
+                b: 0,
+            },
+        );
+        buffer.set_color(1, 0, Color { r: 255, g: 0, b: 0 });
+        assert_eq!(buffer.bytes(), [0x80]);
+    }
 }
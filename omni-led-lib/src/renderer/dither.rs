@@ -0,0 +1,146 @@
+use image::GrayImage;
+
+use crate::renderer::buffer::{BitBuffer, BufferTrait};
+use crate::script_handler::script_data_types::{Dither, Size};
+
+const BAYER_4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+// Applies gamma and contrast to every channel of the image, values of 1.0 keep it unchanged
+pub fn adjust(channels: &mut [u8], gamma: f32, contrast: f32) {
+    if gamma == 1.0 && contrast == 1.0 {
+        return;
+    }
+
+    for channel in channels {
+        let value = (*channel as f32 / 255.0).powf(1.0 / gamma);
+        let value = (value - 0.5) * contrast + 0.5;
+        *channel = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    }
+}
+
+// Converts luminance to on/off pixels. `threshold` is the cut-off for error diffusion, and shifts
+// the ordered patterns for Bayer modes.
+pub fn dither(image: &GrayImage, mode: Dither, threshold: u8) -> BitBuffer {
+    let size = Size {
+        width: image.width() as usize,
+        height: image.height() as usize,
+    };
+    let mut buffer = BitBuffer::new(size);
+
+    match mode {
+        Dither::Threshold => {
+            for (x, y, pixel) in image.enumerate_pixels() {
+                if pixel[0] >= threshold {
+                    buffer.set(x as usize, y as usize);
+                }
+            }
+        }
+        Dither::FloydSteinberg => {
+            let weights = [(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)];
+            diffuse_error(image, &mut buffer, threshold, &weights, 16);
+        }
+        Dither::Atkinson => {
+            let weights = [
+                (1, 0, 1),
+                (2, 0, 1),
+                (-1, 1, 1),
+                (0, 1, 1),
+                (1, 1, 1),
+                (0, 2, 1),
+            ];
+            diffuse_error(image, &mut buffer, threshold, &weights, 8);
+        }
+        Dither::Bayer4 => ordered(image, &mut buffer, threshold, 4),
+        Dither::Bayer8 => ordered(image, &mut buffer, threshold, 8),
+    }
+    buffer
+}
+
+fn diffuse_error(
+    image: &GrayImage,
+    buffer: &mut BitBuffer,
+    threshold: u8,
+    weights: &[(isize, usize, i32)],
+    divisor: i32,
+) {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut values: Vec<i32> = image.pixels().map(|pixel| pixel[0] as i32).collect();
+
+    for y in 0..height {
+        for x in 0..width {
+            let value = values[y * width + x];
+            let on = value >= threshold as i32;
+            if on {
+                buffer.set(x, y);
+            }
+
+            let error = value - if on { 255 } else { 0 };
+            for (dx, dy, weight) in weights {
+                let (nx, ny) = (x as isize + dx, y + dy);
+                if nx >= 0 && (nx as usize) < width && ny < height {
+                    values[ny * width + nx as usize] += error * weight / divisor;
+                }
+            }
+        }
+    }
+}
+
+fn ordered(image: &GrayImage, buffer: &mut BitBuffer, threshold: u8, order: usize) {
+    let levels = (order * order) as i32;
+    let bias = threshold as i32 - 128;
+
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let (x, y) = (x as usize, y as usize);
+        let index = bayer_index(x % order, y % order, order) as i32;
+        let cut = (2 * index + 1) * 128 / levels + bias;
+        if pixel[0] as i32 >= cut {
+            buffer.set(x, y);
+        }
+    }
+}
+
+// 8x8 matrix is built recursively from the 4x4 one
+fn bayer_index(x: usize, y: usize, order: usize) -> u8 {
+    match order {
+        4 => BAYER_4[y][x],
+        _ => 4 * BAYER_4[y % 4][x % 4] + [[0, 2], [3, 1]][y / 4][x / 4],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_set(buffer: &BitBuffer) -> usize {
+        (0..buffer.height())
+            .flat_map(|y| (0..buffer.width()).map(move |x| (x, y)))
+            .filter(|(x, y)| buffer.get(*x, *y).unwrap())
+            .count()
+    }
+
+    #[test]
+    fn modes_keep_average_intensity() {
+        let gray = GrayImage::from_pixel(16, 16, image::Luma([64]));
+        assert_eq!(count_set(&dither(&gray, Dither::Threshold, 128)), 0);
+        assert_eq!(count_set(&dither(&gray, Dither::Bayer4, 128)), 64);
+        assert_eq!(count_set(&dither(&gray, Dither::Bayer8, 128)), 64);
+
+        let floyd_steinberg = count_set(&dither(&gray, Dither::FloydSteinberg, 128));
+        assert!((56..=72).contains(&floyd_steinberg));
+
+        // Atkinson diffuses only part of the error, so it loses some of the mid-tones
+        let atkinson = count_set(&dither(&gray, Dither::Atkinson, 128));
+        assert!((32..=72).contains(&atkinson));
+    }
+
+    #[test]
+    fn adjustments() {
+        let mut image = GrayImage::from_fn(3, 1, |x, _| image::Luma([[0, 64, 192][x as usize]]));
+        adjust(&mut image, 1.0, 1.5);
+        assert_eq!(image.as_raw(), &[0, 32, 224]);
+
+        let mut image = GrayImage::from_pixel(1, 1, image::Luma([64]));
+        adjust(&mut image, 2.0, 1.0);
+        assert_eq!(image.as_raw(), &[128]);
+    }
+}
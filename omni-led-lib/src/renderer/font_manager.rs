@@ -1,22 +1,33 @@
 use font_kit::font::Font;
 use font_kit::properties::Properties;
 use font_kit::source::SystemSource;
-use freetype::RenderMode;
 use freetype::face::LoadFlag;
+use freetype::{RenderMode, bitmap::PixelMode};
 use log::{debug, error};
 use std::collections::HashMap;
 use std::error::Error;
 use std::sync::Arc;
 
 use crate::renderer::bit::Bit;
+use crate::renderer::bitmap_font::BitmapFont;
 use crate::renderer::font_selector::FontSelector;
 use crate::script_handler::script_data_types::FontSize;
 
 pub struct FontManager {
+    typeface: Typeface,
+    antialiasing: bool,
+    cache: HashMap<(char, usize, bool), Character>,
+}
+
+enum Typeface {
+    Outline(OutlineFont),
+    Bitmap(BitmapFont),
+}
+
+struct OutlineFont {
     _library: freetype::Library,
     face: freetype::Face,
     metrics: FontMetrics,
-    cache: HashMap<(char, usize), Character>,
 }
 
 struct FontMetrics {
@@ -27,6 +38,100 @@ struct FontMetrics {
 
 impl FontManager {
     pub fn new(selector: FontSelector) -> Self {
+        let typeface = match selector {
+            FontSelector::Bitmap(selector) => Self::load_bitmap_font(&selector.path),
+            selector => Typeface::Outline(OutlineFont::new(selector)),
+        };
+
+        Self {
+            typeface,
+            antialiasing: false,
+            cache: HashMap::new(),
+        }
+    }
+
+    // Bitmap fonts only look right at their native size, so it's snapped to its multiples
+    pub fn get_font_size(&self, font_setting: FontSize, max_height: usize) -> usize {
+        let font = match &self.typeface {
+            Typeface::Outline(font) => return font.get_font_size(font_setting, max_height),
+            Typeface::Bitmap(font) => font,
+        };
+
+        let scale = match font_setting {
+            FontSize::Value(value) => value / font.line_height(),
+            FontSize::Auto => max_height / font.line_height(),
+            FontSize::AutoUpper => max_height / font.ascent.max(1),
+        };
+        scale.max(1) * font.line_height()
+    }
+
+    pub fn get_offset(&self, font_setting: FontSize, actual_font_size: usize) -> isize {
+        let font = match &self.typeface {
+            Typeface::Outline(font) => return font.get_offset(font_setting, actual_font_size),
+            Typeface::Bitmap(font) => font,
+        };
+
+        match font_setting {
+            FontSize::Value(_) | FontSize::Auto => {
+                (font.descent * actual_font_size / font.line_height()) as isize
+            }
+            FontSize::AutoUpper => 0,
+        }
+    }
+
+    // Distance between the ascender and descender lines
+    pub fn get_line_height(&self, font_size: usize) -> usize {
+        match &self.typeface {
+            Typeface::Outline(font) => {
+                let height = font_size as f64 / font.metrics.full_scale;
+                height.round() as usize
+            }
+            Typeface::Bitmap(_) => font_size,
+        }
+    }
+
+    // Anti-aliased glyphs are only useful for displays that can show more than two levels
+    pub fn set_antialiasing(&mut self, antialiasing: bool) {
+        self.antialiasing = antialiasing;
+    }
+
+    pub fn get_character(&mut self, character: char, font_size: usize) -> &Character {
+        let antialiasing = self.antialiasing;
+        let typeface = &self.typeface;
+
+        self.cache
+            .entry((character, font_size, antialiasing))
+            .or_insert_with(|| match typeface {
+                Typeface::Outline(font) => font.render(character, font_size, antialiasing),
+                Typeface::Bitmap(font) => {
+                    font.render(character, (font_size / font.line_height()).max(1))
+                }
+            })
+    }
+
+    fn load_bitmap_font(path: &str) -> Typeface {
+        let font = std::fs::read(path)
+            .map_err(|err| err.into())
+            .and_then(|data| BitmapFont::parse(&data));
+
+        match font {
+            Ok(font) => {
+                debug!("Loaded bitmap font: {:?}", path);
+                Typeface::Bitmap(font)
+            }
+            Err(err) => {
+                error!(
+                    "Failed to load bitmap font '{}': {:?}. Falling back to default",
+                    path, err
+                );
+                Typeface::Outline(OutlineFont::new(FontSelector::Default))
+            }
+        }
+    }
+}
+
+impl OutlineFont {
+    fn new(selector: FontSelector) -> Self {
         let library = freetype::Library::init().unwrap();
 
         let (data, font_index) = Self::load_font(selector);
@@ -52,11 +157,10 @@ impl FontManager {
                 ascender_only_scale,
                 offset_scale,
             },
-            cache: HashMap::new(),
         }
     }
 
-    pub fn get_font_size(&self, font_setting: FontSize, max_height: usize) -> usize {
+    fn get_font_size(&self, font_setting: FontSize, max_height: usize) -> usize {
         match font_setting {
             FontSize::Value(value) => value,
             FontSize::Auto => {
@@ -70,7 +174,7 @@ impl FontManager {
         }
     }
 
-    pub fn get_offset(&self, font_setting: FontSize, actual_font_size: usize) -> isize {
+    fn get_offset(&self, font_setting: FontSize, actual_font_size: usize) -> isize {
         match font_setting {
             FontSize::Value(_) | FontSize::Auto => {
                 let offset = actual_font_size as f64 * self.metrics.offset_scale;
@@ -80,23 +184,24 @@ impl FontManager {
         }
     }
 
-    pub fn get_character(&mut self, character: char, font_size: usize) -> &Character {
-        self.cache.entry((character, font_size)).or_insert_with(|| {
-            self.face
-                .set_pixel_sizes(font_size as u32, font_size as u32)
-                .unwrap();
-            self.face
-                .load_char(character as usize, LoadFlag::TARGET_MONO)
-                .unwrap();
-            let slot = self.face.glyph();
-            let metrics = slot.metrics();
-            let glyph = slot.get_glyph().unwrap();
-
-            Character {
-                metrics: metrics.into(),
-                bitmap: glyph.to_bitmap(RenderMode::Mono, None).unwrap().into(),
-            }
-        })
+    fn render(&self, character: char, font_size: usize, antialiasing: bool) -> Character {
+        let (load_flag, render_mode) = match antialiasing {
+            true => (LoadFlag::TARGET_NORMAL, RenderMode::Normal),
+            false => (LoadFlag::TARGET_MONO, RenderMode::Mono),
+        };
+
+        self.face
+            .set_pixel_sizes(font_size as u32, font_size as u32)
+            .unwrap();
+        self.face.load_char(character as usize, load_flag).unwrap();
+        let slot = self.face.glyph();
+        let metrics = slot.metrics();
+        let glyph = slot.get_glyph().unwrap();
+
+        Character {
+            metrics: metrics.into(),
+            bitmap: glyph.to_bitmap(render_mode, None).unwrap().into(),
+        }
     }
 
     fn select_font(selector: FontSelector) -> Result<(Font, u32), Box<dyn Error>> {
@@ -118,6 +223,10 @@ impl FontManager {
                 let font = handle.load()?;
                 Ok((font, 0 /* will this always be zero? */))
             }
+            FontSelector::Bitmap(_) => Err("Bitmap fonts can't be loaded as outline fonts".into()),
+            FontSelector::Named(name) => {
+                Err(format!("No font named '{}' in settings", name).into())
+            }
         }
     }
 
@@ -178,15 +287,42 @@ pub struct Bitmap {
     pub rows: usize,
     pub cols: usize,
     stride: usize,
+    grayscale: bool,
     buffer: Vec<u8>,
 }
 
 impl Bitmap {
-    pub fn get(&self, x: usize, y: usize) -> bool {
+    // Rows are padded to whole bytes, with the most significant bit first
+    pub fn mono(
+        offset_x: isize,
+        offset_y: isize,
+        rows: usize,
+        cols: usize,
+        buffer: Vec<u8>,
+    ) -> Self {
+        Self {
+            offset_x,
+            offset_y,
+            rows,
+            cols,
+            stride: cols.div_ceil(8),
+            grayscale: false,
+            buffer,
+        }
+    }
+
+    pub fn get_level(&self, x: usize, y: usize) -> u8 {
         let row_begin = y * self.stride;
+        if self.grayscale {
+            return self.buffer[row_begin + x];
+        }
+
         let mut byte = self.buffer[row_begin + x / 8];
         let bit = Bit::new(&mut byte, 7 - x % 8);
-        bit.get()
+        match bit.get() {
+            true => u8::MAX,
+            false => 0,
+        }
     }
 }
 
@@ -199,6 +335,7 @@ impl From<freetype::BitmapGlyph> for Bitmap {
             rows: bitmap.rows() as usize,
             cols: bitmap.width() as usize,
             stride: bitmap.pitch() as usize,
+            grayscale: matches!(bitmap.pixel_mode(), Ok(PixelMode::Gray)),
             buffer: bitmap.buffer().to_vec(),
         }
     }
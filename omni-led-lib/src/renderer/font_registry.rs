@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use crate::renderer::font_manager::FontManager;
+use crate::renderer::font_selector::FontSelector;
+
+// Fonts are loaded on first use and kept for the lifetime of the renderer, each with its own
+// glyph cache. Named fonts from the settings only map to selectors, so renaming them doesn't
+// reload anything.
+pub struct FontRegistry {
+    default: FontSelector,
+    named: HashMap<String, FontSelector>,
+    fonts: HashMap<FontSelector, FontManager>,
+    antialiasing: bool,
+}
+
+impl FontRegistry {
+    pub fn new(default: FontSelector, named: HashMap<String, FontSelector>) -> Self {
+        Self {
+            default,
+            named,
+            fonts: HashMap::new(),
+            antialiasing: false,
+        }
+    }
+
+    pub fn set_selectors(&mut self, default: FontSelector, named: HashMap<String, FontSelector>) {
+        self.default = default;
+        self.named = named;
+    }
+
+    pub fn set_antialiasing(&mut self, antialiasing: bool) {
+        self.antialiasing = antialiasing;
+        for font in self.fonts.values_mut() {
+            font.set_antialiasing(antialiasing);
+        }
+    }
+
+    // Unknown names are kept as they are, the font manager reports them once and falls back to
+    // the default font
+    pub fn get(&mut self, selector: Option<&FontSelector>) -> &mut FontManager {
+        let selector = self.resolve(selector.unwrap_or(&self.default)).clone();
+        let antialiasing = self.antialiasing;
+
+        self.fonts.entry(selector).or_insert_with_key(|selector| {
+            let mut font = FontManager::new(selector.clone());
+            font.set_antialiasing(antialiasing);
+            font
+        })
+    }
+
+    fn resolve<'a>(&'a self, selector: &'a FontSelector) -> &'a FontSelector {
+        match selector {
+            FontSelector::Named(name) => self.named.get(name).unwrap_or(selector),
+            selector => selector,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolving_names() {
+        let named = HashMap::from([("digits".to_string(), FontSelector::Default)]);
+        let mut registry = FontRegistry::new(FontSelector::Named("digits".to_string()), named);
+
+        registry.get(None);
+        registry.get(Some(&FontSelector::Default));
+        registry.get(Some(&FontSelector::Named("digits".to_string())));
+        assert_eq!(registry.fonts.len(), 1);
+
+        registry.get(Some(&FontSelector::Named("unknown".to_string())));
+        assert_eq!(registry.fonts.len(), 2);
+    }
+}
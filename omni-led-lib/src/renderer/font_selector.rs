@@ -1,23 +1,31 @@
 use mlua::UserData;
 use omni_led_derive::{FromLuaValue, LuaEnum};
 
-#[derive(Debug, Clone, PartialEq, LuaEnum)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, LuaEnum)]
 pub enum FontSelector {
     Default,
     Filesystem(FilesystemSelector),
     System(SystemSelector),
+    Bitmap(BitmapSelector),
+    #[mlua(implicit_construct)]
+    Named(String),
 }
 
 impl UserData for FontSelector {}
 
-#[derive(Debug, Clone, PartialEq, FromLuaValue)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, FromLuaValue)]
+pub struct BitmapSelector {
+    pub path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, FromLuaValue)]
 pub struct FilesystemSelector {
     pub path: String,
     #[mlua(default = 0)]
     pub font_index: u32,
 }
 
-#[derive(Debug, Clone, PartialEq, FromLuaValue)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, FromLuaValue)]
 pub struct SystemSelector {
     pub names: Vec<FamilyName>,
     #[mlua(default = Style::Normal)]
@@ -28,7 +36,7 @@ pub struct SystemSelector {
     pub stretch: Stretch,
 }
 
-#[derive(Debug, Clone, PartialEq, LuaEnum)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, LuaEnum)]
 pub enum FamilyName {
     Title(String),
     Serif,
@@ -53,7 +61,7 @@ impl Into<font_kit::family_name::FamilyName> for FamilyName {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, LuaEnum)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, LuaEnum)]
 pub enum Style {
     Normal,
     Italic,
@@ -72,7 +80,7 @@ impl Into<font_kit::properties::Style> for Style {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, LuaEnum)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, LuaEnum)]
 pub enum Weight {
     Thin,
     ExtraLight,
@@ -103,7 +111,7 @@ impl Into<font_kit::properties::Weight> for Weight {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, LuaEnum)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, LuaEnum)]
 pub enum Stretch {
     UltraCondensed,
     ExtraCondensed,
@@ -161,6 +169,13 @@ mod tests {
         assert_eq!(selector, FontSelector::Default);
     }
 
+    #[test]
+    fn named_selector() {
+        let lua = get_lua_env();
+        let selector = eval!(lua, { "digits" });
+        assert_eq!(selector, FontSelector::Named("digits".to_string()));
+    }
+
     #[test]
     fn filesystem_selector() {
         const PATH: &str = "my/path";
@@ -0,0 +1,228 @@
+use mlua::{Table, Value};
+use std::collections::{HashMap, VecDeque};
+
+use crate::renderer::shapes::{self, Pixels};
+use crate::script_handler::script_data_types::{GraphStyle, Point, Range, Size};
+
+struct History {
+    values: VecDeque<f32>,
+    capacity: usize,
+    pending: bool,
+}
+
+// Values of event keys used as graph sources. Sources are sampled from the moment they are first
+// shown, and keep their history when switching between screens.
+#[derive(Default)]
+pub struct GraphHistory {
+    sources: HashMap<String, History>,
+}
+
+// Whether an event with `key` carries the value of `source`, e.g. 'SYSTEM' for 'SYSTEM.Load'
+pub fn is_sampled_by(source: &str, key: &str) -> bool {
+    source
+        .strip_prefix(key)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+impl GraphHistory {
+    // Called for every dispatched event, sources are sampled once per update
+    pub fn mark_for_sampling(&mut self, key: &str) {
+        for (source, history) in &mut self.sources {
+            if is_sampled_by(source, key) {
+                history.pending = true;
+            }
+        }
+    }
+
+    pub fn sample(&mut self, env: &Table) -> mlua::Result<()> {
+        for (source, history) in &mut self.sources {
+            if !std::mem::take(&mut history.pending) {
+                continue;
+            }
+
+            let value = match Self::get_value(env, source)? {
+                Value::Integer(value) => value as f32,
+                Value::Number(value) => value as f32,
+                _ => continue,
+            };
+
+            history.values.push_back(value);
+            while history.values.len() > history.capacity {
+                history.values.pop_front();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn values(&mut self, source: &str, capacity: usize) -> Vec<f32> {
+        let history = self
+            .sources
+            .entry(source.to_string())
+            .or_insert_with(|| History {
+                values: VecDeque::new(),
+                capacity,
+                pending: false,
+            });
+        history.capacity = history.capacity.max(capacity);
+        history.values.iter().copied().collect()
+    }
+
+    // Resolves keys like 'SYSTEM.Cpus.1.Utilization', numeric parts are used as array indices
+    fn get_value(env: &Table, source: &str) -> mlua::Result<Value> {
+        let mut value = Value::Table(env.clone());
+        for key in source.split('.') {
+            let table = match value {
+                Value::Table(table) => table,
+                _ => return Ok(Value::Nil),
+            };
+
+            value = table.get(key)?;
+            if value.is_nil()
+                && let Ok(index) = key.parse::<usize>()
+            {
+                value = table.get(index)?;
+            }
+        }
+        Ok(value)
+    }
+}
+
+// The newest value is drawn at the right edge of the graph
+pub fn graph(
+    values: &[f32],
+    capacity: usize,
+    range: &Range,
+    style: GraphStyle,
+    size: Size,
+) -> Pixels {
+    if size.width == 0 || size.height == 0 || capacity == 0 {
+        return Vec::new();
+    }
+
+    let values = &values[values.len().saturating_sub(capacity)..];
+    let first_slot = capacity - values.len();
+    let span = range.max - range.min;
+    let fraction = |value: f32| match span > 0.0 {
+        true => ((value - range.min) / span).clamp(0.0, 1.0),
+        false => 0.0,
+    };
+
+    let mut pixels = Vec::new();
+    match style {
+        GraphStyle::Bars => {
+            for (index, value) in values.iter().enumerate() {
+                let slot = first_slot + index;
+                let left = slot * size.width / capacity;
+                let right = ((slot + 1) * size.width / capacity).max(left + 1);
+                let height = (fraction(*value) * size.height as f32).round() as usize;
+                for x in left..right.min(size.width) {
+                    for y in size.height - height..size.height {
+                        pixels.push((x as isize, y as isize));
+                    }
+                }
+            }
+        }
+        GraphStyle::Line | GraphStyle::Filled => {
+            let point = |index: usize, value: f32| Point {
+                x: match capacity {
+                    1 => size.width - 1,
+                    _ => (first_slot + index) * (size.width - 1) / (capacity - 1),
+                },
+                y: size.height - 1 - (fraction(value) * (size.height - 1) as f32).round() as usize,
+            };
+
+            let points: Vec<Point> = values
+                .iter()
+                .enumerate()
+                .map(|(index, value)| point(index, *value))
+                .collect();
+            match points.as_slice() {
+                [single] => pixels.extend(shapes::line(*single, *single)),
+                points => {
+                    for pair in points.windows(2) {
+                        pixels.extend(shapes::line(pair[0], pair[1]));
+                    }
+                }
+            }
+
+            if style == GraphStyle::Filled {
+                let bottom = size.height as isize;
+                pixels = pixels
+                    .into_iter()
+                    .flat_map(|(x, y)| (y..bottom).map(move |y| (x, y)))
+                    .collect();
+            }
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mlua::{Lua, chunk};
+
+    #[test]
+    fn samples_marked_sources() {
+        let lua = Lua::new();
+        let env: Table = lua
+            .load(chunk! {
+                return { SYSTEM = { Cpus = { { Utilization = 10 }, { Utilization = 20 } } } }
+            })
+            .eval()
+            .unwrap();
+
+        let cpu: Table = env
+            .get::<Table>("SYSTEM")
+            .and_then(|system| system.get::<Table>("Cpus"))
+            .and_then(|cpus| cpus.get(2))
+            .unwrap();
+
+        let mut history = GraphHistory::default();
+        assert!(history.values("SYSTEM.Cpus.2.Utilization", 2).is_empty());
+
+        for value in [30, 40, 50] {
+            history.mark_for_sampling("SYSTEM");
+            history.sample(&env).unwrap();
+            cpu.set("Utilization", value).unwrap();
+        }
+
+        // Unrelated events don't add samples
+        history.mark_for_sampling("SYSTEM.Memory");
+        history.mark_for_sampling("SYS");
+        history.sample(&env).unwrap();
+
+        assert_eq!(history.values("SYSTEM.Cpus.2.Utilization", 2), [30.0, 40.0]);
+    }
+
+    #[test]
+    fn graph_styles() {
+        let range = Range { min: 0.0, max: 4.0 };
+        let size = Size {
+            width: 4,
+            height: 5,
+        };
+        let draw = |style: GraphStyle| {
+            let mut rows = vec![vec!['.'; size.width]; size.height];
+            for (x, y) in graph(&[0.0, 2.0, 4.0], 4, &range, style, size) {
+                rows[y as usize][x as usize] = '#';
+            }
+            rows.into_iter()
+                .map(|row| row.into_iter().collect())
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(
+            draw(GraphStyle::Line),
+            ["...#", "...#", "..#.", "..#.", ".#.."]
+        );
+        assert_eq!(
+            draw(GraphStyle::Filled),
+            ["...#", "...#", "..##", "..##", ".###"]
+        );
+        assert_eq!(
+            draw(GraphStyle::Bars),
+            ["...#", "...#", "..##", "..##", "..##"]
+        );
+    }
+}
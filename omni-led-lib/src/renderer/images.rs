@@ -1,40 +1,96 @@
 use image::codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder};
 use image::imageops::FilterType;
-use image::{AnimationDecoder, DynamicImage, ImageFormat};
+use image::{AnimationDecoder, DynamicImage, GrayImage, ImageFormat, RgbImage};
 use std::collections::HashMap;
 use std::io::{BufReader, Cursor};
 
-use crate::renderer::buffer::{BitBuffer, BufferTrait};
-use crate::script_handler::script_data_types::{ImageData, Size};
+use crate::renderer::buffer::BitBuffer;
+use crate::renderer::dither;
+use crate::script_handler::script_data_types::{
+    Dither, HorizontalAlign, Image, ImageData, ImageFilter, ImageFit, Point, Size, VerticalAlign,
+};
 
-pub type CacheKey = (u64, Size, u8);
-pub type ImageCache = HashMap<CacheKey, Vec<BitBuffer>>;
+pub type CacheKey = (u64, Size, Processing);
+pub type ImageCache = HashMap<CacheKey, Vec<RenderedImage>>;
+
+// Conversion of the image for a display, frames are cached separately for each one
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Processing {
+    // Only binary displays use dithering, together with the threshold
+    dither: Option<(Dither, u8)>,
+    gamma: u32,
+    contrast: u32,
+    fit: ImageFit,
+    filter: ImageFilter,
+    align: HorizontalAlign,
+    valign: VerticalAlign,
+    // Position and size of the cropped area in source pixels
+    crop: Option<(usize, usize, usize, usize)>,
+}
+
+impl Processing {
+    pub fn new(image: &Image, binary: bool) -> Self {
+        Self {
+            dither: binary.then_some((image.dither, image.threshold)),
+            gamma: image.gamma.to_bits(),
+            contrast: image.contrast.to_bits(),
+            fit: image.fit,
+            filter: image.filter,
+            align: image.align,
+            valign: image.valign,
+            crop: image.crop.map(|crop| {
+                (
+                    crop.position.x,
+                    crop.position.y,
+                    crop.size.width,
+                    crop.size.height,
+                )
+            }),
+        }
+    }
+}
+
+// Frames only contain the visible part of the image, `offset` is its position inside the widget
+pub struct RenderedImage {
+    pub offset: Point,
+    pub luma: GrayImage,
+    pub color: RgbImage,
+    pub dithered: Option<BitBuffer>,
+}
 
 pub fn render_image<'a>(
     cache: &'a mut ImageCache,
     image: &ImageData,
     size: Size,
-    threshold: u8,
     animated: bool,
-) -> &'a Vec<BitBuffer> {
+    processing: Processing,
+) -> &'a Vec<RenderedImage> {
     cache
-        .entry((image.hash.unwrap(), size, threshold))
+        .entry((image.hash.unwrap(), size, processing))
         .or_insert_with(|| {
             if animated {
-                render_animated_image(image, size, threshold)
+                render_animated_image(image, size, processing)
             } else {
-                render_static_image(image, size, threshold)
+                render_static_image(image, size, processing)
             }
         })
 }
 
-fn render_static_image(image: &ImageData, size: Size, threshold: u8) -> Vec<BitBuffer> {
+fn render_static_image(
+    image: &ImageData,
+    size: Size,
+    processing: Processing,
+) -> Vec<RenderedImage> {
     let image = image::load_from_memory_with_format(&image.bytes, image.format).unwrap();
 
-    vec![render_into_buffer(image, size, threshold)]
+    vec![render_into_buffer(image, size, processing)]
 }
 
-fn render_animated_image(image: &ImageData, size: Size, threshold: u8) -> Vec<BitBuffer> {
+fn render_animated_image(
+    image: &ImageData,
+    size: Size,
+    processing: Processing,
+) -> Vec<RenderedImage> {
     let reader = BufReader::new(Cursor::new(&image.bytes));
 
     let frames = match image.format {
@@ -59,20 +115,181 @@ fn render_animated_image(image: &ImageData, size: Size, threshold: u8) -> Vec<Bi
         .into_iter()
         .map(|frame| {
             let image = DynamicImage::from(frame.into_buffer());
-            render_into_buffer(image, size, threshold)
+            render_into_buffer(image, size, processing)
         })
         .collect()
 }
 
-fn render_into_buffer(image: DynamicImage, size: Size, threshold: u8) -> BitBuffer {
-    let image = image.resize_exact(size.width as u32, size.height as u32, FilterType::Nearest);
-    let image = image.into_luma8();
-
-    let mut buffer = BitBuffer::new(size);
-    for (x, y, pixel) in image.enumerate_pixels() {
-        if pixel[0] >= threshold {
-            buffer.set(x as usize, y as usize);
+fn render_into_buffer(image: DynamicImage, size: Size, processing: Processing) -> RenderedImage {
+    let image = match processing.crop {
+        Some((x, y, width, height)) => {
+            // Crop is clamped to the image, but always keeps at least a single pixel
+            let x = (x as u32).min(image.width() - 1);
+            let y = (y as u32).min(image.height() - 1);
+            let width = (width as u32).min(image.width() - x);
+            let height = (height as u32).min(image.height() - y);
+            image.crop_imm(x, y, width, height)
         }
+        None => image,
+    };
+
+    let source = (image.width() as usize, image.height() as usize);
+    let (width, height) = fit_size(source, size, processing.fit);
+    let image = match (width, height) == source {
+        true => image,
+        false => image.resize_exact(width as u32, height as u32, filter_type(processing.filter)),
+    };
+
+    let x = align_offset(size.width, width, processing.align);
+    let y = align_offset(
+        size.height,
+        height,
+        match processing.valign {
+            VerticalAlign::Top => HorizontalAlign::Left,
+            VerticalAlign::Center => HorizontalAlign::Center,
+            VerticalAlign::Bottom => HorizontalAlign::Right,
+        },
+    );
+
+    // Parts that end up outside of the widget are cut off
+    let (source_x, source_y) = ((-x).max(0) as usize, (-y).max(0) as usize);
+    let offset = Point {
+        x: x.max(0) as usize,
+        y: y.max(0) as usize,
+    };
+    let image = image.crop_imm(
+        source_x as u32,
+        source_y as u32,
+        (width - source_x).min(size.width - offset.x) as u32,
+        (height - source_y).min(size.height - offset.y) as u32,
+    );
+
+    let (gamma, contrast) = (
+        f32::from_bits(processing.gamma),
+        f32::from_bits(processing.contrast),
+    );
+    let mut luma = image.to_luma8();
+    dither::adjust(&mut luma, gamma, contrast);
+    let mut color = image.into_rgb8();
+    dither::adjust(&mut color, gamma, contrast);
+    let dithered = processing
+        .dither
+        .map(|(mode, threshold)| dither::dither(&luma, mode, threshold));
+
+    RenderedImage {
+        offset,
+        luma,
+        color,
+        dithered,
+    }
+}
+
+fn fit_size(source: (usize, usize), size: Size, fit: ImageFit) -> (usize, usize) {
+    let (width, height) = source;
+    // Compares the aspect ratios without rounding, width is the limiting dimension when true
+    let wider = width * size.height >= height * size.width;
+    let scale_to_width = match fit {
+        ImageFit::Fill => return (size.width, size.height),
+        ImageFit::None => return source,
+        ImageFit::Contain => wider,
+        ImageFit::Cover => !wider,
+    };
+
+    let scale = |value: usize, to: usize, from: usize| ((value * to + from / 2) / from).max(1);
+    match scale_to_width {
+        true => (size.width, scale(height, size.width, width)),
+        false => (scale(width, size.height, height), size.height),
+    }
+}
+
+// Offset of the scaled image from the widget edge, negative when the image is larger than the widget
+fn align_offset(space: usize, length: usize, align: HorizontalAlign) -> isize {
+    let space = space as isize - length as isize;
+    match align {
+        HorizontalAlign::Left => 0,
+        HorizontalAlign::Center => space / 2,
+        HorizontalAlign::Right => space,
+    }
+}
+
+fn filter_type(filter: ImageFilter) -> FilterType {
+    match filter {
+        ImageFilter::Nearest => FilterType::Nearest,
+        ImageFilter::Triangle => FilterType::Triangle,
+        ImageFilter::Lanczos3 => FilterType::Lanczos3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_modes() {
+        let size = Size {
+            width: 128,
+            height: 40,
+        };
+        assert_eq!(fit_size((64, 64), size, ImageFit::Fill), (128, 40));
+        assert_eq!(fit_size((64, 64), size, ImageFit::Contain), (40, 40));
+        assert_eq!(fit_size((64, 64), size, ImageFit::Cover), (128, 128));
+        assert_eq!(fit_size((64, 64), size, ImageFit::None), (64, 64));
+        assert_eq!(fit_size((300, 50), size, ImageFit::Contain), (128, 21));
+
+        assert_eq!(align_offset(128, 40, HorizontalAlign::Center), 44);
+        assert_eq!(align_offset(40, 128, HorizontalAlign::Center), -44);
+        assert_eq!(align_offset(40, 128, HorizontalAlign::Right), -88);
+    }
+
+    #[test]
+    fn crop_and_alignment() {
+        let image = DynamicImage::ImageLuma8(GrayImage::from_fn(8, 8, |x, y| {
+            image::Luma([(y * 8 + x) as u8])
+        }));
+        let size = Size {
+            width: 5,
+            height: 3,
+        };
+        let processing = |fit| Processing {
+            dither: None,
+            gamma: 1.0f32.to_bits(),
+            contrast: 1.0f32.to_bits(),
+            fit,
+            filter: ImageFilter::Nearest,
+            align: HorizontalAlign::Right,
+            valign: VerticalAlign::Top,
+            crop: Some((6, 2, 4, 4)),
+        };
+
+        // Crop is clamped to a 2x4 area at the right edge of the source image, bottom row is cut off
+        let rendered = render_into_buffer(image.clone(), size, processing(ImageFit::None));
+        assert_eq!((rendered.offset.x, rendered.offset.y), (3, 0));
+        assert_eq!(rendered.luma.as_raw(), &[22, 23, 30, 31, 38, 39]);
+
+        let rendered = render_into_buffer(image, size, processing(ImageFit::Contain));
+        assert_eq!((rendered.offset.x, rendered.offset.y), (3, 0));
+        assert_eq!(rendered.luma.dimensions(), (2, 3));
+    }
+
+    #[test]
+    fn adjusts_color() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, image::Rgb([0, 64, 192])));
+        let size = Size {
+            width: 1,
+            height: 1,
+        };
+        let processing = Processing {
+            dither: None,
+            gamma: 1.0f32.to_bits(),
+            contrast: 1.5f32.to_bits(),
+            fit: ImageFit::None,
+            filter: ImageFilter::Nearest,
+            align: HorizontalAlign::Left,
+            valign: VerticalAlign::Top,
+            crop: None,
+        };
+
+        let rendered = render_into_buffer(image, size, processing);
+        assert_eq!(rendered.color.as_raw(), &[0, 32, 224]);
     }
-    buffer
 }
@@ -1,9 +1,17 @@
 pub mod animation;
 pub mod animation_group;
+pub mod bitmap_font;
 pub mod buffer;
 pub mod font_selector;
+pub mod graph;
 pub mod renderer;
+pub mod screen_protection;
+pub mod shapes;
+pub mod text_layout;
+pub mod transition;
 
 mod bit;
+mod dither;
 mod font_manager;
+mod font_registry;
 mod images;
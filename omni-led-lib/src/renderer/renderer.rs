@@ -3,17 +3,20 @@ use num_traits::clamp;
 use std::cmp::max;
 use std::collections::HashMap;
 use std::hash::{DefaultHasher, Hash, Hasher};
-use std::str::Chars;
 
 use crate::common::user_data::UserDataRef;
 use crate::renderer::animation::{Animation, State};
 use crate::renderer::animation_group::AnimationGroup;
-use crate::renderer::buffer::{BitBuffer, Buffer, BufferTrait};
+use crate::renderer::buffer::{Buffer, BufferTrait};
 use crate::renderer::font_manager::FontManager;
+use crate::renderer::font_registry::FontRegistry;
+use crate::renderer::graph::{self, GraphHistory};
 use crate::renderer::images;
-use crate::renderer::images::ImageCache;
+use crate::renderer::images::{ImageCache, Processing, RenderedImage};
+use crate::renderer::shapes::{self, Pixels};
+use crate::renderer::text_layout;
 use crate::script_handler::script_data_types::{
-    Bar, Image, MemoryLayout, Modifiers, Point, Text, Widget,
+    Bar, Color, Graph, Image, MemoryLayout, Modifiers, Point, Text, TextWrap, VerticalAlign, Widget,
 };
 use crate::script_handler::script_data_types::{Rectangle, Size};
 use crate::settings::settings::Settings;
@@ -32,46 +35,77 @@ macro_rules! get_animation_settings {
 }
 
 pub struct Renderer {
-    font_manager: FontManager,
+    fonts: FontRegistry,
     image_cache: ImageCache,
     animation_settings: AnimationSettings,
+    graph_history: GraphHistory,
 }
 
 impl Renderer {
     pub fn new(lua: &Lua) -> Self {
         let settings = UserDataRef::<Settings>::load(lua);
-        let font_selector = settings.get().font.clone();
+        let settings = settings.get();
 
         Self {
-            font_manager: FontManager::new(font_selector),
+            fonts: FontRegistry::new(settings.font.clone(), settings.fonts.clone()),
             image_cache: ImageCache::new(),
             animation_settings: AnimationSettings::new(lua),
+            graph_history: GraphHistory::default(),
         }
     }
 
+    pub fn load_settings(&mut self, lua: &Lua) {
+        let settings = UserDataRef::<Settings>::load(lua);
+        let settings = settings.get();
+
+        // Already loaded fonts are kept, so switching between them doesn't load them again
+        self.fonts
+            .set_selectors(settings.font.clone(), settings.fonts.clone());
+        self.animation_settings = AnimationSettings::new(lua);
+    }
+
+    pub fn graph_history(&mut self) -> &mut GraphHistory {
+        &mut self.graph_history
+    }
+
+    // Animations advance only once per update. Other targets rendering the same layout pass
+    // `advance_animations = false`, to draw the same animation steps in their own size.
     pub fn render(
         &mut self,
         animation_groups: &mut HashMap<usize, AnimationGroup>,
         screen_changed: bool,
+        advance_animations: bool,
         size: Size,
         mut widgets: Vec<Widget>,
         memory_layout: MemoryLayout,
     ) -> (State, Buffer) {
         let mut buffer = Buffer::new(size, memory_layout);
-
-        self.calculate_animations(animation_groups, &mut widgets, screen_changed);
+        self.fonts.set_antialiasing(buffer.is_grayscale());
+
+        let binary = !buffer.is_grayscale();
+        self.calculate_animations(
+            animation_groups,
+            &mut widgets,
+            screen_changed,
+            advance_animations,
+            binary,
+        );
 
         for operation in widgets {
             match operation {
                 Widget::Bar(bar) => Self::render_bar(&mut buffer, bar),
                 Widget::Image(image) => self.render_image(&mut buffer, image, animation_groups),
                 Widget::Text(text) => self.render_text(&mut buffer, text, animation_groups),
+                Widget::Graph(graph) => self.render_graph(&mut buffer, graph),
+                shape => Self::render_shape(&mut buffer, shape),
             }
         }
 
-        animation_groups
-            .iter_mut()
-            .for_each(|(_, group)| group.sync());
+        if advance_animations {
+            animation_groups
+                .iter_mut()
+                .for_each(|(_, group)| group.sync());
+        }
 
         let state = Self::animation_state(animation_groups);
 
@@ -117,6 +151,84 @@ impl Renderer {
         }
     }
 
+    // Shapes are clipped to the widget area, same as text
+    fn render_shape(buffer: &mut Buffer, widget: Widget) {
+        let fill = |filled: bool, outline: Pixels| match filled {
+            true => shapes::fill(outline),
+            false => outline,
+        };
+
+        let (position, size, modifiers, pixels) = match widget {
+            Widget::Line(line) => {
+                let pixels = shapes::line(line.start, line.end);
+                (line.position, line.size, line.modifiers, pixels)
+            }
+            Widget::Rect(rect) => {
+                let pixels = fill(rect.filled, shapes::rectangle(rect.size, rect.radius));
+                (rect.position, rect.size, rect.modifiers, pixels)
+            }
+            Widget::Circle(circle) => {
+                let pixels = fill(circle.filled, shapes::circle(circle.size));
+                (circle.position, circle.size, circle.modifiers, pixels)
+            }
+            Widget::Ellipse(ellipse) => {
+                let pixels = fill(ellipse.filled, shapes::ellipse(ellipse.size));
+                (ellipse.position, ellipse.size, ellipse.modifiers, pixels)
+            }
+            Widget::Arc(arc) => {
+                let pixels = shapes::arc(arc.size, arc.start_angle, arc.end_angle);
+                (arc.position, arc.size, arc.modifiers, pixels)
+            }
+            Widget::Polygon(polygon) => {
+                let pixels = match polygon.filled {
+                    true => shapes::fill_polygon(&polygon.points),
+                    false => shapes::polygon(&polygon.points),
+                };
+                (polygon.position, polygon.size, polygon.modifiers, pixels)
+            }
+            Widget::Bar(_) | Widget::Image(_) | Widget::Text(_) | Widget::Graph(_) => return,
+        };
+
+        Self::render_pixels(buffer, position, size, &modifiers, pixels);
+    }
+
+    fn render_graph(&mut self, buffer: &mut Buffer, widget: Graph) {
+        let capacity = widget.capacity.unwrap_or(widget.size.width);
+        let values = match (&widget.values, &widget.source) {
+            (Some(values), _) => values.clone(),
+            (None, Some(source)) => self.graph_history.values(source, capacity),
+            (None, None) => Vec::new(),
+        };
+
+        let pixels = graph::graph(&values, capacity, &widget.range, widget.style, widget.size);
+        Self::render_pixels(
+            buffer,
+            widget.position,
+            widget.size,
+            &widget.modifiers,
+            pixels,
+        );
+    }
+
+    fn render_pixels(
+        buffer: &mut Buffer,
+        position: Point,
+        size: Size,
+        modifiers: &Modifiers,
+        pixels: Pixels,
+    ) {
+        if modifiers.clear_background {
+            Self::clear_background(buffer, position, size, modifiers);
+        }
+
+        let rect = Rectangle { position, size };
+        for (x, y) in pixels {
+            if x >= 0 && y >= 0 && x < size.width as isize && y < size.height as isize {
+                buffer.set(x, y, &rect, modifiers);
+            }
+        }
+    }
+
     fn render_image(
         &mut self,
         buffer: &mut Buffer,
@@ -131,8 +243,8 @@ impl Renderer {
             &mut self.image_cache,
             &widget.image,
             widget.size,
-            widget.threshold,
             widget.animated,
+            Processing::new(&widget, !buffer.is_grayscale()),
         );
 
         let frame = if widget.animated {
@@ -148,7 +260,7 @@ impl Renderer {
         Self::render_image_impl(buffer, &widget, frame);
     }
 
-    fn render_image_impl(buffer: &mut Buffer, widget: &Image, rendered: &BitBuffer) {
+    fn render_image_impl(buffer: &mut Buffer, widget: &Image, rendered: &RenderedImage) {
         if widget.modifiers.clear_background {
             Self::clear_background(buffer, widget.position, widget.size, &widget.modifiers);
         }
@@ -158,11 +270,26 @@ impl Renderer {
             size: widget.size,
         };
 
-        for y in 0..rendered.height() {
-            for x in 0..rendered.width() {
-                if rendered.get(x, y).unwrap() {
-                    buffer.set(x as isize, y as isize, &rect, &widget.modifiers);
+        let (color, grayscale) = (buffer.is_color(), buffer.is_grayscale());
+        for (x, y, pixel) in rendered.luma.enumerate_pixels() {
+            let luminance = pixel[0];
+            let (target_x, target_y) = (
+                (rendered.offset.x + x as usize) as isize,
+                (rendered.offset.y + y as usize) as isize,
+            );
+            // Black pixels are left out on every display, so that the background stays visible
+            if color {
+                let [r, g, b] = rendered.color.get_pixel(x, y).0;
+                if [r, g, b] != [0, 0, 0] {
+                    let color = Color { r, g, b };
+                    buffer.set_color_at(target_x, target_y, &rect, &widget.modifiers, color);
                 }
+            } else if grayscale && luminance > 0 {
+                buffer.blend(target_x, target_y, &rect, &widget.modifiers, luminance);
+            } else if let Some(dithered) = &rendered.dithered
+                && dithered.get(x as usize, y as usize).unwrap()
+            {
+                buffer.set(target_x, target_y, &rect, &widget.modifiers);
             }
         }
     }
@@ -181,49 +308,132 @@ impl Renderer {
             Self::clear_background(buffer, widget.position, widget.size, &widget.modifiers);
         }
 
-        let mut characters = widget.text.chars();
+        let step = match widget.scrolling {
+            true => {
+                let hash = widget.hash.unwrap();
+                let group = Self::get_animation_group(animation_groups, widget.animation_group);
+                let animation = group.entry(hash).unwrap();
+                animation.step()
+            }
+            false => 0,
+        };
 
-        if widget.scrolling {
-            let hash = widget.hash.unwrap();
-            let group = Self::get_animation_group(animation_groups, widget.animation_group);
-            let animation = group.entry(hash).unwrap();
-            let step = animation.step();
+        let font_manager = self.fonts.get(widget.font.as_ref());
+        let font_size = font_manager.get_font_size(widget.font_size, widget.size.height);
+        let lines = Self::layout_text(font_manager, &widget, font_size, step);
+        Self::render_text_impl(buffer, font_manager, &widget, font_size, lines);
+    }
 
-            for _ in 0..step {
-                _ = characters.next();
+    // Lines of text that are visible at the current scrolling `step`. Without wrapping the text
+    // scrolls horizontally by characters, otherwise it scrolls vertically by lines.
+    fn layout_text(
+        font_manager: &mut FontManager,
+        widget: &Text,
+        font_size: usize,
+        step: usize,
+    ) -> Vec<String> {
+        let width = widget.size.width as isize;
+        let line_height = font_manager.get_line_height(font_size);
+        let visible =
+            text_layout::visible_lines(widget.size.height, line_height, widget.line_spacing);
+        let mut advance = |character| {
+            font_manager
+                .get_character(character, font_size)
+                .metrics
+                .advance
+        };
+
+        let mut lines = text_layout::wrap(&widget.text, width, widget.wrap, &mut advance);
+        match widget.wrap {
+            TextWrap::None => {
+                for line in &mut lines {
+                    *line = line.chars().skip(step).collect();
+                }
+            }
+            TextWrap::Word | TextWrap::Char => {
+                lines.drain(..step.min(lines.len()));
             }
         }
 
-        Self::render_text_impl(buffer, &mut self.font_manager, &widget, characters);
+        let truncated = lines.len() > visible;
+        lines.truncate(visible);
+
+        if widget.ellipsis && !widget.scrolling {
+            let last = lines.len() - 1;
+            for (index, line) in lines.iter_mut().enumerate() {
+                let overflows = text_layout::line_width(line, &mut advance) > width;
+                if overflows || (truncated && index == last) {
+                    *line = text_layout::ellipsize(line, width, &mut advance);
+                }
+            }
+        }
+        lines
     }
 
     fn render_text_impl(
         buffer: &mut Buffer,
         font_manager: &mut FontManager,
         widget: &Text,
-        characters: Chars,
+        font_size: usize,
+        lines: Vec<String>,
+    ) {
+        let text_offset = widget
+            .text_offset
+            .unwrap_or_else(|| font_manager.get_offset(widget.font_size, font_size));
+
+        let height = widget.size.height as isize;
+        let line_pitch = font_manager.get_line_height(font_size) as isize + widget.line_spacing;
+        let block_height = line_pitch * lines.len() as isize - widget.line_spacing;
+        let block_bottom = match widget.valign {
+            VerticalAlign::Top => block_height,
+            VerticalAlign::Center => (height + block_height) / 2,
+            VerticalAlign::Bottom => height,
+        };
+
+        for (index, line) in lines.iter().enumerate() {
+            let lines_below = (lines.len() - 1 - index) as isize;
+            let cursor_y = block_bottom - lines_below * line_pitch;
+            Self::render_line(
+                buffer,
+                font_manager,
+                widget,
+                font_size,
+                line,
+                cursor_y - text_offset,
+            );
+        }
+    }
+
+    fn render_line(
+        buffer: &mut Buffer,
+        font_manager: &mut FontManager,
+        widget: &Text,
+        font_size: usize,
+        line: &str,
+        baseline: isize,
     ) {
         let rect = Rectangle {
             position: widget.position,
             size: widget.size,
         };
 
-        let font_size = font_manager.get_font_size(widget.font_size, widget.size.height);
-        let text_offset = widget
-            .text_offset
-            .unwrap_or_else(|| font_manager.get_offset(widget.font_size, font_size));
-
-        let mut cursor_x = 0;
-        let cursor_y = widget.size.height as isize;
+        let line_width = text_layout::line_width(line, &mut |character| {
+            font_manager
+                .get_character(character, font_size)
+                .metrics
+                .advance
+        });
+        let mut cursor_x =
+            text_layout::align_offset(widget.align, rect.size.width as isize, line_width);
 
-        for character in characters {
+        for character in line.chars() {
             let character = font_manager.get_character(character, font_size);
             let bitmap = &character.bitmap;
 
             for bitmap_y in 0..bitmap.rows as isize {
                 for bitmap_x in 0..bitmap.cols as isize {
                     let x = cursor_x + bitmap_x + bitmap.offset_x;
-                    let y = cursor_y + bitmap_y - bitmap.offset_y - text_offset;
+                    let y = baseline + bitmap_y - bitmap.offset_y;
 
                     if x < 0
                         || y < 0
@@ -233,8 +443,9 @@ impl Renderer {
                         continue;
                     }
 
-                    if bitmap.get(bitmap_x as usize, bitmap_y as usize) {
-                        buffer.set(x, y, &rect, &widget.modifiers);
+                    let coverage = bitmap.get_level(bitmap_x as usize, bitmap_y as usize);
+                    if coverage > 0 {
+                        buffer.blend(x, y, &rect, &widget.modifiers, coverage);
                     }
                 }
             }
@@ -251,10 +462,19 @@ impl Renderer {
         animation_groups: &mut HashMap<usize, AnimationGroup>,
         widgets: &mut Vec<Widget>,
         screen_changed: bool,
+        advance_animations: bool,
+        binary: bool,
     ) {
         for widget in widgets {
             match widget {
-                Widget::Bar(_) => continue,
+                Widget::Bar(_)
+                | Widget::Line(_)
+                | Widget::Rect(_)
+                | Widget::Circle(_)
+                | Widget::Ellipse(_)
+                | Widget::Arc(_)
+                | Widget::Polygon(_)
+                | Widget::Graph(_) => continue,
                 Widget::Image(image) => {
                     if !image.animated {
                         continue;
@@ -269,8 +489,8 @@ impl Renderer {
                             &mut self.image_cache,
                             &image.image,
                             image.size,
-                            image.threshold,
                             image.animated,
+                            Processing::new(image, binary),
                         );
                         Animation::new(
                             settings.ticks_at_edge,
@@ -290,7 +510,8 @@ impl Renderer {
                     let group = Self::get_animation_group(animation_groups, text.animation_group);
                     group.entry(text.hash.unwrap()).or_insert_with(|| {
                         let settings = get_animation_settings!(self.animation_settings, text);
-                        let steps = Self::pre_render_text(&mut self.font_manager, text);
+                        let font_manager = self.fonts.get(text.font.as_ref());
+                        let steps = Self::pre_render_text(font_manager, text);
                         Animation::new(
                             settings.ticks_at_edge,
                             settings.ticks_per_move,
@@ -302,6 +523,10 @@ impl Renderer {
             };
         }
 
+        if !advance_animations {
+            return;
+        }
+
         for (_, group) in &mut *animation_groups {
             group.pre_sync();
         }
@@ -359,11 +584,35 @@ impl Renderer {
 
     fn pre_render_text(font_manager: &mut FontManager, text: &Text) -> usize {
         let font_size = font_manager.get_font_size(text.font_size, text.size.height);
+
+        if text.wrap != TextWrap::None {
+            let line_height = font_manager.get_line_height(font_size);
+            let visible =
+                text_layout::visible_lines(text.size.height, line_height, text.line_spacing);
+            let lines = text_layout::wrap(
+                &text.text,
+                text.size.width as isize,
+                text.wrap,
+                &mut |character| {
+                    font_manager
+                        .get_character(character, font_size)
+                        .metrics
+                        .advance
+                },
+            );
+            return lines.len().saturating_sub(visible) + 1;
+        }
+
         let text_width = text.size.width;
         let character = font_manager.get_character('a', font_size);
         let char_width = character.metrics.advance as usize;
         let max_characters = text_width / max(char_width, 1);
-        let len = text.text.chars().count();
+        let len = text
+            .text
+            .split('\n')
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
 
         if len <= max_characters {
             1
@@ -0,0 +1,227 @@
+use mlua::Lua;
+use omni_led_derive::FromLuaValue;
+use std::time::Duration;
+
+use crate::renderer::buffer::Buffer;
+use crate::script_handler::script_data_types::DurationWrapper;
+
+const SHIFT_PATTERN: [(usize, usize); 4] = [(0, 0), (1, 0), (1, 1), (0, 1)];
+
+#[derive(Clone, Debug, FromLuaValue)]
+#[mlua(impl_default)]
+pub struct ScreenProtectionSettings {
+    #[mlua(transform = transform_duration)]
+    #[mlua(default = None)]
+    pub pixel_shift_interval: Option<Duration>,
+
+    #[mlua(default = 1)]
+    pub pixel_shift_amount: usize,
+
+    #[mlua(transform = transform_duration)]
+    #[mlua(default = None)]
+    pub idle_timeout: Option<Duration>,
+
+    #[mlua(transform = transform_duration)]
+    #[mlua(default = None)]
+    pub invert_interval: Option<Duration>,
+}
+
+fn transform_duration(
+    duration: Option<DurationWrapper>,
+    _: &Lua,
+) -> mlua::Result<Option<Duration>> {
+    Ok(duration.map(|duration| duration.0))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Phase {
+    shift_x: usize,
+    shift_y: usize,
+    inverted: bool,
+}
+
+pub struct ScreenProtection {
+    settings: ScreenProtectionSettings,
+    elapsed: Duration,
+    idle: Duration,
+    applied: Option<Phase>,
+}
+
+impl ScreenProtection {
+    pub fn new(settings: ScreenProtectionSettings) -> Self {
+        Self {
+            settings,
+            elapsed: Duration::ZERO,
+            idle: Duration::ZERO,
+            applied: None,
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: ScreenProtectionSettings) {
+        self.settings = settings;
+        self.applied = None;
+    }
+
+    pub fn update(&mut self, time_passed: Duration) {
+        self.elapsed += time_passed;
+        self.idle += time_passed;
+    }
+
+    pub fn register_input(&mut self) {
+        self.idle = Duration::ZERO;
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.settings
+            .idle_timeout
+            .is_some_and(|timeout| self.idle >= timeout)
+    }
+
+    // Last presented frame is out of date, e.g. it should be shifted by a different amount now
+    pub fn needs_refresh(&self) -> bool {
+        self.applied.is_some_and(|applied| applied != self.phase())
+    }
+
+    pub fn apply(&mut self, buffer: Buffer) -> Buffer {
+        let phase = self.phase();
+        self.applied = Some(phase);
+
+        if phase == Phase::default() {
+            return buffer;
+        }
+
+        // Pixels pushed off one edge wrap around to the other, so that no content is lost
+        let (width, height) = (buffer.width(), buffer.height());
+        let (shift_x, shift_y) = (phase.shift_x % width.max(1), phase.shift_y % height.max(1));
+        let mut output = buffer.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let color = buffer.get_color(
+                    (x + width - shift_x) % width,
+                    (y + height - shift_y) % height,
+                );
+                let color = match phase.inverted {
+                    true => color.invert(),
+                    false => color,
+                };
+                output.set_color(x, y, color);
+            }
+        }
+        output
+    }
+
+    fn phase(&self) -> Phase {
+        let shift_cycle = Self::cycle(self.elapsed, self.settings.pixel_shift_interval);
+        let (shift_x, shift_y) = match shift_cycle {
+            Some(cycle) => {
+                let (x, y) = SHIFT_PATTERN[cycle % SHIFT_PATTERN.len()];
+                let amount = self.settings.pixel_shift_amount;
+                (x * amount, y * amount)
+            }
+            None => (0, 0),
+        };
+        let inverted = Self::cycle(self.elapsed, self.settings.invert_interval)
+            .is_some_and(|cycle| cycle % 2 == 1);
+
+        Phase {
+            shift_x,
+            shift_y,
+            inverted,
+        }
+    }
+
+    fn cycle(elapsed: Duration, interval: Option<Duration>) -> Option<usize> {
+        interval
+            .filter(|interval| !interval.is_zero())
+            .map(|interval| (elapsed.as_nanos() / interval.as_nanos()) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script_handler::script_data_types::{MemoryLayout, Size};
+
+    const SIZE: Size = Size {
+        width: 4,
+        height: 3,
+    };
+
+    fn make_buffer() -> Buffer {
+        let mut buffer = Buffer::new(SIZE, MemoryLayout::BitPerPixel);
+        buffer.set_pixel(0, 0, true);
+        buffer
+    }
+
+    fn make_protection(
+        pixel_shift_interval: Option<Duration>,
+        invert_interval: Option<Duration>,
+    ) -> ScreenProtection {
+        ScreenProtection::new(ScreenProtectionSettings {
+            pixel_shift_interval,
+            pixel_shift_amount: 1,
+            idle_timeout: Some(Duration::from_secs(10)),
+            invert_interval,
+        })
+    }
+
+    #[test]
+    fn pixel_shift_cycles_through_pattern() {
+        let mut protection = make_protection(Some(Duration::from_secs(1)), None);
+
+        for (x, y) in SHIFT_PATTERN.iter().chain(SHIFT_PATTERN.iter()) {
+            let output = protection.apply(make_buffer());
+            assert!(output.get(*x, *y), "Pixel not shifted to ({x}, {y})");
+
+            protection.update(Duration::from_millis(500));
+            assert!(!protection.needs_refresh());
+            protection.update(Duration::from_millis(500));
+            assert!(protection.needs_refresh());
+        }
+    }
+
+    #[test]
+    fn pixel_shift_wraps_around() {
+        let mut protection = make_protection(Some(Duration::from_secs(1)), None);
+        protection.update(Duration::from_secs(2));
+
+        let mut buffer = Buffer::new(SIZE, MemoryLayout::BitPerPixel);
+        buffer.set_pixel(SIZE.width - 1, SIZE.height - 1, true);
+        let output = protection.apply(buffer);
+        assert!(output.get(0, 0));
+        assert_eq!(
+            output.bytes().iter().map(|x| x.count_ones()).sum::<u32>(),
+            1
+        );
+    }
+
+    #[test]
+    fn inversion_alternates() {
+        let mut protection = make_protection(None, Some(Duration::from_secs(1)));
+
+        let output = protection.apply(make_buffer());
+        assert_eq!(output.bytes(), make_buffer().bytes());
+
+        protection.update(Duration::from_secs(1));
+        let output = protection.apply(make_buffer());
+        assert!(!output.get(0, 0));
+        assert!(output.get(1, 0));
+
+        protection.update(Duration::from_secs(1));
+        let output = protection.apply(make_buffer());
+        assert_eq!(output.bytes(), make_buffer().bytes());
+    }
+
+    #[test]
+    fn idle_resets_on_input() {
+        let mut protection = make_protection(None, None);
+
+        protection.update(Duration::from_secs(9));
+        assert!(!protection.is_idle());
+        protection.update(Duration::from_secs(1));
+        assert!(protection.is_idle());
+
+        protection.register_input();
+        assert!(!protection.is_idle());
+    }
+}
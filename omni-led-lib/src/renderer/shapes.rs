@@ -0,0 +1,294 @@
+use std::collections::BTreeMap;
+
+use crate::script_handler::script_data_types::{Point, Size};
+
+// Pixels of vector shapes, coordinates are relative to the upper-left corner of the widget
+pub type Pixels = Vec<(isize, isize)>;
+
+pub fn line(start: Point, end: Point) -> Pixels {
+    let mut pixels = Vec::new();
+    line_impl(&mut pixels, to_pixel(start), to_pixel(end));
+    pixels
+}
+
+// Outline of a rectangle covering the whole `size`, with optionally rounded corners
+pub fn rectangle(size: Size, radius: usize) -> Pixels {
+    if size.width == 0 || size.height == 0 {
+        return Vec::new();
+    }
+
+    let (right, bottom) = (size.width as isize - 1, size.height as isize - 1);
+    let radius = radius.min((size.width - 1) / 2).min((size.height - 1) / 2) as isize;
+
+    let mut pixels = Vec::new();
+    line_impl(&mut pixels, (radius, 0), (right - radius, 0));
+    line_impl(&mut pixels, (radius, bottom), (right - radius, bottom));
+    line_impl(&mut pixels, (0, radius), (0, bottom - radius));
+    line_impl(&mut pixels, (right, radius), (right, bottom - radius));
+
+    if radius > 0 {
+        let (left, top) = (radius, radius);
+        let (right, bottom) = (right - radius, bottom - radius);
+        for (x, y) in circle_octant(radius) {
+            for (dx, dy) in [(x, y), (y, x)] {
+                pixels.push((right + dx, bottom + dy));
+                pixels.push((left - dx, bottom + dy));
+                pixels.push((right + dx, top - dy));
+                pixels.push((left - dx, top - dy));
+            }
+        }
+    }
+    pixels
+}
+
+// Outline of an ellipse inscribed in `size`
+pub fn ellipse(size: Size) -> Pixels {
+    if size.width == 0 || size.height == 0 {
+        return Vec::new();
+    }
+
+    let (mut x0, mut x1) = (0, size.width as i64 - 1);
+    let (a, b) = (x1, size.height as i64 - 1);
+    let b1 = b & 1;
+
+    let mut dx = 4 * (1 - a) * b * b;
+    let mut dy = 4 * (b1 + 1) * a * a;
+    let mut err = dx + dy + b1 * a * a;
+
+    let mut y0 = (b + 1) / 2;
+    let mut y1 = y0 - b1;
+    let (a, b1) = (8 * a * a, 8 * b * b);
+
+    let mut pixels = Vec::new();
+    let mut plot = |x: i64, y: i64| pixels.push((x as isize, y as isize));
+    loop {
+        plot(x1, y0);
+        plot(x0, y0);
+        plot(x0, y1);
+        plot(x1, y1);
+
+        let e2 = 2 * err;
+        if e2 <= dy {
+            y0 += 1;
+            y1 -= 1;
+            dy += a;
+            err += dy;
+        }
+        if e2 >= dx || 2 * err > dy {
+            x0 += 1;
+            x1 -= 1;
+            dx += b1;
+            err += dx;
+        }
+        if x0 > x1 {
+            break;
+        }
+    }
+
+    // Finish the tips of very flat ellipses
+    while y0 - y1 < b {
+        plot(x0 - 1, y0);
+        plot(x1 + 1, y0);
+        plot(x0 - 1, y1);
+        plot(x1 + 1, y1);
+        y0 += 1;
+        y1 -= 1;
+    }
+    pixels
+}
+
+// Outline of the largest circle that fits in `size`, centered
+pub fn circle(size: Size) -> Pixels {
+    let diameter = size.width.min(size.height);
+    let offset_x = ((size.width - diameter) / 2) as isize;
+    let offset_y = ((size.height - diameter) / 2) as isize;
+
+    let size = Size {
+        width: diameter,
+        height: diameter,
+    };
+    ellipse(size)
+        .into_iter()
+        .map(|(x, y)| (x + offset_x, y + offset_y))
+        .collect()
+}
+
+// Part of the ellipse inscribed in `size`. Angles are in degrees, clockwise from the top.
+pub fn arc(size: Size, start_angle: f32, end_angle: f32) -> Pixels {
+    let sweep = end_angle - start_angle;
+    if sweep >= 360.0 {
+        return ellipse(size);
+    }
+    let sweep = sweep.rem_euclid(360.0);
+
+    let center_x = (size.width as f32 - 1.0) / 2.0;
+    let center_y = (size.height as f32 - 1.0) / 2.0;
+    ellipse(size)
+        .into_iter()
+        .filter(|(x, y)| {
+            let angle = (*x as f32 - center_x)
+                .atan2(center_y - *y as f32)
+                .to_degrees();
+            (angle - start_angle).rem_euclid(360.0) <= sweep
+        })
+        .collect()
+}
+
+// Closed outline going through all of the `points`
+pub fn polygon(points: &[Point]) -> Pixels {
+    let mut pixels = Vec::new();
+    for (index, start) in points.iter().enumerate() {
+        let end = points[(index + 1) % points.len()];
+        line_impl(&mut pixels, to_pixel(*start), to_pixel(end));
+    }
+    pixels
+}
+
+// Fills the polygon using the even-odd rule
+pub fn fill_polygon(points: &[Point]) -> Pixels {
+    let mut pixels = polygon(points);
+
+    let vertices: Vec<(f32, f32)> = points.iter().map(|p| (p.x as f32, p.y as f32)).collect();
+    let (min_y, max_y) = points.iter().fold((usize::MAX, 0), |(min, max), p| {
+        (min.min(p.y), max.max(p.y))
+    });
+
+    for y in min_y..=max_y {
+        let y = y as f32;
+        let mut crossings: Vec<f32> = vertices
+            .iter()
+            .zip(vertices.iter().cycle().skip(1))
+            .filter(|((_, y0), (_, y1))| (*y0 <= y && y < *y1) || (*y1 <= y && y < *y0))
+            .map(|((x0, y0), (x1, y1))| x0 + (y - y0) * (x1 - x0) / (y1 - y0))
+            .collect();
+        crossings.sort_by(f32::total_cmp);
+
+        for span in crossings.chunks_exact(2) {
+            for x in span[0].ceil() as isize..=span[1].floor() as isize {
+                pixels.push((x, y as isize));
+            }
+        }
+    }
+    pixels
+}
+
+// Fills every row between the leftmost and rightmost pixel of a convex outline
+pub fn fill(outline: Pixels) -> Pixels {
+    let mut rows: BTreeMap<isize, (isize, isize)> = BTreeMap::new();
+    for (x, y) in outline {
+        let row = rows.entry(y).or_insert((x, x));
+        row.0 = row.0.min(x);
+        row.1 = row.1.max(x);
+    }
+
+    rows.into_iter()
+        .flat_map(|(y, (left, right))| (left..=right).map(move |x| (x, y)))
+        .collect()
+}
+
+fn to_pixel(point: Point) -> (isize, isize) {
+    (point.x as isize, point.y as isize)
+}
+
+fn line_impl(pixels: &mut Pixels, start: (isize, isize), end: (isize, isize)) {
+    let (mut x, mut y) = start;
+    let dx = (end.0 - x).abs();
+    let dy = -(end.1 - y).abs();
+    let step_x = if x < end.0 { 1 } else { -1 };
+    let step_y = if y < end.1 { 1 } else { -1 };
+    let mut err = dx + dy;
+
+    loop {
+        pixels.push((x, y));
+        if (x, y) == end {
+            break;
+        }
+
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += step_x;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += step_y;
+        }
+    }
+}
+
+// Midpoint circle algorithm, returns the octant going from the bottom towards the right
+fn circle_octant(radius: isize) -> Pixels {
+    let (mut x, mut y) = (0, radius);
+    let mut err = 1 - radius;
+
+    let mut pixels = Vec::new();
+    while x <= y {
+        pixels.push((x, y));
+        x += 1;
+        if err < 0 {
+            err += 2 * x + 1;
+        } else {
+            y -= 1;
+            err += 2 * (x - y) + 1;
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(pixels: Pixels, width: usize, height: usize) -> Vec<String> {
+        let mut rows = vec![vec!['.'; width]; height];
+        for (x, y) in pixels {
+            rows[y as usize][x as usize] = '#';
+        }
+        rows.into_iter()
+            .map(|row| row.into_iter().collect())
+            .collect()
+    }
+
+    #[test]
+    fn lines() {
+        let start = Point { x: 0, y: 0 };
+        let end = Point { x: 4, y: 2 };
+        assert_eq!(draw(line(start, end), 5, 3), ["#....", ".##..", "...##"]);
+        assert_eq!(line(end, end), [(4, 2)]);
+    }
+
+    #[test]
+    fn ellipses_and_rectangles() {
+        let size = Size {
+            width: 7,
+            height: 5,
+        };
+        assert_eq!(
+            draw(ellipse(size), 7, 5),
+            ["..###..", ".#...#.", "#.....#", ".#...#.", "..###.."]
+        );
+        assert_eq!(
+            draw(fill(rectangle(size, 1)), 7, 5),
+            [".#####.", "#######", "#######", "#######", ".#####."]
+        );
+        assert_eq!(
+            draw(arc(size, 0.0, 90.0), 7, 5),
+            ["...##..", ".....#.", "......#", ".......", "......."]
+        );
+    }
+
+    #[test]
+    fn filled_polygon() {
+        let points = [
+            Point { x: 0, y: 0 },
+            Point { x: 4, y: 0 },
+            Point { x: 4, y: 4 },
+            Point { x: 2, y: 2 },
+            Point { x: 0, y: 4 },
+        ];
+        assert_eq!(
+            draw(fill_polygon(&points), 5, 5),
+            ["#####", "#####", "#####", "##.##", "#...#"]
+        );
+    }
+}
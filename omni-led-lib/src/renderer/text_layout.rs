@@ -0,0 +1,176 @@
+use crate::script_handler::script_data_types::{HorizontalAlign, TextWrap};
+
+pub const ELLIPSIS: &str = "...";
+
+// Splits text into lines, `advance` returns the width of a character in pixels
+pub fn wrap(
+    text: &str,
+    width: isize,
+    wrap: TextWrap,
+    advance: &mut impl FnMut(char) -> isize,
+) -> Vec<String> {
+    let mut builder = LineBuilder::new(width);
+    for paragraph in text.split('\n') {
+        match wrap {
+            TextWrap::None => builder.push_str(paragraph, advance),
+            TextWrap::Char => builder.push_chars(paragraph, advance),
+            TextWrap::Word => {
+                for word in paragraph.split_whitespace() {
+                    builder.push_word(word, advance);
+                }
+            }
+        }
+        builder.finish_line();
+    }
+    builder.lines
+}
+
+pub fn line_width(line: &str, advance: &mut impl FnMut(char) -> isize) -> isize {
+    line.chars().map(advance).sum()
+}
+
+// Shortens the line so that it fits in `width` together with the ellipsis
+pub fn ellipsize(line: &str, width: isize, advance: &mut impl FnMut(char) -> isize) -> String {
+    let ellipsis_width = line_width(ELLIPSIS, advance);
+
+    let mut result = String::new();
+    let mut used = 0;
+    for character in line.chars() {
+        let character_width = advance(character);
+        if used + character_width + ellipsis_width > width {
+            break;
+        }
+        result.push(character);
+        used += character_width;
+    }
+
+    result.truncate(result.trim_end().len());
+    result + ELLIPSIS
+}
+
+// Number of lines that fit in `height`, at least one line is always shown
+pub fn visible_lines(height: usize, line_height: usize, line_spacing: isize) -> usize {
+    let pitch = line_height as isize + line_spacing;
+    if pitch <= 0 {
+        return 1;
+    }
+    ((height as isize + line_spacing) / pitch).max(1) as usize
+}
+
+// Offset of a line from the left edge. Lines that don't fit are always aligned to the left.
+pub fn align_offset(align: HorizontalAlign, width: isize, line_width: isize) -> isize {
+    let space = (width - line_width).max(0);
+    match align {
+        HorizontalAlign::Left => 0,
+        HorizontalAlign::Center => space / 2,
+        HorizontalAlign::Right => space,
+    }
+}
+
+struct LineBuilder {
+    lines: Vec<String>,
+    current: String,
+    current_width: isize,
+    width: isize,
+}
+
+impl LineBuilder {
+    fn new(width: isize) -> Self {
+        Self {
+            lines: Vec::new(),
+            current: String::new(),
+            current_width: 0,
+            width,
+        }
+    }
+
+    fn push_str(&mut self, text: &str, advance: &mut impl FnMut(char) -> isize) {
+        self.current.push_str(text);
+        self.current_width += line_width(text, advance);
+    }
+
+    fn push_chars(&mut self, text: &str, advance: &mut impl FnMut(char) -> isize) {
+        for character in text.chars() {
+            let character_width = advance(character);
+            if !self.current.is_empty() && self.current_width + character_width > self.width {
+                self.finish_line();
+            }
+            self.current.push(character);
+            self.current_width += character_width;
+        }
+    }
+
+    fn push_word(&mut self, word: &str, advance: &mut impl FnMut(char) -> isize) {
+        let word_width = line_width(word, advance);
+        if self.current.is_empty() {
+            if word_width <= self.width {
+                self.push_str(word, advance);
+            } else {
+                // Words longer than the whole line are broken between characters
+                self.push_chars(word, advance);
+            }
+            return;
+        }
+
+        let space_width = advance(' ');
+        if self.current_width + space_width + word_width <= self.width {
+            self.push_str(" ", advance);
+            self.push_str(word, advance);
+        } else {
+            self.finish_line();
+            self.push_word(word, advance);
+        }
+    }
+
+    fn finish_line(&mut self) {
+        self.lines.push(std::mem::take(&mut self.current));
+        self.current_width = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monospace(_: char) -> isize {
+        1
+    }
+
+    #[test]
+    fn wrapping() {
+        let text = "The quick brown fox\n\njumps";
+        assert_eq!(
+            wrap(text, 10, TextWrap::Word, &mut monospace),
+            ["The quick", "brown fox", "", "jumps"]
+        );
+        assert_eq!(
+            wrap(text, 10, TextWrap::Char, &mut monospace),
+            ["The quick ", "brown fox", "", "jumps"]
+        );
+        assert_eq!(
+            wrap(text, 10, TextWrap::None, &mut monospace),
+            ["The quick brown fox", "", "jumps"]
+        );
+        assert_eq!(
+            wrap("abcdefgh ij", 3, TextWrap::Word, &mut monospace),
+            ["abc", "def", "gh", "ij"]
+        );
+    }
+
+    #[test]
+    fn truncation_and_alignment() {
+        assert_eq!(
+            ellipsize("The quick brown", 10, &mut monospace),
+            "The qui..."
+        );
+        assert_eq!(ellipsize("The quick", 7, &mut monospace), "The...");
+
+        assert_eq!(visible_lines(20, 8, 2), 2);
+        assert_eq!(visible_lines(18, 8, 2), 2);
+        assert_eq!(visible_lines(4, 8, 2), 1);
+
+        assert_eq!(align_offset(HorizontalAlign::Center, 10, 5), 2);
+        assert_eq!(align_offset(HorizontalAlign::Right, 10, 5), 5);
+        assert_eq!(align_offset(HorizontalAlign::Right, 10, 12), 0);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::renderer::buffer::Buffer;
+use crate::script_handler::script_data_types::{Transition, TransitionEffect};
+
+const BAYER_MATRIX: [[usize; 4]; 4] =
+    [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+pub struct TransitionState {
+    transition: Transition,
+    from: Buffer,
+    to: Buffer,
+    current_tick: usize,
+}
+
+impl TransitionState {
+    pub fn new(transition: Transition, from: Buffer, to: Buffer) -> Self {
+        Self {
+            transition,
+            from,
+            to,
+            current_tick: 0,
+        }
+    }
+
+    pub fn set_target(&mut self, to: Buffer) {
+        self.to = to;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current_tick >= self.transition.ticks
+    }
+
+    pub fn step(&mut self) -> Buffer {
+        self.current_tick = (self.current_tick + 1).min(self.transition.ticks);
+        let progress = match self.transition.ticks {
+            0 => 1.0,
+            ticks => self.current_tick as f32 / ticks as f32,
+        };
+
+        composite(&self.from, &self.to, self.transition.effect, progress)
+    }
+}
+
+pub fn composite(from: &Buffer, to: &Buffer, effect: TransitionEffect, progress: f32) -> Buffer {
+    let width = to.width();
+    let height = to.height();
+    let progress = progress.clamp(0.0, 1.0);
+    let offset_x = (width as f32 * progress).round() as usize;
+    let offset_y = (height as f32 * progress).round() as usize;
+    let dither_level = (progress * 16.0).round() as usize;
+
+    // Cloning the target keeps the memory layout, `progress` of 0.0 shows only `from` and 1.0 only `to`
+    let mut output = to.clone();
+    for y in 0..height {
+        for x in 0..width {
+            let color = match effect {
+                TransitionEffect::SlideLeft => match x < width - offset_x {
+                    true => from.get_color(x + offset_x, y),
+                    false => to.get_color(x + offset_x - width, y),
+                },
+                TransitionEffect::SlideRight => match x >= offset_x {
+                    true => from.get_color(x - offset_x, y),
+                    false => to.get_color(x + width - offset_x, y),
+                },
+                TransitionEffect::Wipe => match x < offset_x {
+                    true => to.get_color(x, y),
+                    false => from.get_color(x, y),
+                },
+                TransitionEffect::Dissolve => match BAYER_MATRIX[y % 4][x % 4] < dither_level {
+                    true => to.get_color(x, y),
+                    false => from.get_color(x, y),
+                },
+                TransitionEffect::PushUp => match y < height - offset_y {
+                    true => from.get_color(x, y + offset_y),
+                    false => to.get_color(x, y + offset_y - height),
+                },
+            };
+            output.set_color(x, y, color);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script_handler::script_data_types::{MemoryLayout, Size};
+    use test_case::test_case;
+
+    const SIZE: Size = Size {
+        width: 8,
+        height: 4,
+    };
+
+    fn make_buffers(memory_layout: MemoryLayout) -> (Buffer, Buffer) {
+        let from = Buffer::new(SIZE, memory_layout);
+        let mut to = Buffer::new(SIZE, memory_layout);
+        for y in 0..SIZE.height {
+            for x in 0..SIZE.width {
+                to.set_pixel(x, y, true);
+            }
+        }
+        (from, to)
+    }
+
+    fn count_set(buffer: &Buffer) -> usize {
+        let mut count = 0;
+        for y in 0..buffer.height() {
+            for x in 0..buffer.width() {
+                if buffer.get(x, y) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    #[test_case(TransitionEffect::SlideLeft ; "Slide left")]
+    #[test_case(TransitionEffect::SlideRight ; "Slide right")]
+    #[test_case(TransitionEffect::Wipe ; "Wipe")]
+    #[test_case(TransitionEffect::Dissolve ; "Dissolve")]
+    #[test_case(TransitionEffect::PushUp ; "Push up")]
+    fn edges_match_source_frames(effect: TransitionEffect) {
+        for memory_layout in [
+            MemoryLayout::BitPerPixel,
+            MemoryLayout::BytePerPixel,
+            MemoryLayout::BitPerPixelVertical,
+        ] {
+            let (from, to) = make_buffers(memory_layout);
+
+            let begin = composite(&from, &to, effect, 0.0);
+            assert_eq!(begin.bytes(), from.bytes());
+
+            let end = composite(&from, &to, effect, 1.0);
+            assert_eq!(end.bytes(), to.bytes());
+
+            let half = composite(&from, &to, effect, 0.5);
+            assert_eq!(count_set(&half), SIZE.width * SIZE.height / 2);
+        }
+    }
+
+    #[test]
+    fn slide_left_moves_new_frame_in_from_the_right() {
+        let (from, to) = make_buffers(MemoryLayout::BytePerPixel);
+        let frame = composite(&from, &to, TransitionEffect::SlideLeft, 0.25);
+
+        for y in 0..SIZE.height {
+            for x in 0..SIZE.width {
+                assert_eq!(frame.get(x, y), x >= 6, "Mismatch at ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn state_finishes_after_configured_ticks() {
+        let (from, to) = make_buffers(MemoryLayout::BitPerPixel);
+        let transition = Transition {
+            effect: TransitionEffect::Wipe,
+            ticks: 4,
+        };
+        let mut state = TransitionState::new(transition, from, to.clone());
+
+        for _ in 0..3 {
+            _ = state.step();
+            assert!(!state.is_finished());
+        }
+
+        let last = state.step();
+        assert!(state.is_finished());
+        assert_eq!(last.bytes(), to.bytes());
+    }
+}
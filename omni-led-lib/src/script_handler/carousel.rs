@@ -0,0 +1,122 @@
+use mlua::Function;
+use omni_led_derive::FromLuaValue;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::renderer::animation::State;
+use crate::script_handler::script_data_types::DurationWrapper;
+
+#[derive(Clone, FromLuaValue)]
+pub struct CarouselSettings {
+    #[mlua(transform = DurationWrapper::transform)]
+    pub interval: Duration,
+
+    #[mlua(default = false)]
+    pub pause_on_key: bool,
+}
+
+// Rotates through screens of a layout group without user input
+pub struct Carousel {
+    settings: CarouselSettings,
+    current: Rc<RefCell<usize>>,
+    predicates: Vec<Vec<Option<Function>>>,
+    elapsed: Duration,
+}
+
+impl Carousel {
+    pub fn new(
+        settings: CarouselSettings,
+        current: Rc<RefCell<usize>>,
+        predicates: Vec<Vec<Option<Function>>>,
+    ) -> Self {
+        Self {
+            settings,
+            current,
+            predicates,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    // Start counting the interval from scratch, e.g. after the screen was changed manually
+    pub fn reset(&mut self) {
+        self.elapsed = Duration::ZERO;
+    }
+
+    pub fn register_input(&mut self) {
+        if self.settings.pause_on_key {
+            self.reset();
+        }
+    }
+
+    // Returns `true` when the current screen has changed. Screens are not changed in the middle
+    // of `Repeat::Once` animations, they advance as soon as the animation has finished instead.
+    pub fn update(&mut self, time_passed: Duration, state: State) -> mlua::Result<bool> {
+        self.elapsed += time_passed;
+        if self.elapsed < self.settings.interval || state == State::InProgress {
+            return Ok(false);
+        }
+        self.elapsed = Duration::ZERO;
+
+        let count = self.predicates.len();
+        let current = *self.current.borrow();
+        for offset in 1..count {
+            let screen = (current + offset) % count;
+            if self.can_show(screen)? {
+                *self.current.borrow_mut() = screen;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // Screen is skipped if none of its layouts could be shown
+    fn can_show(&self, screen: usize) -> mlua::Result<bool> {
+        for predicate in &self.predicates[screen] {
+            let result = match predicate {
+                Some(predicate) => predicate.call(())?,
+                None => true,
+            };
+            if result {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mlua::Lua;
+
+    #[test]
+    fn skips_hidden_screens() {
+        let lua = Lua::new();
+        let hidden = lua.create_function(|_, _: ()| Ok(false)).unwrap();
+
+        let current = Rc::new(RefCell::new(0));
+        let settings = CarouselSettings {
+            interval: Duration::from_secs(1),
+            pause_on_key: true,
+        };
+        let predicates = vec![vec![None], vec![Some(hidden)], vec![None]];
+        let mut carousel = Carousel::new(settings, Rc::clone(&current), predicates);
+
+        let half = Duration::from_millis(500);
+        assert!(!carousel.update(half, State::Finished).unwrap());
+        carousel.register_input();
+        assert!(!carousel.update(half, State::Finished).unwrap());
+        assert!(!carousel.update(half, State::InProgress).unwrap());
+        assert_eq!(*current.borrow(), 0);
+
+        // Interval has passed, but the animation had to finish first
+        assert!(carousel.update(Duration::ZERO, State::Finished).unwrap());
+        assert_eq!(*current.borrow(), 2);
+
+        carousel
+            .update(Duration::from_secs(1), State::CanFinish)
+            .unwrap();
+        assert_eq!(*current.borrow(), 0);
+    }
+}
@@ -0,0 +1,344 @@
+use mlua::{FromLuaMulti, Function, Lua, MultiValue, Table, Value};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::ffi::c_void;
+use std::rc::Rc;
+
+use crate::renderer::graph::is_sampled_by;
+
+// Event keys read by a layout function, e.g. 'CLOCK.Seconds'
+#[derive(Clone, Debug, Default)]
+pub struct Dependencies {
+    keys: HashSet<String>,
+    // Tables that were iterated or measured as a whole, changes to any of their entries count
+    prefixes: HashSet<String>,
+    // Graph sources, they are read from the graph history instead of the event data
+    sources: HashSet<String>,
+}
+
+impl Dependencies {
+    pub fn matches(&self, key: &str) -> bool {
+        if self.keys.contains(key)
+            || self.prefixes.contains(key)
+            || self.sources.iter().any(|source| is_sampled_by(source, key))
+        {
+            return true;
+        }
+
+        key.match_indices('.')
+            .any(|(index, _)| self.prefixes.contains(&key[..index]))
+    }
+
+    pub fn extend(&mut self, other: Dependencies) {
+        self.keys.extend(other.keys);
+        self.prefixes.extend(other.prefixes);
+        self.sources.extend(other.sources);
+    }
+
+    pub fn add_source(&mut self, source: String) {
+        self.sources.insert(source);
+    }
+}
+
+type Recorder = Rc<RefCell<Dependencies>>;
+
+// Proxy metatable entry with a function that returns the original table
+const ORIGINAL: &str = "__original";
+
+// Proxies are empty tables, so code that reads tables directly instead of indexing them, e.g. to
+// serialize them, has to do it on the original table. The whole table is recorded as read.
+pub fn unwrap_proxy(table: Table) -> mlua::Result<Table> {
+    match table.metatable() {
+        Some(meta) => match meta.raw_get::<Value>(ORIGINAL)? {
+            Value::Function(original) => original.call(()),
+            _ => Ok(table),
+        },
+        None => Ok(table),
+    }
+}
+
+// Calls `function` with event data in `env` replaced by proxy tables, that record every value read
+// from them. Missing globals are recorded as well, so that the function runs again once they show up.
+pub fn track<R: FromLuaMulti>(
+    lua: &Lua,
+    env: &Table,
+    roots: &HashSet<String>,
+    function: &Function,
+) -> mlua::Result<(R, Dependencies)> {
+    let recorder = Recorder::default();
+
+    let mut originals = Vec::new();
+    for root in roots {
+        if let Value::Table(table) = env.raw_get(root.as_str())? {
+            let proxy = create_proxy(lua, &recorder, root.clone(), table.clone())?;
+            env.raw_set(root.as_str(), proxy)?;
+            originals.push((root, table));
+        }
+    }
+
+    let record_missing = env.metatable().is_none();
+    if record_missing {
+        let meta = lua.create_table()?;
+        meta.set(
+            "__index",
+            lua.create_function({
+                let recorder = Rc::clone(&recorder);
+                move |_, (_, key): (Table, Value)| {
+                    if let Value::String(key) = key {
+                        recorder.borrow_mut().keys.insert(key.to_str()?.to_string());
+                    }
+                    Ok(Value::Nil)
+                }
+            })?,
+        )?;
+        env.set_metatable(Some(meta))?;
+    }
+
+    let result = function.call::<MultiValue>(());
+
+    // Restore the environment before reporting any errors
+    if record_missing {
+        env.set_metatable(None)?;
+    }
+    for (root, table) in originals {
+        env.raw_set(root.as_str(), table)?;
+    }
+
+    // Event data returned from the function is replaced with the original tables, so that it can be
+    // read without going through metamethods
+    let mut visited = HashSet::new();
+    let result = result?
+        .into_iter()
+        .map(|value| unwrap_value(&mut visited, value))
+        .collect::<mlua::Result<MultiValue>>()?;
+    Ok((R::from_lua_multi(result, lua)?, recorder.take()))
+}
+
+fn unwrap_value(visited: &mut HashSet<*const c_void>, value: Value) -> mlua::Result<Value> {
+    let Value::Table(table) = value else {
+        return Ok(value);
+    };
+
+    let original = unwrap_proxy(table.clone())?;
+    if original != table {
+        return Ok(Value::Table(original));
+    }
+
+    if visited.insert(table.to_pointer()) {
+        let mut replaced = Vec::new();
+        for pair in table.pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            if let Value::Table(nested) = &value {
+                let unwrapped = unwrap_value(visited, value.clone())?;
+                if unwrapped.to_pointer() != nested.to_pointer() {
+                    replaced.push((key, unwrapped));
+                }
+            }
+        }
+        for (key, value) in replaced {
+            table.raw_set(key, value)?;
+        }
+    }
+    Ok(Value::Table(table))
+}
+
+fn create_proxy(lua: &Lua, recorder: &Recorder, path: String, table: Table) -> mlua::Result<Table> {
+    let meta = lua.create_table()?;
+
+    meta.set(
+        "__index",
+        lua.create_function({
+            let recorder = Rc::clone(recorder);
+            let path = path.clone();
+            let table = table.clone();
+            move |lua, (_, key): (Table, Value)| {
+                let value: Value = table.get(key.clone())?;
+                let key = match key {
+                    Value::String(key) => format!("{}.{}", path, key.to_str()?),
+                    _ => {
+                        // Arrays are always updated as a whole
+                        recorder.borrow_mut().prefixes.insert(path.clone());
+                        return Ok(value);
+                    }
+                };
+
+                match value {
+                    Value::Table(table) if table.raw_len() == 0 => {
+                        let nested = create_proxy(lua, &recorder, key, table)?;
+                        Ok(Value::Table(nested))
+                    }
+                    Value::Table(table) => {
+                        recorder.borrow_mut().prefixes.insert(key);
+                        Ok(Value::Table(table))
+                    }
+                    value => {
+                        recorder.borrow_mut().keys.insert(key);
+                        Ok(value)
+                    }
+                }
+            }
+        })?,
+    )?;
+
+    meta.set(
+        "__newindex",
+        lua.create_function({
+            let table = table.clone();
+            move |_, (_, key, value): (Table, Value, Value)| table.set(key, value)
+        })?,
+    )?;
+
+    meta.set(
+        "__len",
+        lua.create_function({
+            let recorder = Rc::clone(recorder);
+            let path = path.clone();
+            let table = table.clone();
+            move |_, _: Value| {
+                recorder.borrow_mut().prefixes.insert(path.clone());
+                Ok(table.raw_len())
+            }
+        })?,
+    )?;
+
+    let next: Function = lua.globals().get("next")?;
+    meta.set(
+        "__pairs",
+        lua.create_function({
+            let recorder = Rc::clone(recorder);
+            let path = path.clone();
+            let table = table.clone();
+            move |_, _: Value| {
+                recorder.borrow_mut().prefixes.insert(path.clone());
+                Ok((next.clone(), table.clone(), Value::Nil))
+            }
+        })?,
+    )?;
+
+    meta.raw_set(
+        ORIGINAL,
+        lua.create_function({
+            let recorder = Rc::clone(recorder);
+            move |_, _: ()| {
+                recorder.borrow_mut().prefixes.insert(path.clone());
+                Ok(table.clone())
+            }
+        })?,
+    )?;
+
+    let proxy = lua.create_table()?;
+    proxy.set_metatable(Some(meta))?;
+    Ok(proxy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mlua::chunk;
+
+    use crate::common::common::load_internal_functions;
+
+    #[test]
+    fn records_values_read() {
+        let lua = Lua::new();
+        let env: Table = lua
+            .load(chunk! {
+                return {
+                    CLOCK = { Seconds = 1, Minutes = 2, Date = { Day = 3 } },
+                    LIST = { Values = { 4, 5 } },
+                    ipairs = ipairs,
+                }
+            })
+            .eval()
+            .unwrap();
+        let clock: Table = env.get("CLOCK").unwrap();
+        let function = lua
+            .load(
+                "local sum = CLOCK.Seconds + CLOCK.Date.Day + (SPOTIFY and 1 or 0)
+                   for _, value in ipairs(LIST.Values) do sum = sum + value end
+                   return sum",
+            )
+            .set_environment(env.clone())
+            .into_function()
+            .unwrap();
+
+        let roots = HashSet::from(["CLOCK".to_string(), "LIST".to_string()]);
+        let (sum, dependencies): (usize, _) = track(&lua, &env, &roots, &function).unwrap();
+        assert_eq!(sum, 13);
+
+        assert!(dependencies.matches("CLOCK.Seconds"));
+        assert!(dependencies.matches("CLOCK.Date.Day"));
+        assert!(dependencies.matches("LIST.Values"));
+        assert!(dependencies.matches("SPOTIFY"));
+        assert!(!dependencies.matches("CLOCK"));
+        assert!(!dependencies.matches("CLOCK.Minutes"));
+        assert!(!dependencies.matches("CLOCK.Date"));
+
+        // Environment is restored after the call
+        assert_eq!(env.raw_get::<Table>("CLOCK").unwrap(), clock);
+        assert!(env.metatable().is_none());
+    }
+
+    #[test]
+    fn returns_original_tables() {
+        let lua = Lua::new();
+        let env: Table = lua
+            .load(chunk! {
+                return { CLOCK = { Seconds = 1, Date = { Day = 3, Month = 4 } } }
+            })
+            .eval()
+            .unwrap();
+        let date: Table = env.get::<Table>("CLOCK").unwrap().get("Date").unwrap();
+        let function = lua
+            .load("return CLOCK.Date, { nested = { date = CLOCK.Date } }")
+            .set_environment(env.clone())
+            .into_function()
+            .unwrap();
+
+        let roots = HashSet::from(["CLOCK".to_string()]);
+        let ((returned, wrapper), dependencies): ((Table, Table), _) =
+            track(&lua, &env, &roots, &function).unwrap();
+
+        // Nested event tables are passed through with all of their entries visible
+        assert_eq!(returned, date);
+        assert_eq!(returned.pairs::<String, usize>().count(), 2);
+        let nested: Table = wrapper.get::<Table>("nested").unwrap().get("date").unwrap();
+        assert_eq!(nested, date);
+        assert_eq!(nested.raw_get::<usize>("Day").unwrap(), 3);
+
+        assert!(dependencies.matches("CLOCK.Date.Month"));
+        assert!(!dependencies.matches("CLOCK.Seconds"));
+    }
+
+    #[test]
+    fn reads_original_tables() {
+        let lua = Lua::new();
+        load_internal_functions(&lua);
+        let env: Table = lua
+            .load(chunk! {
+                return {
+                    CLOCK = { Seconds = 1, Date = { Day = 3 } },
+                    SPOTIFY = { Artist = "Artist" },
+                    json = internal.json,
+                    next = internal.next,
+                }
+            })
+            .eval()
+            .unwrap();
+        let function = lua
+            .load("return json.encode(CLOCK.Date), next(SPOTIFY)")
+            .set_environment(env.clone())
+            .into_function()
+            .unwrap();
+
+        let roots = HashSet::from(["CLOCK".to_string(), "SPOTIFY".to_string()]);
+        let ((encoded, key), dependencies): ((String, String), _) =
+            track(&lua, &env, &roots, &function).unwrap();
+        assert_eq!(encoded, r#"{"Day":3}"#);
+        assert_eq!(key, "Artist");
+
+        assert!(dependencies.matches("CLOCK.Date.Day"));
+        assert!(dependencies.matches("SPOTIFY.Artist"));
+        assert!(!dependencies.matches("CLOCK.Seconds"));
+    }
+}
@@ -0,0 +1,326 @@
+use mlua::{Function, Lua, UserData, UserDataMethods, Value};
+use omni_led_api::plugin::Plugin;
+use omni_led_derive::{FromLuaValue, LuaName};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::common::lua_traits::{LuaTypeStaticMembers, StaticMembers};
+use crate::common::user_data::UserDataRef;
+use crate::events::events::Events;
+use crate::events::shortcuts::Shortcuts;
+use crate::script_handler::script_data_types::{Modifiers, Point, Size, Text, Widget};
+
+#[derive(Clone, FromLuaValue)]
+pub struct MenuEntry {
+    pub name: String,
+    pub action: Option<Function>,
+    #[mlua(default)]
+    pub entries: Vec<MenuEntry>,
+}
+
+#[derive(Clone, Default, FromLuaValue)]
+pub struct MenuKeys {
+    #[mlua(default)]
+    pub up: Vec<String>,
+    #[mlua(default)]
+    pub down: Vec<String>,
+    #[mlua(default)]
+    pub select: Vec<String>,
+    #[mlua(default)]
+    pub back: Vec<String>,
+}
+
+#[derive(Clone, FromLuaValue)]
+pub struct MenuOptions {
+    pub name: String,
+    pub entries: Vec<MenuEntry>,
+    #[mlua(default)]
+    pub keys: MenuKeys,
+}
+
+#[derive(Clone, FromLuaValue)]
+pub struct MenuArea {
+    pub position: Point,
+    pub size: Size,
+    #[mlua(default = 3)]
+    pub rows: usize,
+}
+
+struct MenuState {
+    name: String,
+    entries: Vec<MenuEntry>,
+    path: Vec<usize>,
+    selected: usize,
+}
+
+impl MenuState {
+    fn current_entries(&self) -> &[MenuEntry] {
+        let mut entries = self.entries.as_slice();
+        for index in &self.path {
+            entries = &entries[*index].entries;
+        }
+        entries
+    }
+
+    fn move_selection(&mut self, forward: bool) {
+        let count = self.current_entries().len();
+        if count == 0 {
+            return;
+        }
+
+        self.selected = match forward {
+            true => (self.selected + 1) % count,
+            false => (self.selected + count - 1) % count,
+        };
+    }
+
+    // Enters the submenu of the selected entry, or returns its action
+    fn select(&mut self) -> Option<Function> {
+        let entry = self.current_entries().get(self.selected)?;
+        if entry.entries.is_empty() {
+            return entry.action.clone();
+        }
+
+        self.path.push(self.selected);
+        self.selected = 0;
+        None
+    }
+
+    fn back(&mut self) {
+        if let Some(selected) = self.path.pop() {
+            self.selected = selected;
+        }
+    }
+}
+
+type MenuAction = fn(&Lua, &Menu) -> mlua::Result<()>;
+
+#[derive(Clone, LuaName)]
+pub struct Menu {
+    state: Rc<RefCell<MenuState>>,
+}
+
+impl Menu {
+    pub fn new(lua: &Lua, options: MenuOptions) -> mlua::Result<Self> {
+        // Events with invalid names are dropped, so the menu would never notify about changes
+        if !Plugin::is_valid_identifier(&options.name) {
+            return Err(mlua::Error::runtime(format!(
+                "'{}' is not a valid event name",
+                options.name
+            )));
+        }
+
+        let menu = Self {
+            state: Rc::new(RefCell::new(MenuState {
+                name: options.name,
+                entries: options.entries,
+                path: Vec::new(),
+                selected: 0,
+            })),
+        };
+
+        let keys = options.keys;
+        let bindings: [(Vec<String>, MenuAction); 4] = [
+            (keys.up, |lua, menu| menu.move_selection(lua, false)),
+            (keys.down, |lua, menu| menu.move_selection(lua, true)),
+            (keys.select, Menu::select),
+            (keys.back, Menu::back),
+        ];
+
+        let mut shortcuts = UserDataRef::<Shortcuts>::load(lua);
+        for (keys, action) in bindings {
+            if keys.is_empty() {
+                continue;
+            }
+
+            let menu = menu.clone();
+            let function = lua.create_function(move |lua, _: ()| action(lua, &menu))?;
+            shortcuts.get_mut().register(lua, keys, function)?;
+        }
+
+        Ok(menu)
+    }
+
+    fn move_selection(&self, lua: &Lua, forward: bool) -> mlua::Result<()> {
+        self.state.borrow_mut().move_selection(forward);
+        self.notify(lua)
+    }
+
+    fn select(lua: &Lua, menu: &Menu) -> mlua::Result<()> {
+        // Borrow has to be released before running the action, it can use the menu as well
+        let action = menu.state.borrow_mut().select();
+        menu.notify(lua)?;
+
+        match action {
+            Some(action) => action.call(()),
+            None => Ok(()),
+        }
+    }
+
+    fn back(lua: &Lua, menu: &Menu) -> mlua::Result<()> {
+        menu.state.borrow_mut().back();
+        menu.notify(lua)
+    }
+
+    fn selected(&self) -> Option<String> {
+        let state = self.state.borrow();
+        state
+            .current_entries()
+            .get(state.selected)
+            .map(|entry| entry.name.clone())
+    }
+
+    fn notify(&self, lua: &Lua) -> mlua::Result<()> {
+        let name = self.state.borrow().name.clone();
+        let value = match self.selected() {
+            Some(selected) => Value::String(lua.create_string(selected)?),
+            None => Value::Nil,
+        };
+        Events::send(name, value);
+        Ok(())
+    }
+
+    fn widgets(&self, area: MenuArea) -> Vec<Widget> {
+        let state = self.state.borrow();
+        let entries = state.current_entries();
+        let rows = area.rows.max(1);
+        let row_height = area.size.height / rows;
+
+        // Scroll just enough to keep the selected entry visible
+        let first = (state.selected + 1).saturating_sub(rows);
+
+        entries
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(rows)
+            .map(|(index, entry)| {
+                let selected = index == state.selected;
+                let text = match entry.entries.is_empty() {
+                    true => entry.name.clone(),
+                    false => format!("{} >", entry.name),
+                };
+
+                let position = Point {
+                    x: area.position.x,
+                    y: area.position.y + (index - first) * row_height,
+                };
+                let size = Size {
+                    width: area.size.width,
+                    height: row_height,
+                };
+                Widget::Text(Text {
+                    scrolling: selected,
+                    modifiers: Modifiers {
+                        clear_background: true,
+                        negative: selected,
+                        ..Default::default()
+                    },
+                    ..Text::new(text, position, size)
+                })
+            })
+            .collect()
+    }
+}
+
+impl LuaTypeStaticMembers for Menu {
+    fn add_members(functions: &mut StaticMembers<'_>) {
+        functions.add_function("new", |lua, options: MenuOptions| Menu::new(lua, options));
+    }
+}
+
+impl UserData for Menu {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("up", |lua, menu, _: ()| menu.move_selection(lua, false));
+
+        methods.add_method("down", |lua, menu, _: ()| menu.move_selection(lua, true));
+
+        methods.add_method("select", |lua, menu, _: ()| Menu::select(lua, menu));
+
+        methods.add_method("back", |lua, menu, _: ()| Menu::back(lua, menu));
+
+        methods.add_method("selected", |_lua, menu, _: ()| Ok(menu.selected()));
+
+        methods.add_method("widgets", |_lua, menu, area: MenuArea| {
+            Ok(menu.widgets(area))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, entries: Vec<MenuEntry>) -> MenuEntry {
+        MenuEntry {
+            name: name.to_string(),
+            action: None,
+            entries,
+        }
+    }
+
+    fn make_state() -> MenuState {
+        MenuState {
+            name: "MENU".to_string(),
+            entries: vec![
+                entry(
+                    "Audio",
+                    vec![entry("Headphones", vec![]), entry("Speakers", vec![])],
+                ),
+                entry("Lights", vec![]),
+                entry("Exit", vec![]),
+            ],
+            path: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    fn selected_name(state: &MenuState) -> &str {
+        &state.current_entries()[state.selected].name
+    }
+
+    #[test]
+    fn selection_wraps_around() {
+        let mut state = make_state();
+
+        state.move_selection(false);
+        assert_eq!(selected_name(&state), "Exit");
+
+        state.move_selection(true);
+        state.move_selection(true);
+        assert_eq!(selected_name(&state), "Lights");
+    }
+
+    #[test]
+    fn nested_menus() {
+        let mut state = make_state();
+
+        assert!(state.select().is_none());
+        assert_eq!(state.current_entries().len(), 2);
+        assert_eq!(selected_name(&state), "Headphones");
+
+        state.move_selection(true);
+        assert_eq!(selected_name(&state), "Speakers");
+
+        state.back();
+        assert_eq!(state.current_entries().len(), 3);
+        assert_eq!(selected_name(&state), "Audio");
+
+        // Going back from the top level menu does nothing
+        state.back();
+        assert_eq!(selected_name(&state), "Audio");
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        let lua = Lua::new();
+        for name in ["", "my menu", "menu", "1MENU"] {
+            let options = MenuOptions {
+                name: name.to_string(),
+                entries: vec![],
+                keys: MenuKeys::default(),
+            };
+            assert!(Menu::new(&lua, options).is_err(), "Accepted '{name}'");
+        }
+    }
+}
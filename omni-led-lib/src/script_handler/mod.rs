@@ -1,3 +1,10 @@
+pub mod carousel;
+pub mod dependencies;
+pub mod menu;
+pub mod modules;
+pub mod overlay;
+pub mod profiler;
 pub mod script_handler;
+pub mod storage;
 
 pub mod script_data_types;
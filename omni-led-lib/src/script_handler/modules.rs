@@ -0,0 +1,216 @@
+use log::debug;
+use mlua::chunk::ChunkMode;
+use mlua::{ErrorContext, Function, Lua, Table, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+const LIB_DIR: &str = "lib";
+
+// Shared with the `require` function, so that modules can be loaded from within layouts and
+// predicates, while the script handler is borrowed
+pub struct Modules {
+    root: PathBuf,
+    environment: Option<Table>,
+    // Keyed by resolved paths, since a module can be required under different names
+    entries: HashMap<PathBuf, Module>,
+}
+
+enum Module {
+    Loading,
+    Loaded(Value),
+}
+
+impl Modules {
+    pub fn new(root: PathBuf) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            root,
+            environment: None,
+            entries: HashMap::new(),
+        }))
+    }
+
+    pub fn set_environment(&mut self, environment: Table) {
+        self.environment = Some(environment);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn create_require_fn(lua: &Lua, modules: &Rc<RefCell<Self>>) -> Function {
+        let modules = Rc::clone(modules);
+        lua.create_function(move |lua, name: String| Self::require(lua, &modules, name))
+            .unwrap()
+    }
+
+    fn require(lua: &Lua, modules: &RefCell<Self>, name: String) -> mlua::Result<Value> {
+        let (path, env) = {
+            let mut modules = modules.borrow_mut();
+            let path = resolve(&modules.root, &name)?;
+            match modules.entries.get(&path) {
+                Some(Module::Loaded(value)) => return Ok(value.clone()),
+                Some(Module::Loading) => {
+                    return Err(mlua::Error::runtime(format!(
+                        "Circular dependency detected when loading module '{name}'"
+                    )));
+                }
+                None => {}
+            }
+
+            let env = modules
+                .environment
+                .clone()
+                .ok_or_else(|| mlua::Error::runtime("Modules can't be loaded yet"))?;
+            modules.entries.insert(path.clone(), Module::Loading);
+            (path, env)
+        };
+
+        // Modules must not be borrowed here, nested modules are loaded recursively
+        let result = load_module(lua, &path, env);
+
+        let mut modules = modules.borrow_mut();
+        match result {
+            Ok(value) => {
+                debug!("Loaded module '{}' from '{}'", name, path.display());
+                modules.entries.insert(path, Module::Loaded(value.clone()));
+                Ok(value)
+            }
+            Err(err) => {
+                modules.entries.remove(&path);
+                Err(err)
+            }
+        }
+    }
+}
+
+fn load_module(lua: &Lua, path: &Path, env: Table) -> mlua::Result<Value> {
+    let source = std::fs::read_to_string(path)
+        .map_err(mlua::Error::external)
+        .with_context(|_| format!("Failed to read module '{}'", path.display()))?;
+
+    let value: Value = lua
+        .load(source)
+        .set_mode(ChunkMode::Text)
+        .set_name(path.to_string_lossy())
+        .set_environment(env)
+        .call(())?;
+
+    // Same as builtin `require`, modules that don't return anything are stored as `true`
+    match value {
+        Value::Nil => Ok(Value::Boolean(true)),
+        value => Ok(value),
+    }
+}
+
+fn resolve(root: &Path, name: &str) -> mlua::Result<PathBuf> {
+    let valid = !name.is_empty()
+        && name.split('.').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        });
+    if !valid {
+        return Err(mlua::Error::runtime(format!(
+            "'{name}' is not a valid module name"
+        )));
+    }
+
+    let relative: PathBuf = name.split('.').collect();
+    let relative = relative.with_extension("lua");
+
+    let root = root.canonicalize().map_err(mlua::Error::external)?;
+    for dir in [root.clone(), root.join(LIB_DIR)] {
+        let path = match dir.join(&relative).canonicalize() {
+            Ok(path) => path,
+            Err(_) => continue,
+        };
+
+        // Symlinks could still point outside the config directory
+        if path.starts_with(&root) && path.is_file() {
+            return Ok(path);
+        }
+    }
+
+    Err(mlua::Error::runtime(format!(
+        "Module '{}' not found in '{}' or '{}'",
+        name,
+        root.display(),
+        root.join(LIB_DIR).display()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("omni-led-modules-{name}"));
+        _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join(LIB_DIR).join("nested")).unwrap();
+        std::fs::write(root.join("top.lua"), "return 1").unwrap();
+        std::fs::write(root.join(LIB_DIR).join("helpers.lua"), "return 2").unwrap();
+        std::fs::write(
+            root.join(LIB_DIR).join("nested").join("deep.lua"),
+            "return 3",
+        )
+        .unwrap();
+        root.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn resolve_search_order() {
+        let root = make_root("search-order");
+
+        assert_eq!(resolve(&root, "top").unwrap(), root.join("top.lua"));
+        assert_eq!(
+            resolve(&root, "helpers").unwrap(),
+            root.join(LIB_DIR).join("helpers.lua")
+        );
+        assert_eq!(
+            resolve(&root, "lib.helpers").unwrap(),
+            root.join(LIB_DIR).join("helpers.lua")
+        );
+        assert_eq!(
+            resolve(&root, "nested.deep").unwrap(),
+            root.join(LIB_DIR).join("nested").join("deep.lua")
+        );
+        assert!(resolve(&root, "missing").is_err());
+    }
+
+    #[test]
+    fn resolve_rejects_escaping_names() {
+        let root = make_root("escaping");
+
+        for name in [
+            "",
+            "..",
+            "../top",
+            "lib/helpers",
+            "/etc/passwd",
+            "a..b",
+            ".top",
+        ] {
+            assert!(resolve(&root, name).is_err(), "Accepted '{name}'");
+        }
+    }
+
+    #[test]
+    fn require_shares_modules_between_names() {
+        let root = make_root("shared");
+        std::fs::write(root.join(LIB_DIR).join("state.lua"), "return {}").unwrap();
+
+        let lua = Lua::new();
+        let modules = Modules::new(root);
+        modules
+            .borrow_mut()
+            .set_environment(lua.create_table().unwrap());
+        let require = Modules::create_require_fn(&lua, &modules);
+
+        let short: Table = require.call("state").unwrap();
+        let full: Table = require.call("lib.state").unwrap();
+        assert_eq!(short, full);
+    }
+}
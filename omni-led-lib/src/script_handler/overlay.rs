@@ -0,0 +1,266 @@
+use mlua::Lua;
+use omni_led_derive::FromLuaValue;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use crate::renderer::animation_group::AnimationGroup;
+use crate::renderer::buffer::Buffer;
+use crate::renderer::renderer::Renderer;
+use crate::script_handler::script_data_types::{
+    DurationWrapper, MemoryLayout, Modifiers, Point, Size, Text, Widget,
+};
+
+const DEFAULT_OVERLAY_TIME: Duration = Duration::from_millis(2000);
+
+#[derive(Clone, FromLuaValue)]
+pub struct OverlayData {
+    pub widgets: Vec<Widget>,
+
+    #[mlua(transform = Self::transform_duration)]
+    pub duration: Duration,
+
+    #[mlua(default = Point { x: 0, y: 0 })]
+    pub position: Point,
+
+    pub device: Option<String>,
+}
+
+impl OverlayData {
+    fn transform_duration(duration: Option<DurationWrapper>, _lua: &Lua) -> mlua::Result<Duration> {
+        Ok(duration.map_or(DEFAULT_OVERLAY_TIME, |duration| duration.0))
+    }
+}
+
+struct ActiveOverlay {
+    widgets: Vec<Widget>,
+    time_remaining: Duration,
+    animation_groups: HashMap<usize, AnimationGroup>,
+    first_frame: bool,
+}
+
+#[derive(Default)]
+pub struct Overlays {
+    queue: VecDeque<OverlayData>,
+    active: Option<ActiveOverlay>,
+    debug_text: Option<String>,
+    dirty: bool,
+}
+
+impl Overlays {
+    pub fn push(&mut self, overlay: OverlayData) {
+        self.queue.push_back(overlay);
+    }
+
+    pub fn update(&mut self, time_passed: Duration) {
+        if let Some(active) = &mut self.active {
+            active.time_remaining = active.time_remaining.saturating_sub(time_passed);
+            if active.time_remaining.is_zero() {
+                self.active = None;
+                self.dirty = true;
+            }
+        }
+
+        if self.active.is_none()
+            && let Some(overlay) = self.queue.pop_front()
+        {
+            self.active = Some(ActiveOverlay {
+                widgets: overlay
+                    .widgets
+                    .into_iter()
+                    .map(|widget| translate(widget, overlay.position))
+                    .collect(),
+                time_remaining: overlay.duration,
+                animation_groups: HashMap::new(),
+                first_frame: true,
+            });
+        }
+    }
+
+    // Debug information, like render timings, shown in the top left corner
+    pub fn set_debug_text(&mut self, text: Option<String>) {
+        self.debug_text = text;
+        self.dirty = true;
+    }
+
+    // Frame has to be presented again, even if the layout below didn't change
+    pub fn needs_refresh(&self) -> bool {
+        self.active.is_some() || self.dirty
+    }
+
+    pub fn composite(
+        &mut self,
+        renderer: &mut Renderer,
+        image: Buffer,
+        memory_layout: MemoryLayout,
+    ) -> Buffer {
+        self.dirty = false;
+
+        let mut output = image;
+        if let Some(active) = &mut self.active {
+            output = draw(
+                renderer,
+                &mut active.animation_groups,
+                active.first_frame,
+                output,
+                &active.widgets,
+                memory_layout,
+            );
+            active.first_frame = false;
+        }
+
+        if let Some(text) = &self.debug_text {
+            let widgets = [debug_widget(text, &output)];
+            output = draw(
+                renderer,
+                &mut HashMap::new(),
+                true,
+                output,
+                &widgets,
+                memory_layout,
+            );
+        }
+
+        output
+    }
+}
+
+fn draw(
+    renderer: &mut Renderer,
+    animation_groups: &mut HashMap<usize, AnimationGroup>,
+    first_frame: bool,
+    image: Buffer,
+    widgets: &[Widget],
+    memory_layout: MemoryLayout,
+) -> Buffer {
+    let size = Size {
+        width: image.width(),
+        height: image.height(),
+    };
+    let (_, overlay) = renderer.render(
+        animation_groups,
+        first_frame,
+        true,
+        size,
+        widgets.to_vec(),
+        memory_layout,
+    );
+
+    // Area covered by the overlay replaces the image below, so it stays readable
+    let mut output = image;
+    for (position, size) in widgets.iter().map(bounds) {
+        let x_end = (position.x + size.width).min(output.width());
+        let y_end = (position.y + size.height).min(output.height());
+        for y in position.y..y_end {
+            for x in position.x..x_end {
+                output.set_color(x, y, overlay.get_color(x, y));
+            }
+        }
+    }
+    output
+}
+
+fn debug_widget(text: &str, image: &Buffer) -> Widget {
+    let size = Size {
+        width: image.width() / 2,
+        height: (image.height() / 4).max(8).min(image.height()),
+    };
+    Widget::Text(Text {
+        modifiers: Modifiers {
+            clear_background: true,
+            negative: true,
+            ..Default::default()
+        },
+        ..Text::new(text.to_string(), Point { x: 0, y: 0 }, size)
+    })
+}
+
+fn translate(mut widget: Widget, offset: Point) -> Widget {
+    let position = match &mut widget {
+        Widget::Bar(bar) => &mut bar.position,
+        Widget::Image(image) => &mut image.position,
+        Widget::Text(text) => &mut text.position,
+        Widget::Line(line) => &mut line.position,
+        Widget::Rect(rect) => &mut rect.position,
+        Widget::Circle(ellipse) | Widget::Ellipse(ellipse) => &mut ellipse.position,
+        Widget::Arc(arc) => &mut arc.position,
+        Widget::Polygon(polygon) => &mut polygon.position,
+        Widget::Graph(graph) => &mut graph.position,
+    };
+    position.x += offset.x;
+    position.y += offset.y;
+    widget
+}
+
+fn bounds(widget: &Widget) -> (Point, Size) {
+    match widget {
+        Widget::Bar(bar) => (bar.position, bar.size),
+        Widget::Image(image) => (image.position, image.size),
+        Widget::Text(text) => (text.position, text.size),
+        Widget::Line(line) => (line.position, line.size),
+        Widget::Rect(rect) => (rect.position, rect.size),
+        Widget::Circle(ellipse) | Widget::Ellipse(ellipse) => (ellipse.position, ellipse.size),
+        Widget::Arc(arc) => (arc.position, arc.size),
+        Widget::Polygon(polygon) => (polygon.position, polygon.size),
+        Widget::Graph(graph) => (graph.position, graph.size),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script_handler::script_data_types::{Bar, Range};
+
+    fn make_overlay(duration: Duration) -> OverlayData {
+        OverlayData {
+            widgets: vec![Widget::Bar(Bar {
+                value: 100.0,
+                range: Range {
+                    min: 0.0,
+                    max: 100.0,
+                },
+                vertical: false,
+                position: Point { x: 0, y: 0 },
+                size: Size {
+                    width: 2,
+                    height: 2,
+                },
+                modifiers: Default::default(),
+            })],
+            duration,
+            position: Point { x: 1, y: 1 },
+            device: None,
+        }
+    }
+
+    #[test]
+    fn overlays_are_queued() {
+        let mut overlays = Overlays::default();
+        overlays.push(make_overlay(Duration::from_secs(1)));
+        overlays.push(make_overlay(Duration::from_secs(2)));
+
+        overlays.update(Duration::ZERO);
+        assert!(overlays.needs_refresh());
+        assert_eq!(overlays.queue.len(), 1);
+
+        overlays.update(Duration::from_secs(1));
+        assert!(overlays.queue.is_empty());
+        assert_eq!(
+            overlays.active.as_ref().unwrap().time_remaining,
+            Duration::from_secs(2)
+        );
+
+        overlays.update(Duration::from_secs(2));
+        assert!(overlays.active.is_none());
+        assert!(overlays.needs_refresh());
+    }
+
+    #[test]
+    fn widgets_are_translated() {
+        let mut overlays = Overlays::default();
+        overlays.push(make_overlay(Duration::from_secs(1)));
+        overlays.update(Duration::ZERO);
+
+        let (position, _) = bounds(&overlays.active.as_ref().unwrap().widgets[0]);
+        assert_eq!((position.x, position.y), (1, 1));
+    }
+}
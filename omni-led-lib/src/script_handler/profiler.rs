@@ -0,0 +1,364 @@
+use ciborium::Value as CborValue;
+use log::{debug, error};
+use omni_led_derive::FromLuaValue;
+use std::cell::Cell;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::constants::constants::Constants;
+use crate::events::event_queue::{Event, EventQueue};
+use crate::script_handler::script_data_types::DurationWrapper;
+
+const CSV_FILE: &str = "profile.csv";
+const CSV_HEADER: &str = "timestamp,device,metric,p50,p90,p99,max";
+const TOTAL: &str = "Total";
+
+#[derive(Clone, Debug, FromLuaValue)]
+#[mlua(impl_default)]
+pub struct ProfilingSettings {
+    #[mlua(default = false)]
+    pub enabled: bool,
+
+    #[mlua(default = false)]
+    pub overlay: bool,
+
+    #[mlua(default = false)]
+    pub csv: bool,
+
+    #[mlua(default = 100)]
+    pub samples: usize,
+
+    #[mlua(transform = DurationWrapper::transform)]
+    #[mlua(default = Duration::from_secs(1))]
+    pub report_interval: Duration,
+}
+
+// Timings of a single device update, collected by the script handler
+#[derive(Default)]
+pub struct FrameTimings {
+    entries: Vec<(String, Duration)>,
+}
+
+impl FrameTimings {
+    pub fn record(&mut self, metric: &str, duration: Duration) {
+        match self.entries.iter_mut().find(|(name, _)| name == metric) {
+            Some((_, total)) => *total += duration,
+            None => self.entries.push((metric.to_string(), duration)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Summary {
+    p50: Duration,
+    p90: Duration,
+    p99: Duration,
+    max: Duration,
+}
+
+#[derive(Default)]
+struct RollingStats {
+    samples: VecDeque<Duration>,
+}
+
+impl RollingStats {
+    fn push(&mut self, sample: Duration, capacity: usize) {
+        while self.samples.len() >= capacity.max(1) {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort();
+        let max = *sorted.last()?;
+
+        // Nearest-rank percentiles
+        let percentile = |p: f64| {
+            let rank = (p * sorted.len() as f64).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+
+        Some(Summary {
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            max,
+        })
+    }
+}
+
+type DeviceStats = BTreeMap<String, RollingStats>;
+type Summaries = BTreeMap<String, BTreeMap<String, Summary>>;
+
+pub struct Profiler {
+    settings: ProfilingSettings,
+    stats: BTreeMap<String, DeviceStats>,
+    since_report: Duration,
+    overlay: Rc<Cell<bool>>,
+    overlay_shown: bool,
+    overlay_text: BTreeMap<String, String>,
+    refresh: bool,
+    csv: Option<File>,
+}
+
+impl Profiler {
+    pub fn new(settings: ProfilingSettings) -> Self {
+        let mut profiler = Self {
+            settings: Default::default(),
+            stats: BTreeMap::new(),
+            since_report: Duration::ZERO,
+            overlay: Rc::new(Cell::new(false)),
+            overlay_shown: false,
+            overlay_text: BTreeMap::new(),
+            refresh: false,
+            csv: None,
+        };
+        profiler.set_settings(settings);
+        profiler
+    }
+
+    pub fn set_settings(&mut self, settings: ProfilingSettings) {
+        self.overlay.set(settings.overlay);
+        if !settings.csv {
+            self.csv = None;
+        }
+        self.settings = settings;
+
+        if !self.is_enabled() {
+            self.stats.clear();
+        }
+    }
+
+    // Shared with the script sandbox, so that the overlay can be toggled from user scripts
+    pub fn overlay_toggle(&self) -> Rc<Cell<bool>> {
+        Rc::clone(&self.overlay)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.settings.enabled || self.overlay.get()
+    }
+
+    pub fn record(&mut self, device_name: &str, timings: FrameTimings) {
+        if !self.is_enabled() || timings.entries.is_empty() {
+            return;
+        }
+
+        let capacity = self.settings.samples;
+        let stats = self.stats.entry(device_name.to_string()).or_default();
+        let total: Duration = timings.entries.iter().map(|(_, duration)| *duration).sum();
+        for (metric, duration) in timings.entries {
+            stats.entry(metric).or_default().push(duration, capacity);
+        }
+        stats
+            .entry(TOTAL.to_string())
+            .or_default()
+            .push(total, capacity);
+    }
+
+    pub fn update(&mut self, time_passed: Duration) {
+        let overlay = self.overlay.get();
+        if overlay != self.overlay_shown {
+            self.overlay_shown = overlay;
+            self.overlay_text.clear();
+            self.refresh = true;
+        }
+
+        if !self.is_enabled() {
+            self.stats.clear();
+            self.since_report = Duration::ZERO;
+            return;
+        }
+
+        self.since_report += time_passed;
+        if self.since_report < self.settings.report_interval {
+            return;
+        }
+        self.since_report = Duration::ZERO;
+
+        let summaries = self.summaries();
+        if self.settings.enabled {
+            Self::send_event(&summaries);
+        }
+        if self.settings.csv {
+            self.write_csv(&summaries);
+        }
+        if overlay {
+            self.overlay_text = Self::overlay_text(&summaries);
+            self.refresh = true;
+        }
+    }
+
+    // Returns `Some` when the debug overlay has changed and has to be redrawn
+    pub fn take_overlay_update(&mut self) -> Option<BTreeMap<String, String>> {
+        match std::mem::take(&mut self.refresh) {
+            true => Some(self.overlay_text.clone()),
+            false => None,
+        }
+    }
+
+    fn summaries(&self) -> Summaries {
+        self.stats
+            .iter()
+            .map(|(device_name, stats)| {
+                let summaries = stats
+                    .iter()
+                    .filter_map(|(metric, stats)| Some((metric.clone(), stats.summary()?)))
+                    .collect();
+                (device_name.clone(), summaries)
+            })
+            .collect()
+    }
+
+    fn send_event(summaries: &Summaries) {
+        let milliseconds = |duration: Duration| CborValue::Float(duration.as_secs_f64() * 1000.0);
+
+        let devices = summaries
+            .iter()
+            .map(|(device_name, metrics)| {
+                let metrics = metrics
+                    .iter()
+                    .map(|(metric, summary)| {
+                        let values = vec![
+                            ("P50".into(), milliseconds(summary.p50)),
+                            ("P90".into(), milliseconds(summary.p90)),
+                            ("P99".into(), milliseconds(summary.p99)),
+                            ("Max".into(), milliseconds(summary.max)),
+                        ];
+                        (metric.as_str().into(), CborValue::Map(values))
+                    })
+                    .collect();
+                (device_name.as_str().into(), CborValue::Map(metrics))
+            })
+            .collect();
+
+        let stats = CborValue::Map(vec![("Stats".into(), CborValue::Map(devices))]);
+        let table = CborValue::Map(vec![("OMNILED".into(), stats)]);
+        EventQueue::instance()
+            .lock()
+            .unwrap()
+            .push(Event::Application(table));
+    }
+
+    fn write_csv(&mut self, summaries: &Summaries) {
+        if self.csv.is_none() {
+            self.csv = match Self::open_csv() {
+                Ok(file) => Some(file),
+                Err(err) => {
+                    error!("Failed to open profiling report: {err}");
+                    self.settings.csv = false;
+                    return;
+                }
+            };
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let milliseconds = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+        let mut rows = String::new();
+        for (device_name, metrics) in summaries {
+            for (metric, summary) in metrics {
+                rows += &format!(
+                    "{timestamp},{device_name},{metric},{:.3},{:.3},{:.3},{:.3}\n",
+                    milliseconds(summary.p50),
+                    milliseconds(summary.p90),
+                    milliseconds(summary.p99),
+                    milliseconds(summary.max),
+                );
+            }
+        }
+
+        if let Some(file) = &mut self.csv
+            && let Err(err) = file.write_all(rows.as_bytes())
+        {
+            error!("Failed to write profiling report: {err}");
+        }
+    }
+
+    fn open_csv() -> std::io::Result<File> {
+        let dir = Constants::data_dir();
+        std::fs::create_dir_all(&dir)?;
+
+        let path = dir.join(CSV_FILE);
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{CSV_HEADER}")?;
+        }
+
+        debug!("Writing profiling report to '{}'", path.display());
+        Ok(file)
+    }
+
+    fn overlay_text(summaries: &Summaries) -> BTreeMap<String, String> {
+        summaries
+            .iter()
+            .filter_map(|(device_name, metrics)| {
+                let total = metrics.get(TOTAL)?;
+                let text = format!(
+                    "{:.1}/{:.1}ms",
+                    total.p50.as_secs_f64() * 1000.0,
+                    total.p99.as_secs_f64() * 1000.0
+                );
+                Some((device_name.clone(), text))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(values: &[u64]) -> Vec<Duration> {
+        values.iter().map(|v| Duration::from_millis(*v)).collect()
+    }
+
+    #[test]
+    fn rolling_percentiles() {
+        let mut stats = RollingStats::default();
+        for sample in millis(&[100, 100])
+            .into_iter()
+            .chain((1..=10).map(Duration::from_millis))
+        {
+            stats.push(sample, 10);
+        }
+
+        // Oldest samples are dropped once the capacity is reached
+        assert_eq!(
+            stats.summary(),
+            Some(Summary {
+                p50: Duration::from_millis(5),
+                p90: Duration::from_millis(9),
+                p99: Duration::from_millis(10),
+                max: Duration::from_millis(10),
+            })
+        );
+        assert_eq!(RollingStats::default().summary(), None);
+    }
+
+    #[test]
+    fn records_totals_per_device() {
+        let mut profiler = Profiler::new(ProfilingSettings {
+            enabled: true,
+            ..Default::default()
+        });
+
+        let mut timings = FrameTimings::default();
+        timings.record("Layout1", Duration::from_millis(2));
+        timings.record("Render", Duration::from_millis(1));
+        timings.record("Render", Duration::from_millis(1));
+        profiler.record("DEVICE", timings);
+
+        let summaries = profiler.summaries();
+        let device = &summaries["DEVICE"];
+        assert_eq!(device["Render"].max, Duration::from_millis(2));
+        assert_eq!(device[TOTAL].max, Duration::from_millis(4));
+    }
+}
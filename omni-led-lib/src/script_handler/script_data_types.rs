@@ -3,6 +3,7 @@ use omni_led_derive::{FromLuaValue, LuaEnum, LuaName};
 use std::{hash::Hash, time::Duration};
 
 use crate::common::lua_traits::{FromUserdata, LuaName, LuaTypeStaticMembers, StaticMembers};
+use crate::renderer::font_selector::FontSelector;
 
 #[derive(Debug, Clone, Copy, FromLuaValue)]
 pub struct Point {
@@ -34,6 +35,58 @@ pub struct Rectangle {
 
 impl UserData for Rectangle {}
 
+#[derive(Debug, Clone, Copy, PartialEq, FromLuaValue)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::gray(0);
+    pub const WHITE: Color = Color::gray(u8::MAX);
+
+    pub const fn gray(level: u8) -> Self {
+        Self {
+            r: level,
+            g: level,
+            b: level,
+        }
+    }
+
+    // Weighted sum with weights adding up to 256, so that grays map exactly to their level
+    pub fn luminance(&self) -> u8 {
+        ((self.r as u32 * 77 + self.g as u32 * 150 + self.b as u32 * 29) >> 8) as u8
+    }
+
+    pub fn scale(&self, brightness: u8) -> Self {
+        self.blend(Color::BLACK, u8::MAX - brightness)
+    }
+
+    pub fn invert(&self) -> Self {
+        Self {
+            r: u8::MAX - self.r,
+            g: u8::MAX - self.g,
+            b: u8::MAX - self.b,
+        }
+    }
+
+    // Mixes `other` into this color, `alpha` of 255 returns `other`
+    pub fn blend(&self, other: Color, alpha: u8) -> Self {
+        let mix = |current: u8, target: u8| {
+            let alpha = alpha as u32;
+            ((current as u32 * (255 - alpha) + target as u32 * alpha + 127) / 255) as u8
+        };
+        Self {
+            r: mix(self.r, other.r),
+            g: mix(self.g, other.g),
+            b: mix(self.b, other.b),
+        }
+    }
+}
+
+impl UserData for Color {}
+
 #[derive(Debug, Clone, FromLuaValue)]
 pub struct ImageData {
     #[mlua(transform = Self::parse_format)]
@@ -96,6 +149,13 @@ pub enum Widget {
     Bar(Bar),
     Image(Image),
     Text(Text),
+    Line(Line),
+    Rect(Rect),
+    Circle(Ellipse),
+    Ellipse(Ellipse),
+    Arc(Arc),
+    Polygon(Polygon),
+    Graph(Graph),
 }
 
 impl UserData for Widget {}
@@ -138,6 +198,118 @@ pub struct Bar {
 
 impl UserData for Bar {}
 
+#[derive(Clone, Debug, FromLuaValue)]
+pub struct Line {
+    pub start: Point,
+    pub end: Point,
+    pub position: Point,
+    pub size: Size,
+
+    #[mlua(default)]
+    pub modifiers: Modifiers,
+}
+
+impl UserData for Line {}
+
+#[derive(Clone, Debug, FromLuaValue)]
+pub struct Rect {
+    #[mlua(default = false)]
+    pub filled: bool,
+    #[mlua(default = 0)]
+    pub radius: usize,
+    pub position: Point,
+    pub size: Size,
+
+    #[mlua(default)]
+    pub modifiers: Modifiers,
+}
+
+impl UserData for Rect {}
+
+#[derive(Clone, Debug, FromLuaValue)]
+pub struct Ellipse {
+    #[mlua(default = false)]
+    pub filled: bool,
+    pub position: Point,
+    pub size: Size,
+
+    #[mlua(default)]
+    pub modifiers: Modifiers,
+}
+
+impl UserData for Ellipse {}
+
+#[derive(Clone, Debug, FromLuaValue)]
+pub struct Arc {
+    pub start_angle: f32,
+    pub end_angle: f32,
+    pub position: Point,
+    pub size: Size,
+
+    #[mlua(default)]
+    pub modifiers: Modifiers,
+}
+
+impl UserData for Arc {}
+
+#[derive(Clone, Debug, FromLuaValue)]
+pub struct Polygon {
+    pub points: Vec<Point>,
+    #[mlua(default = false)]
+    pub filled: bool,
+    pub position: Point,
+    pub size: Size,
+
+    #[mlua(default)]
+    pub modifiers: Modifiers,
+}
+
+impl UserData for Polygon {}
+
+#[derive(Clone, Copy, Debug, PartialEq, LuaEnum)]
+pub enum GraphStyle {
+    Line,
+    Bars,
+    Filled,
+}
+
+impl UserData for GraphStyle {}
+
+#[derive(Clone, Debug, FromLuaValue)]
+#[mlua(validate = Self::validate_data)]
+pub struct Graph {
+    pub values: Option<Vec<f32>>,
+    pub source: Option<String>,
+    pub capacity: Option<usize>,
+    #[mlua(default = Range {min: 0.0, max: 100.0})]
+    pub range: Range,
+    #[mlua(default = GraphStyle::Line)]
+    pub style: GraphStyle,
+    pub position: Point,
+    pub size: Size,
+
+    #[mlua(default)]
+    pub modifiers: Modifiers,
+}
+
+impl Graph {
+    fn validate_data(graph: &Self) -> mlua::Result<()> {
+        if graph.values.is_some() == graph.source.is_some() {
+            return Err(mlua::Error::runtime(
+                "Graph requires exactly one of 'values' or 'source'",
+            ));
+        }
+        if graph.capacity == Some(0) {
+            return Err(mlua::Error::runtime(
+                "Graph capacity has to be greater than zero",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl UserData for Graph {}
+
 #[derive(Debug, PartialEq, Copy, Clone, LuaEnum)]
 pub enum Repeat {
     Once,
@@ -147,12 +319,28 @@ pub enum Repeat {
 impl UserData for Repeat {}
 
 #[derive(Clone, Debug, FromLuaValue)]
+#[mlua(validate = Self::validate)]
 pub struct Image {
     pub image: ImageData,
     #[mlua(default = false)]
     pub animated: bool,
+    #[mlua(default = ImageFit::Fill)]
+    pub fit: ImageFit,
+    #[mlua(default = HorizontalAlign::Center)]
+    pub align: HorizontalAlign,
+    #[mlua(default = VerticalAlign::Center)]
+    pub valign: VerticalAlign,
+    #[mlua(default = ImageFilter::Nearest)]
+    pub filter: ImageFilter,
+    pub crop: Option<Rectangle>,
     #[mlua(default = 128)]
     pub threshold: u8,
+    #[mlua(default = Dither::Threshold)]
+    pub dither: Dither,
+    #[mlua(default = 1.0)]
+    pub gamma: f32,
+    #[mlua(default = 1.0)]
+    pub contrast: f32,
     #[mlua(default = Repeat::ForDuration)]
     pub repeats: Repeat,
     pub animation_group: Option<usize>,
@@ -165,18 +353,87 @@ pub struct Image {
     pub modifiers: Modifiers,
 }
 
+impl Image {
+    fn validate(image: &Self) -> mlua::Result<()> {
+        if let Some(crop) = image.crop
+            && (crop.size.width == 0 || crop.size.height == 0)
+        {
+            return Err(mlua::Error::runtime(
+                "Image crop has to be at least 1x1 pixels",
+            ));
+        }
+
+        if !image.gamma.is_finite() || image.gamma <= 0.0 {
+            return Err(mlua::Error::runtime(format!(
+                "Image gamma has to be a finite number greater than 0, got {}",
+                image.gamma
+            )));
+        }
+
+        if !image.contrast.is_finite() {
+            return Err(mlua::Error::runtime(format!(
+                "Image contrast has to be a finite number, got {}",
+                image.contrast
+            )));
+        }
+
+        Ok(())
+    }
+}
+
 impl UserData for Image {}
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, LuaEnum)]
+pub enum ImageFit {
+    Fill,
+    Contain,
+    Cover,
+    None,
+}
+
+impl UserData for ImageFit {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, LuaEnum)]
+pub enum ImageFilter {
+    Nearest,
+    Triangle,
+    Lanczos3,
+}
+
+impl UserData for ImageFilter {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, LuaEnum)]
+pub enum Dither {
+    Threshold,
+    FloydSteinberg,
+    Atkinson,
+    Bayer4,
+    Bayer8,
+}
+
+impl UserData for Dither {}
+
 #[derive(Clone, Debug, FromLuaValue)]
 pub struct Text {
     pub text: String,
     pub text_offset: Option<isize>,
+    pub font: Option<FontSelector>,
     #[mlua(default = FontSize::Auto)]
     pub font_size: FontSize,
     #[mlua(default = false)]
     pub scrolling: bool,
     #[mlua(default = Repeat::ForDuration)]
     pub repeats: Repeat,
+    #[mlua(default = HorizontalAlign::Left)]
+    pub align: HorizontalAlign,
+    #[mlua(default = VerticalAlign::Bottom)]
+    pub valign: VerticalAlign,
+    #[mlua(default = TextWrap::None)]
+    pub wrap: TextWrap,
+    #[mlua(default = 0)]
+    pub line_spacing: isize,
+    #[mlua(default = false)]
+    pub ellipsis: bool,
     pub animation_group: Option<usize>,
     pub animation_ticks_delay: Option<usize>,
     pub animation_ticks_rate: Option<usize>,
@@ -188,8 +445,61 @@ pub struct Text {
     pub modifiers: Modifiers,
 }
 
+impl Text {
+    // Plain text with the same defaults as widgets created in scripts
+    pub fn new(text: String, position: Point, size: Size) -> Self {
+        Self {
+            text,
+            text_offset: None,
+            font: None,
+            font_size: FontSize::Auto,
+            scrolling: false,
+            repeats: Repeat::ForDuration,
+            align: HorizontalAlign::Left,
+            valign: VerticalAlign::Bottom,
+            wrap: TextWrap::None,
+            line_spacing: 0,
+            ellipsis: false,
+            animation_group: None,
+            animation_ticks_delay: None,
+            animation_ticks_rate: None,
+            position,
+            size,
+            hash: None,
+            modifiers: Modifiers::default(),
+        }
+    }
+}
+
 impl UserData for Text {}
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, LuaEnum)]
+pub enum HorizontalAlign {
+    Left,
+    Center,
+    Right,
+}
+
+impl UserData for HorizontalAlign {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, LuaEnum)]
+pub enum VerticalAlign {
+    Top,
+    Center,
+    Bottom,
+}
+
+impl UserData for VerticalAlign {}
+
+#[derive(Clone, Copy, Debug, PartialEq, LuaEnum)]
+pub enum TextWrap {
+    None,
+    Word,
+    Char,
+}
+
+impl UserData for TextWrap {}
+
 #[derive(Copy, Clone, Debug, LuaEnum)]
 pub enum FontSize {
     #[mlua(implicit_construct)]
@@ -200,7 +510,7 @@ pub enum FontSize {
 
 impl UserData for FontSize {}
 
-#[derive(Clone, Copy, Debug, Default, FromLuaValue)]
+#[derive(Clone, Copy, Debug, FromLuaValue)]
 pub struct Modifiers {
     #[mlua(default = false)]
     pub clear_background: bool,
@@ -213,17 +523,65 @@ pub struct Modifiers {
 
     #[mlua(default = false)]
     pub negative: bool,
+
+    #[mlua(default = 255)]
+    pub brightness: u8,
+
+    #[mlua(default = Color::WHITE)]
+    pub color: Color,
+
+    #[mlua(default = Color::BLACK)]
+    pub background: Color,
+}
+
+impl Default for Modifiers {
+    fn default() -> Self {
+        Self {
+            clear_background: false,
+            flip_horizontal: false,
+            flip_vertical: false,
+            negative: false,
+            brightness: u8::MAX,
+            color: Color::WHITE,
+            background: Color::BLACK,
+        }
+    }
 }
 
 impl UserData for Modifiers {}
 
-#[derive(Clone, Copy, LuaEnum)]
+#[derive(Clone, Copy, Debug, PartialEq, LuaEnum)]
+pub enum TransitionEffect {
+    SlideLeft,
+    SlideRight,
+    Wipe,
+    Dissolve,
+    PushUp,
+}
+
+impl UserData for TransitionEffect {}
+
+#[derive(Clone, Copy, Debug, FromLuaValue)]
+pub struct Transition {
+    pub effect: TransitionEffect,
+    #[mlua(default = 8)]
+    pub ticks: usize,
+}
+
+impl UserData for Transition {}
+
+#[derive(Clone, Copy, PartialEq, LuaEnum)]
 pub enum MemoryLayout {
     #[mlua(alias = "SteelSeries")]
     BitPerPixel,
     BytePerPixel,
     #[mlua(alias = "SteelSeries2")]
     BitPerPixelVertical,
+    TwoBitsPerPixel,
+    #[mlua(alias = "SSD1322")]
+    FourBitsPerPixel,
+    Rgb565,
+    Rgb888,
 }
 
 impl UserData for MemoryLayout {}
@@ -1,48 +1,230 @@
-use log::{debug, warn};
-use mlua::{Function, Lua, Table, UserData, UserDataMethods, Value, chunk};
+use log::{debug, error, warn};
+use mlua::{Either, Function, Lua, Table, UserData, UserDataMethods, Value, chunk};
 use omni_led_derive::{FromLuaValue, LuaName};
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::rc::Rc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::common::lua_traits::{LuaTypeStaticMembers, StaticMembers};
 use crate::common::user_data::{UserDataRef, set_unique_user_data};
-use crate::constants::config::{ConfigType, load_config};
+use crate::constants::config::{ConfigType, load_config, read_config};
 use crate::create_table_with_defaults;
-use crate::devices::device::Device;
+use crate::devices::device::{Device, MemoryLayout};
 use crate::devices::devices::Devices;
 use crate::events::cbor_to_lua::get_cleanup_entries_metatable;
 use crate::events::events::Events;
 use crate::events::shortcuts::Shortcuts;
 use crate::renderer::animation::State;
 use crate::renderer::animation_group::AnimationGroup;
+use crate::renderer::buffer::Buffer;
 use crate::renderer::renderer::Renderer;
-use crate::script_handler::script_data_types::{DurationWrapper, EventKey, Regex, Widget};
+use crate::renderer::screen_protection::{ScreenProtection, ScreenProtectionSettings};
+use crate::renderer::transition::TransitionState;
+use crate::script_handler::carousel::{Carousel, CarouselSettings};
+use crate::script_handler::dependencies::{self, Dependencies};
+use crate::script_handler::modules::Modules;
+use crate::script_handler::overlay::{OverlayData, Overlays};
+use crate::script_handler::profiler::{FrameTimings, Profiler};
+use crate::script_handler::script_data_types::{
+    DurationWrapper, EventKey, Point, Rectangle, Regex, Size, Text, Transition, Widget,
+};
+use crate::script_handler::storage::{GLOBAL_NAMESPACE, StorageNamespace};
+use crate::settings::settings::Settings;
 
 #[derive(LuaName)]
 pub struct ScriptHandler {
     environment: Table,
     renderer: Renderer,
     devices: Vec<DeviceContext>,
+    modules: Rc<RefCell<Modules>>,
+    pending_overlays: Rc<RefCell<Vec<OverlayData>>>,
+    profiler: Profiler,
+    event_roots: HashSet<String>,
 }
 
+// Screen definition shown on one or more devices
 struct DeviceContext {
+    outputs: Vec<DeviceOutput>,
+    // Devices that failed to open again after a reload, retried on the next one
+    closed: Vec<String>,
+    targets: Vec<RenderTarget>,
+    zones: Vec<Zone>,
+    screensaver: Option<Function>,
+    screensaver_animation_groups: HashMap<usize, AnimationGroup>,
+    idle: bool,
+    timings: FrameTimings,
+}
+
+impl DeviceContext {
+    // Name used in profiling reports, e.g. 'Apex Pro, Emulator'
+    fn name(&self) -> String {
+        let names: Vec<&str> = self.outputs.iter().map(|x| x.name.as_str()).collect();
+        names.join(", ")
+    }
+
+    fn has_device(&self, device_name: &str) -> bool {
+        self.outputs.iter().any(|output| output.name == device_name)
+            || self.closed.iter().any(|name| name == device_name)
+    }
+
+    fn reset_state(&mut self) {
+        for zone in &mut self.zones {
+            zone.reset_state();
+        }
+    }
+
+    // Drops everything computed for the screen size, it could have changed before the next update
+    fn reset_targets(&mut self) {
+        self.targets.clear();
+        for zone in &mut self.zones {
+            zone.animation_groups.iter_mut().for_each(HashMap::clear);
+        }
+        self.screensaver_animation_groups.clear();
+        self.reset_state();
+    }
+}
+
+struct DeviceOutput {
     device: Box<dyn Device>,
     name: String,
+    target: usize,
+    protection: ScreenProtection,
+    overlays: Overlays,
+}
+
+// Part of the screen with its own layouts, covers the whole screen if `area` is not set
+struct Zone {
+    area: Option<Rectangle>,
     layouts: Vec<Layout>,
-    animation_groups: Vec<HashMap<usize, AnimationGroup>>,
     layout_update_flags: Vec<bool>,
+    dependencies: Vec<Option<Dependencies>>,
+    // Shared by all render targets, so that mirrored devices animate in sync
+    animation_groups: Vec<HashMap<usize, AnimationGroup>>,
     time_remaining: Duration,
-    last_priority: usize,
+    last_priority: Option<usize>,
     state: State,
+    carousel: Option<Carousel>,
+    screen_changed: Rc<Cell<bool>>,
+}
+
+impl Zone {
+    fn new(
+        area: Option<Rectangle>,
+        layouts: Vec<Layout>,
+        carousel: Option<Carousel>,
+        screen_changed: Rc<Cell<bool>>,
+    ) -> Self {
+        let layout_count = layouts.len();
+        Self {
+            area,
+            layouts,
+            layout_update_flags: vec![false; layout_count],
+            dependencies: vec![None; layout_count],
+            animation_groups: vec![HashMap::new(); layout_count],
+            time_remaining: Duration::ZERO,
+            last_priority: None,
+            state: State::Finished,
+            carousel,
+            screen_changed,
+        }
+    }
+
+    fn size(&self, screen: Size) -> Size {
+        self.area.map_or(screen, |area| area.size)
+    }
+
+    fn reset(&mut self) {
+        self.layout_update_flags.fill(false);
+        self.reset_state();
+        if let Some(carousel) = &mut self.carousel {
+            carousel.reset();
+        }
+    }
+
+    fn reset_state(&mut self) {
+        self.time_remaining = Duration::ZERO;
+        self.last_priority = None;
+        self.state = State::Finished;
+    }
+}
+
+// Frames are rendered once for all devices with the same size and memory layout
+struct RenderTarget {
+    size: Size,
+    memory_layout: MemoryLayout,
+    zones: Vec<ZoneTarget>,
+    last_frame: Option<Buffer>,
+    changed: bool,
+}
+
+struct ZoneTarget {
+    last_frame: Option<Buffer>,
+    transition: Option<TransitionState>,
+}
+
+impl RenderTarget {
+    fn new(size: Size, memory_layout: MemoryLayout, zones: &[Zone]) -> Self {
+        let zones = zones
+            .iter()
+            .map(|_| ZoneTarget {
+                last_frame: None,
+                transition: None,
+            })
+            .collect();
+
+        Self {
+            size,
+            memory_layout,
+            zones,
+            last_frame: None,
+            changed: false,
+        }
+    }
+
+    // Zones are drawn in the order they were registered, each one clipped to its area
+    fn composite(&self, zones: &[Zone]) -> Buffer {
+        if let ([zone], [target]) = (zones, self.zones.as_slice())
+            && zone.area.is_none()
+            && let Some(last_frame) = &target.last_frame
+        {
+            return last_frame.clone();
+        }
+
+        let mut buffer = Buffer::new(self.size, self.memory_layout);
+        for (zone, target) in zones.iter().zip(&self.zones) {
+            let Some(frame) = &target.last_frame else {
+                continue;
+            };
+
+            let position = zone.area.map_or(Point { x: 0, y: 0 }, |area| area.position);
+            let width = frame
+                .width()
+                .min(self.size.width.saturating_sub(position.x));
+            let height = frame
+                .height()
+                .min(self.size.height.saturating_sub(position.y));
+            for y in 0..height {
+                for x in 0..width {
+                    buffer.set_color(position.x + x, position.y + y, frame.get_color(x, y));
+                }
+            }
+        }
+        buffer
+    }
 }
 
 const DEFAULT_UPDATE_TIME: Duration = Duration::from_millis(1000);
 
 impl ScriptHandler {
-    pub fn load(lua: &Lua, config: String) {
-        let environment = Self::make_sandbox(lua);
+    // Modules loaded with `require` are resolved relative to `modules_root`
+    pub fn load(lua: &Lua, config: String, modules_root: PathBuf) -> mlua::Result<()> {
+        let pending_overlays = Rc::new(RefCell::new(Vec::new()));
+        let profiler = Profiler::new(UserDataRef::<Settings>::load(lua).get().profiling.clone());
+        let modules = Modules::new(modules_root);
+        let environment = Self::make_sandbox(lua, &pending_overlays, &profiler, &modules);
+        modules.borrow_mut().set_environment(environment.clone());
 
         set_unique_user_data(
             lua,
@@ -50,6 +232,10 @@ impl ScriptHandler {
                 renderer: Renderer::new(lua),
                 environment: environment.clone(),
                 devices: vec![],
+                modules,
+                pending_overlays,
+                profiler,
+                event_roots: HashSet::new(),
             },
         );
 
@@ -57,11 +243,20 @@ impl ScriptHandler {
             .create_function(|lua, (event, value): (String, Value)| {
                 let mut this = UserDataRef::<ScriptHandler>::load(lua);
                 this.get_mut().mark_for_update(&event);
+                this.get_mut()
+                    .renderer
+                    .graph_history()
+                    .mark_for_sampling(&event);
+
+                if event.starts_with("KEY(") {
+                    this.get_mut().register_input();
+                }
 
                 if !event.contains('.') {
                     // Set values recursively only from top-level application events
-                    let env = &this.get().environment;
-                    Self::set_value(lua, env, &event, value)?;
+                    let env = this.get().environment.clone();
+                    Self::set_value(lua, &env, &event, value)?;
+                    this.get_mut().event_roots.insert(event);
                 }
 
                 Ok(())
@@ -71,7 +266,7 @@ impl ScriptHandler {
         let regex = Regex::new(".*").unwrap();
         Events::register(EventKey::Regex(regex), event_handler, true);
 
-        Self::reload_config(lua, config).unwrap()
+        Self::reload_config(lua, config)
     }
 
     pub fn reload_config(lua: &Lua, config: String) -> mlua::Result<()> {
@@ -80,17 +275,178 @@ impl ScriptHandler {
         let mut this = UserDataRef::<Self>::load(lua);
         let environment = this.get().environment.clone();
         this.get_mut().cleanup(lua)?;
+        this.get().modules.borrow_mut().clear();
 
         load_config(lua, ConfigType::Scripts, &config, environment)
     }
 
+    #[cfg(test)]
+    pub(crate) fn environment(&self) -> &Table {
+        &self.environment
+    }
+
+    pub fn reload(lua: &Lua) {
+        let device_names: Vec<String> = UserDataRef::<Self>::load(lua)
+            .get()
+            .devices
+            .iter()
+            .flat_map(|ctx| ctx.outputs.iter().map(|output| output.name.clone()))
+            .collect();
+
+        let result =
+            read_config(ConfigType::Scripts).and_then(|config| Self::reload_config(lua, config));
+        let err = match result {
+            Ok(()) => return,
+            Err(err) => err,
+        };
+        error!("Failed to reload user scripts: {err}");
+
+        // Keep the devices that didn't get registered again busy with the error message
+        let mut this = UserDataRef::<Self>::load(lua);
+        for device_name in device_names {
+            if this
+                .get()
+                .devices
+                .iter()
+                .any(|ctx| ctx.has_device(&device_name))
+            {
+                continue;
+            }
+
+            if let Err(err) = this.get_mut().show_error(lua, &device_name, &err) {
+                error!("Failed to show error on device '{device_name}': {err}");
+            }
+        }
+    }
+
+    fn show_error(&mut self, lua: &Lua, device_name: &str, err: &mlua::Error) -> mlua::Result<()> {
+        let message = err
+            .to_string()
+            .lines()
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let message = format!("Script error: {message}");
+
+        let environment = self.environment.clone();
+        let layout = lua.create_function(move |lua, _: ()| {
+            let screen: Size = environment.get("SCREEN")?;
+            let text = Widget::Text(Text {
+                scrolling: true,
+                ..Text::new(message.clone(), Point { x: 0, y: 0 }, screen)
+            });
+
+            let output = lua.create_table()?;
+            output.set("widgets", vec![text])?;
+            Ok(output)
+        })?;
+
+        let layout = Layout {
+            layout,
+            predicate: None,
+            run_on: vec![EventKey::String("OMNILED.Update".to_string())],
+            track_dependencies: false,
+            transition: None,
+        };
+        let zone = Zone::new(None, vec![layout], None, Rc::new(Cell::new(false)));
+        self.register(lua, vec![device_name.to_string()], zone, None)
+    }
+
+    pub fn reopen_devices(&mut self, lua: &Lua, device_names: &[String]) -> mlua::Result<()> {
+        let mut devices = UserDataRef::<Devices>::load(lua);
+        let mut errors = Vec::new();
+        for ctx in &mut self.devices {
+            if !device_names.iter().any(|name| ctx.has_device(name)) && ctx.closed.is_empty() {
+                continue;
+            }
+
+            let mut closed = Vec::new();
+            for mut output in std::mem::take(&mut ctx.outputs) {
+                if !device_names.contains(&output.name) {
+                    ctx.outputs.push(output);
+                    continue;
+                }
+
+                // Old device has to be closed first, the new one could be using the same hardware
+                let unloaded = devices.get_mut().unload_device(lua, output.device);
+                let reopened =
+                    unloaded.and_then(|()| devices.get_mut().load_device(lua, output.name.clone()));
+                match reopened {
+                    Ok(device) => {
+                        output.device = device;
+                        ctx.outputs.push(output);
+                    }
+                    Err(err) => {
+                        errors.push(format!(
+                            "Failed to reopen device '{}': {}",
+                            output.name, err
+                        ));
+                        closed.push(output.name);
+                    }
+                }
+            }
+
+            // Devices that failed before could have been fixed by any change to the configuration
+            for name in std::mem::take(&mut ctx.closed) {
+                match devices.get_mut().load_device(lua, name.clone()) {
+                    Ok(device) => {
+                        let protection =
+                            ScreenProtection::new(Self::protection_settings(lua, &name));
+                        ctx.outputs.push(DeviceOutput {
+                            device,
+                            name,
+                            target: 0,
+                            protection,
+                            overlays: Overlays::default(),
+                        });
+                    }
+                    Err(err) => {
+                        errors.push(format!("Failed to reopen device '{}': {}", name, err));
+                        closed.push(name);
+                    }
+                }
+            }
+            ctx.closed = closed;
+
+            // Screen size could have changed, so anything rendered before is no longer valid
+            ctx.reset_targets();
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(mlua::Error::runtime(errors.join("\n"))),
+        }
+    }
+
+    pub fn load_settings(&mut self, lua: &Lua) {
+        self.renderer.load_settings(lua);
+
+        let settings = UserDataRef::<Settings>::load(lua).get().profiling.clone();
+        self.profiler.set_settings(settings);
+
+        for output in self.devices.iter_mut().flat_map(|ctx| &mut ctx.outputs) {
+            let settings = Self::protection_settings(lua, &output.name);
+            output.protection.set_settings(settings);
+        }
+    }
+
+    fn protection_settings(lua: &Lua, device_name: &str) -> ScreenProtectionSettings {
+        let settings = UserDataRef::<Settings>::load(lua);
+        let settings = settings.get();
+        settings
+            .screen_protection
+            .get(device_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
     fn cleanup(&mut self, lua: &Lua) -> mlua::Result<()> {
         // Clear device contexts and unload the devices
         let mut devices = UserDataRef::<Devices>::load(lua);
         let mut device_contexts = Vec::new();
         std::mem::swap(&mut self.devices, &mut device_contexts);
-        for device_ctx in device_contexts {
-            devices.get_mut().unload_device(lua, device_ctx.device)?;
+        for output in device_contexts.into_iter().flat_map(|ctx| ctx.outputs) {
+            devices.get_mut().unload_device(lua, output.device)?;
         }
 
         Ok(())
@@ -124,33 +480,77 @@ impl ScriptHandler {
     }
 
     fn mark_for_update(&mut self, key: &String) {
-        for device in &mut self.devices {
-            for (index, layout) in device.layouts.iter().enumerate() {
-                for event_key in &layout.run_on {
-                    if event_key.matches(key) {
-                        device.layout_update_flags[index] = true;
-                        break;
-                    }
+        for zone in self.devices.iter_mut().flat_map(|ctx| &mut ctx.zones) {
+            for (index, layout) in zone.layouts.iter().enumerate() {
+                let tracked = layout.track_dependencies
+                    && match &zone.dependencies[index] {
+                        Some(dependencies) => dependencies.matches(key),
+                        // Layout has to run once before its dependencies are known
+                        None => true,
+                    };
+
+                if tracked || layout.run_on.iter().any(|event_key| event_key.matches(key)) {
+                    zone.layout_update_flags[index] = true;
                 }
             }
         }
     }
 
     pub fn update(&mut self, lua: &Lua, time_passed: Duration) -> mlua::Result<()> {
+        let pending_overlays = std::mem::take(&mut *self.pending_overlays.borrow_mut());
+        for overlay in pending_overlays {
+            for output in self.devices.iter_mut().flat_map(|ctx| &mut ctx.outputs) {
+                if overlay
+                    .device
+                    .as_ref()
+                    .is_none_or(|name| *name == output.name)
+                {
+                    output.overlays.push(overlay.clone());
+                }
+            }
+        }
+
+        self.renderer.graph_history().sample(&self.environment)?;
+
+        self.profiler.update(time_passed);
+        let debug_text = self.profiler.take_overlay_update();
+
         let env = &self.environment;
         for device in &mut self.devices {
-            Self::update_impl(lua, device, &mut self.renderer, &env, time_passed)?;
+            // Screen stays registered while all of its devices are closed
+            if device.outputs.is_empty() {
+                continue;
+            }
+
+            let name = device.name();
+            if let Some(debug_text) = &debug_text {
+                let text = debug_text.get(&name).cloned();
+                for output in &mut device.outputs {
+                    output.overlays.set_debug_text(text.clone());
+                }
+            }
+
+            Self::update_impl(
+                lua,
+                device,
+                &mut self.renderer,
+                env,
+                &self.event_roots,
+                time_passed,
+            )?;
+
+            let timings = std::mem::take(&mut device.timings);
+            self.profiler.record(&name, timings);
         }
         Ok(())
     }
 
     fn reset(&mut self, device_name: &String) {
-        match self.devices.iter_mut().find(|x| x.name == *device_name) {
+        match self.devices.iter_mut().find(|x| x.has_device(device_name)) {
             Some(ctx) => {
-                ctx.layout_update_flags.fill(false);
-                ctx.time_remaining = Duration::ZERO;
-                ctx.last_priority = 0;
-                ctx.state = State::Finished;
+                for zone in &mut ctx.zones {
+                    zone.reset();
+                }
             }
             None => {
                 warn!("Device {} not found", device_name);
@@ -161,29 +561,134 @@ impl ScriptHandler {
     fn register(
         &mut self,
         lua: &Lua,
-        device_name: String,
-        layouts: Vec<Layout>,
+        device_names: Vec<String>,
+        zone: Zone,
+        screensaver: Option<Function>,
     ) -> mlua::Result<()> {
+        if zone.area.is_some()
+            && let Some(ctx) = self
+                .devices
+                .iter_mut()
+                .find(|ctx| device_names.iter().any(|name| ctx.has_device(name)))
+            && ctx.zones.iter().all(|zone| zone.area.is_some())
+        {
+            return Self::add_zone(lua, ctx, device_names, zone, screensaver);
+        }
+
         let mut devices = UserDataRef::<Devices>::load(lua);
-        let device = devices.get_mut().load_device(lua, device_name.clone())?;
+        let mut outputs: Vec<DeviceOutput> = Vec::new();
+        for device_name in device_names {
+            if outputs.iter().any(|output| output.name == device_name) {
+                continue;
+            }
 
-        let layout_count = layouts.len();
+            let device = match devices.get_mut().load_device(lua, device_name.clone()) {
+                Ok(device) => device,
+                Err(err) => {
+                    // Release devices loaded so far, they can be registered again after an error
+                    for output in outputs {
+                        devices.get_mut().unload_device(lua, output.device)?;
+                    }
+                    return Err(err);
+                }
+            };
+
+            let protection = ScreenProtection::new(Self::protection_settings(lua, &device_name));
+            outputs.push(DeviceOutput {
+                device,
+                name: device_name,
+                target: 0,
+                protection,
+                overlays: Overlays::default(),
+            });
+        }
+
+        if outputs.is_empty() {
+            return Err(mlua::Error::runtime("No devices provided to register"));
+        }
+
+        let fits = outputs
+            .iter_mut()
+            .try_for_each(|output| Self::check_zone(lua, output, &zone));
+        if let Err(err) = fits {
+            for output in outputs {
+                devices.get_mut().unload_device(lua, output.device)?;
+            }
+            return Err(err);
+        }
 
         let context = DeviceContext {
-            device,
-            name: device_name,
-            layouts,
-            animation_groups: vec![HashMap::new(); layout_count],
-            layout_update_flags: vec![false; layout_count],
-            time_remaining: Default::default(),
-            last_priority: 0,
-            state: State::Finished,
+            outputs,
+            closed: Vec::new(),
+            targets: Vec::new(),
+            zones: vec![zone],
+            screensaver,
+            screensaver_animation_groups: HashMap::new(),
+            idle: false,
+            timings: FrameTimings::default(),
         };
         self.devices.push(context);
 
         Ok(())
     }
 
+    fn add_zone(
+        lua: &Lua,
+        ctx: &mut DeviceContext,
+        device_names: Vec<String>,
+        zone: Zone,
+        screensaver: Option<Function>,
+    ) -> mlua::Result<()> {
+        let same_devices = device_names.iter().all(|name| ctx.has_device(name))
+            && ctx
+                .outputs
+                .iter()
+                .map(|output| &output.name)
+                .chain(&ctx.closed)
+                .all(|name| device_names.contains(name));
+        if !same_devices {
+            return Err(mlua::Error::runtime(format!(
+                "Zones of '{}' have to be registered for the same devices",
+                ctx.name()
+            )));
+        }
+        for output in &mut ctx.outputs {
+            Self::check_zone(lua, output, &zone)?;
+        }
+
+        ctx.zones.push(zone);
+        if screensaver.is_some() {
+            ctx.screensaver = screensaver;
+        }
+
+        // Targets are created again with the new zone on the next update
+        ctx.reset_targets();
+        Ok(())
+    }
+
+    fn check_zone(lua: &Lua, output: &mut DeviceOutput, zone: &Zone) -> mlua::Result<()> {
+        let Some(area) = &zone.area else {
+            return Ok(());
+        };
+
+        let screen = output.device.size(lua)?;
+        if area.position.x + area.size.width > screen.width
+            || area.position.y + area.size.height > screen.height
+        {
+            return Err(mlua::Error::runtime(format!(
+                "Zone at ({}, {}) of size {}x{} doesn't fit the {}x{} screen of '{}'",
+                area.position.x,
+                area.position.y,
+                area.size.width,
+                area.size.height,
+                screen.width,
+                screen.height,
+                output.name
+            )));
+        }
+        Ok(())
+    }
+
     fn test_predicate(function: &Option<Function>) -> mlua::Result<bool> {
         let predicate = match function {
             Some(predicate) => predicate.call::<_>(())?,
@@ -192,37 +697,155 @@ impl ScriptHandler {
         Ok(predicate)
     }
 
+    fn register_input(&mut self) {
+        for ctx in &mut self.devices {
+            for output in &mut ctx.outputs {
+                output.protection.register_input();
+            }
+            for carousel in ctx
+                .zones
+                .iter_mut()
+                .filter_map(|zone| zone.carousel.as_mut())
+            {
+                carousel.register_input();
+            }
+        }
+    }
+
+    // Groups devices by their size and memory layout, so that each group is rendered only once
+    fn update_targets(lua: &Lua, ctx: &mut DeviceContext) -> mlua::Result<()> {
+        let mut keys = Vec::with_capacity(ctx.outputs.len());
+        for output in &mut ctx.outputs {
+            keys.push((output.device.size(lua)?, output.device.memory_layout(lua)?));
+        }
+
+        let unchanged = !ctx.targets.is_empty()
+            && ctx.outputs.iter().zip(&keys).all(|(output, key)| {
+                let target = &ctx.targets[output.target];
+                (target.size, target.memory_layout) == *key
+            });
+        if unchanged {
+            return Ok(());
+        }
+
+        ctx.targets.clear();
+        for (output, (size, memory_layout)) in ctx.outputs.iter_mut().zip(keys) {
+            let existing = ctx
+                .targets
+                .iter()
+                .position(|target| target.size == size && target.memory_layout == memory_layout);
+            output.target = match existing {
+                Some(index) => index,
+                None => {
+                    let target = RenderTarget::new(size, memory_layout, &ctx.zones);
+                    ctx.targets.push(target);
+                    ctx.targets.len() - 1
+                }
+            };
+        }
+        ctx.reset_state();
+
+        Ok(())
+    }
+
+    // Devices render separately only if their sizes differ, in that case the screen keeps going
+    // until animations on all of them can finish
+    fn merge_states(left: State, right: State) -> State {
+        match (left, right) {
+            (State::InProgress, _) | (_, State::InProgress) => State::InProgress,
+            (State::CanFinish, _) | (_, State::CanFinish) => State::CanFinish,
+            (State::Finished, State::Finished) => State::Finished,
+        }
+    }
+
     fn update_impl(
         lua: &Lua,
         ctx: &mut DeviceContext,
         renderer: &mut Renderer,
         env: &Table,
+        event_roots: &HashSet<String>,
         time_passed: Duration,
     ) -> mlua::Result<()> {
-        ctx.time_remaining = ctx.time_remaining.saturating_sub(time_passed);
-        let has_time_remaining = !ctx.time_remaining.is_zero();
+        for output in &mut ctx.outputs {
+            output.protection.update(time_passed);
+            output.overlays.update(time_passed);
+        }
+        Self::update_targets(lua, ctx)?;
+
+        // Mirrored screens are idle only once all of their devices are idle
+        if ctx.outputs.iter().all(|output| output.protection.is_idle()) {
+            return Self::update_idle(lua, ctx, renderer, env);
+        }
+        ctx.idle = false;
+
+        for zone in 0..ctx.zones.len() {
+            Self::update_zone(lua, ctx, zone, renderer, env, event_roots, time_passed)?;
+        }
+
+        for index in 0..ctx.targets.len() {
+            match std::mem::take(&mut ctx.targets[index].changed) {
+                true => {
+                    let image = ctx.targets[index].composite(&ctx.zones);
+                    Self::present(lua, ctx, index, renderer, image)?;
+                }
+                false => Self::update_unchanged(lua, ctx, index, renderer)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn update_zone(
+        lua: &Lua,
+        ctx: &mut DeviceContext,
+        zone_index: usize,
+        renderer: &mut Renderer,
+        env: &Table,
+        event_roots: &HashSet<String>,
+        time_passed: Duration,
+    ) -> mlua::Result<()> {
+        let zone_count = ctx.zones.len();
+        let zone = &mut ctx.zones[zone_index];
+
+        // Screens can be selected from scripts at any time, so the reset is deferred until here.
+        // Update flags are kept, layouts of the new screen may have been marked since then.
+        if zone.screen_changed.take() {
+            zone.reset_state();
+            if let Some(carousel) = &mut zone.carousel {
+                carousel.reset();
+            }
+        }
+
+        // Layouts of the new screen can still be drawn in this update if they were marked for it
+        if let Some(carousel) = &mut zone.carousel
+            && carousel.update(time_passed, zone.state)?
+        {
+            zone.reset_state();
+        }
+
+        zone.time_remaining = zone.time_remaining.saturating_sub(time_passed);
+        let has_time_remaining = !zone.time_remaining.is_zero();
 
         let mut to_update = None;
         let mut new_update = false;
-        for (priority, marked_for_update) in ctx.layout_update_flags.iter().enumerate() {
+        for (priority, marked_for_update) in zone.layout_update_flags.iter().enumerate() {
             // If a more important layout still has time remaining, don't bother checking further
-            if has_time_remaining && ctx.last_priority < priority {
+            if has_time_remaining && zone.last_priority.is_some_and(|last| last < priority) {
                 break;
             }
 
-            if *marked_for_update && Self::test_predicate(&ctx.layouts[priority].predicate)? {
+            if *marked_for_update && Self::test_predicate(&zone.layouts[priority].predicate)? {
                 to_update = Some(priority);
                 new_update = true;
                 break;
             }
 
             // Handle repetition if currently processed priority is equal to that of the last update
-            if ctx.last_priority == priority {
+            if zone.last_priority == Some(priority) {
                 // For `Repeat::ForDuration` make sure that there is still time remaining
-                let repeat_for_duration = has_time_remaining && ctx.state == State::CanFinish;
+                let repeat_for_duration = has_time_remaining && zone.state == State::CanFinish;
 
                 // For `Repeat::Once` make sure that the animation is in progress
-                let repeat_once = ctx.state == State::InProgress;
+                let repeat_once = zone.state == State::InProgress;
 
                 if repeat_for_duration || repeat_once {
                     to_update = Some(priority);
@@ -231,39 +854,250 @@ impl ScriptHandler {
             }
         }
 
-        ctx.layout_update_flags.fill(false);
+        zone.layout_update_flags.fill(false);
 
         let to_update = match to_update {
             Some(to_update) => to_update,
-            None => return Ok(()),
+            None => {
+                // Keep playing transitions of a screen that doesn't need to be updated
+                for target in &mut ctx.targets {
+                    let zone_target = &mut target.zones[zone_index];
+                    if let Some(transition) = &mut zone_target.transition {
+                        zone_target.last_frame = Some(transition.step());
+                        if transition.is_finished() {
+                            zone_target.transition = None;
+                        }
+                        target.changed = true;
+                    }
+                }
+                return Ok(());
+            }
+        };
+        let screen_changed = zone.last_priority != Some(to_update);
+        let layout_metric = match zone_count {
+            1 => format!("Layout{}", to_update + 1),
+            _ => format!("Zone{}.Layout{}", zone_index + 1, to_update + 1),
         };
-        let screen_changed = to_update != ctx.last_priority;
-
-        let size = ctx.device.size(lua)?;
-        let memory_layout = ctx.device.memory_layout(lua)?;
-        env.set("SCREEN", size)?;
 
-        let output: LayoutData = ctx.layouts[to_update].layout.call(())?;
-        let (animation_state, image) = renderer.render(
-            &mut ctx.animation_groups[to_update],
-            screen_changed,
-            size,
-            output.widgets,
-            memory_layout,
-        );
+        let mut duration = Duration::ZERO;
+        let mut animation_state = None;
+        let mut dependencies = Dependencies::default();
+        for (index, target) in ctx.targets.iter_mut().enumerate() {
+            let size = zone.size(target.size);
+            env.set("SCREEN", size)?;
+
+            let begin = Instant::now();
+            let layout = &zone.layouts[to_update];
+            let output: LayoutData = match layout.track_dependencies {
+                true => {
+                    let (output, read): (LayoutData, _) =
+                        dependencies::track(lua, env, event_roots, &layout.layout)?;
+                    dependencies.extend(read);
+
+                    // Graphs with a source read their values after the layout function returns
+                    for widget in &output.widgets {
+                        if let Widget::Graph(graph) = widget
+                            && let Some(source) = &graph.source
+                        {
+                            dependencies.add_source(source.clone());
+                        }
+                    }
+                    output
+                }
+                false => layout.layout.call(())?,
+            };
+            ctx.timings.record(&layout_metric, begin.elapsed());
+
+            target.changed = true;
+            let memory_layout = target.memory_layout;
+            let target = &mut target.zones[zone_index];
+            let begin = Instant::now();
+            let (state, image) = renderer.render(
+                &mut zone.animation_groups[to_update],
+                screen_changed,
+                index == 0,
+                size,
+                output.widgets,
+                memory_layout,
+            );
+            ctx.timings.record("Render", begin.elapsed());
+
+            if screen_changed {
+                target.transition = match (layout.transition, target.last_frame.take()) {
+                    (Some(transition), Some(last_frame)) if transition.ticks > 0 => {
+                        Some(TransitionState::new(transition, last_frame, image.clone()))
+                    }
+                    _ => None,
+                };
+            }
 
-        ctx.device.update(lua, image)?;
+            let image = match &mut target.transition {
+                Some(transition) => {
+                    transition.set_target(image);
+                    let image = transition.step();
+                    if transition.is_finished() {
+                        target.transition = None;
+                    }
+                    image
+                }
+                None => image,
+            };
+            target.last_frame = Some(image);
+
+            duration = duration.max(output.duration);
+            animation_state = Some(match animation_state {
+                Some(animation_state) => Self::merge_states(animation_state, state),
+                None => state,
+            });
+        }
 
         if new_update {
-            ctx.time_remaining = output.duration;
+            zone.time_remaining = duration;
+        }
+        if zone.layouts[to_update].track_dependencies {
+            zone.dependencies[to_update] = Some(dependencies);
         }
-        ctx.last_priority = to_update;
-        ctx.state = animation_state;
+        zone.last_priority = Some(to_update);
+        zone.state = animation_state.unwrap_or(State::Finished);
 
         Ok(())
     }
 
-    fn make_sandbox(lua: &Lua) -> Table {
+    fn update_idle(
+        lua: &Lua,
+        ctx: &mut DeviceContext,
+        renderer: &mut Renderer,
+        env: &Table,
+    ) -> mlua::Result<()> {
+        let entered_idle = !ctx.idle;
+        ctx.idle = true;
+
+        if entered_idle {
+            // Make sure that the regular layouts are drawn from scratch after leaving idle state
+            ctx.reset_state();
+            for target in ctx.targets.iter_mut().flat_map(|target| &mut target.zones) {
+                target.transition = None;
+            }
+        }
+        for zone in &mut ctx.zones {
+            zone.layout_update_flags.fill(false);
+        }
+
+        let screensaver = match &ctx.screensaver {
+            Some(screensaver) => screensaver.clone(),
+            None => {
+                // Blank screen bypasses the protection, inverting it would defeat the purpose
+                if entered_idle {
+                    for output in &mut ctx.outputs {
+                        let target = &mut ctx.targets[output.target];
+                        target.last_frame = None;
+                        let blank = Buffer::new(target.size, target.memory_layout);
+                        output.device.update(lua, blank)?;
+                    }
+                }
+                return Ok(());
+            }
+        };
+
+        for index in 0..ctx.targets.len() {
+            let size = ctx.targets[index].size;
+            let memory_layout = ctx.targets[index].memory_layout;
+            env.set("SCREEN", size)?;
+
+            let begin = Instant::now();
+            let output: LayoutData = screensaver.call(())?;
+            ctx.timings.record("Screensaver", begin.elapsed());
+
+            let begin = Instant::now();
+            let (_, image) = renderer.render(
+                &mut ctx.screensaver_animation_groups,
+                entered_idle,
+                index == 0,
+                size,
+                output.widgets,
+                memory_layout,
+            );
+            ctx.timings.record("Render", begin.elapsed());
+            Self::present(lua, ctx, index, renderer, image)?;
+        }
+        Ok(())
+    }
+
+    // Redraws the last frame only if overlays or screen protection have changed
+    fn update_unchanged(
+        lua: &Lua,
+        ctx: &mut DeviceContext,
+        index: usize,
+        renderer: &mut Renderer,
+    ) -> mlua::Result<()> {
+        let DeviceContext {
+            outputs,
+            targets,
+            timings,
+            ..
+        } = &mut *ctx;
+        let target = &targets[index];
+        for output in outputs.iter_mut().filter(|output| output.target == index) {
+            let overlay_refresh = output.overlays.needs_refresh();
+            if !overlay_refresh && !output.protection.needs_refresh() {
+                continue;
+            }
+
+            let image = match &target.last_frame {
+                Some(last_frame) => last_frame.clone(),
+                None if overlay_refresh => Buffer::new(target.size, target.memory_layout),
+                None => continue,
+            };
+            Self::present_output(lua, output, renderer, timings, image)?;
+        }
+        Ok(())
+    }
+
+    fn present(
+        lua: &Lua,
+        ctx: &mut DeviceContext,
+        index: usize,
+        renderer: &mut Renderer,
+        image: Buffer,
+    ) -> mlua::Result<()> {
+        ctx.targets[index].last_frame = Some(image.clone());
+
+        for output in ctx
+            .outputs
+            .iter_mut()
+            .filter(|output| output.target == index)
+        {
+            Self::present_output(lua, output, renderer, &mut ctx.timings, image.clone())?;
+        }
+        Ok(())
+    }
+
+    fn present_output(
+        lua: &Lua,
+        output: &mut DeviceOutput,
+        renderer: &mut Renderer,
+        timings: &mut FrameTimings,
+        image: Buffer,
+    ) -> mlua::Result<()> {
+        let memory_layout = output.device.memory_layout(lua)?;
+
+        let begin = Instant::now();
+        let image = output.overlays.composite(renderer, image, memory_layout);
+        let image = output.protection.apply(image);
+        timings.record("Composite", begin.elapsed());
+
+        let begin = Instant::now();
+        let result = output.device.update(lua, image);
+        timings.record("Transfer", begin.elapsed());
+        result
+    }
+
+    fn make_sandbox(
+        lua: &Lua,
+        pending_overlays: &Rc<RefCell<Vec<OverlayData>>>,
+        profiler: &Profiler,
+        modules: &Rc<RefCell<Modules>>,
+    ) -> Table {
         let always_fn = lua.create_function(|_, _: ()| Ok(true)).unwrap();
 
         let never_fn = lua.create_function(|_, _: ()| Ok(false)).unwrap();
@@ -278,16 +1112,49 @@ impl ScriptHandler {
             })
             .unwrap();
 
+        let require_fn = Modules::create_require_fn(lua, modules);
+
+        let storage = StorageNamespace::new(GLOBAL_NAMESPACE.to_string());
+
+        // Overlays can be shown from within layout functions, while the script handler is borrowed
+        let show_overlay_fn = lua
+            .create_function({
+                let pending_overlays = Rc::clone(pending_overlays);
+                move |_, overlay: OverlayData| {
+                    pending_overlays.borrow_mut().push(overlay);
+                    Ok(())
+                }
+            })
+            .unwrap();
+
+        let toggle_overlay_fn = lua
+            .create_function({
+                let overlay = profiler.overlay_toggle();
+                move |_, _: ()| {
+                    overlay.set(!overlay.get());
+                    Ok(())
+                }
+            })
+            .unwrap();
+
         create_table_with_defaults!(lua, {
             Events = Events,
             Log = Log,
+            Overlay = {
+                show = $show_overlay_fn,
+            },
             PLATFORM = PLATFORM,
+            Profiler = {
+                toggle_overlay = $toggle_overlay_fn,
+            },
             Shortcuts = Shortcuts,
+            Storage = $storage,
             PREDICATE = {
                 Always = $always_fn,
                 Never = $never_fn,
                 Times = $times_fn,
-            }
+            },
+            require = $require_fn,
         })
     }
 }
@@ -296,8 +1163,11 @@ impl UserData for ScriptHandler {
     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
         methods.add_method_mut(
             "register",
-            |lua, handler, (device, layouts): (String, Vec<Layout>)| {
-                handler.register(lua, device, layouts)
+            |lua,
+             handler,
+             (device, layouts, screensaver): (String, Vec<Layout>, Option<Function>)| {
+                let zone = Zone::new(None, layouts, None, Rc::new(Cell::new(false)));
+                handler.register(lua, vec![device], zone, screensaver)
             },
         );
 
@@ -309,14 +1179,33 @@ impl UserData for ScriptHandler {
 }
 
 #[derive(FromLuaValue, Clone)]
-struct Layout {
+#[mlua(validate = Self::validate)]
+pub(crate) struct Layout {
+    #[mlua(lua_type = "fun(): LayoutData")]
     layout: Function,
+    #[mlua(lua_type = "fun(): boolean")]
     predicate: Option<Function>,
+    #[mlua(default)]
     run_on: Vec<EventKey>,
+    #[mlua(default = false)]
+    track_dependencies: bool,
+    transition: Option<Transition>,
+}
+
+impl Layout {
+    // Layouts without any trigger would never run
+    fn validate(layout: &Self) -> mlua::Result<()> {
+        if layout.run_on.is_empty() && !layout.track_dependencies {
+            return Err(mlua::Error::runtime(
+                "Layout has to set 'run_on' events, unless 'track_dependencies' is enabled",
+            ));
+        }
+        Ok(())
+    }
 }
 
 #[derive(FromLuaValue, Clone)]
-struct LayoutData {
+pub(crate) struct LayoutData {
     widgets: Vec<Widget>,
 
     #[mlua(transform = Self::transform_duration)]
@@ -342,28 +1231,51 @@ enum BuilderType {
 pub struct ScreenBuilder {
     layouts: Vec<Layout>,
     shortcut: Vec<String>,
-    device_name: String,
+    shortcut_previous: Vec<String>,
+    device_names: Vec<String>,
     builder_type: Option<BuilderType>,
     screen_count: usize,
     current_screen: Rc<RefCell<usize>>,
+    screen_changed: Rc<Cell<bool>>,
+    transition: Option<Transition>,
+    screensaver: Option<Function>,
+    carousel: Option<CarouselSettings>,
+    screen_predicates: Vec<Vec<Option<Function>>>,
+    zone: Option<Rectangle>,
 }
 
 impl ScreenBuilder {
-    pub fn new(name: String) -> Self {
+    pub fn new(names: Vec<String>) -> Self {
         Self {
             layouts: vec![],
             shortcut: vec![],
-            device_name: name,
+            shortcut_previous: vec![],
+            device_names: names,
             builder_type: None,
             screen_count: 0,
             current_screen: Rc::new(RefCell::new(0)),
+            screen_changed: Rc::new(Cell::new(false)),
+            transition: None,
+            screensaver: None,
+            carousel: None,
+            screen_predicates: vec![],
+            zone: None,
         }
     }
 }
 
 impl LuaTypeStaticMembers for ScreenBuilder {
     fn add_members(functions: &mut StaticMembers<'_>) {
-        functions.add_function("new", |_lua, name: String| Ok(Self::new(name)));
+        functions.add_function("new", |_lua, names: Either<String, Vec<String>>| {
+            let names = match names {
+                Either::Left(name) => vec![name],
+                Either::Right(names) if names.is_empty() => {
+                    return Err(mlua::Error::runtime("At least one device name is required"));
+                }
+                Either::Right(names) => names,
+            };
+            Ok(Self::new(names))
+        });
     }
 }
 
@@ -397,10 +1309,13 @@ impl UserData for ScreenBuilder {
             if layouts.len() == 0 {
                 warn!(
                     "Registering a layout group for device '{}' with 0 layouts",
-                    builder.device_name
+                    builder.device_names.join(", ")
                 );
             }
 
+            let predicates = layouts.iter().map(|x| x.predicate.clone()).collect();
+            builder.screen_predicates.push(predicates);
+
             for mut layout in layouts {
                 let current_screen = builder.current_screen.clone();
                 let predicate = layout.predicate;
@@ -443,25 +1358,94 @@ impl UserData for ScreenBuilder {
             },
         );
 
+        methods.add_method_mut(
+            "with_layout_group_previous",
+            |_lua, builder, keys: Vec<String>| {
+                if let Some(BuilderType::Layout) = builder.builder_type {
+                    return Err(mlua::Error::RuntimeError(
+                        "Can't use 'with_layout_group_previous' after calling 'with_layout'."
+                            .to_string(),
+                    ));
+                }
+                builder.builder_type = Some(BuilderType::LayoutGroup);
+
+                builder.shortcut_previous = keys;
+
+                Ok(builder.clone())
+            },
+        );
+
+        methods.add_method_mut(
+            "with_carousel",
+            |_lua, builder, settings: CarouselSettings| {
+                if let Some(BuilderType::Layout) = builder.builder_type {
+                    return Err(mlua::Error::RuntimeError(
+                        "Can't use 'with_carousel' after calling 'with_layout'.".to_string(),
+                    ));
+                }
+                builder.builder_type = Some(BuilderType::LayoutGroup);
+
+                builder.carousel = Some(settings);
+
+                Ok(builder.clone())
+            },
+        );
+
+        methods.add_method_mut("with_zone", |_lua, builder, area: Rectangle| {
+            if area.size.width == 0 || area.size.height == 0 {
+                return Err(mlua::Error::runtime(
+                    "Zone size has to be greater than zero",
+                ));
+            }
+
+            builder.zone = Some(area);
+
+            Ok(builder.clone())
+        });
+
+        methods.add_method_mut(
+            "with_transition",
+            |_lua, builder, transition: Transition| {
+                builder.transition = Some(transition);
+
+                Ok(builder.clone())
+            },
+        );
+
+        methods.add_method_mut(
+            "with_screensaver",
+            |_lua, builder, screensaver: Function| {
+                builder.screensaver = Some(screensaver);
+
+                Ok(builder.clone())
+            },
+        );
+
         methods.add_method_mut("register", |lua, builder, _: ()| {
-            if !builder.shortcut.is_empty() {
+            let screens = Screens {
+                device_name: builder.device_names[0].clone(),
+                count: builder.screen_count,
+                current: builder.current_screen.clone(),
+                changed: builder.screen_changed.clone(),
+            };
+
+            let navigation: [(&Vec<String>, Navigate); 2] = [
+                (&builder.shortcut, Screens::next),
+                (&builder.shortcut_previous, Screens::previous),
+            ];
+            for (keys, navigate) in navigation {
+                if keys.is_empty() {
+                    continue;
+                }
+
                 if builder.screen_count < 2 {
-                    warn!("Registering shortcut to toggle screens for device '{}', but its screen count is {}", builder.device_name, builder.screen_count);
+                    warn!("Registering shortcut to toggle screens for device '{}', but its screen count is {}", builder.device_names.join(", "), builder.screen_count);
                 }
 
-                let current = builder.current_screen.clone();
-                let count = builder.screen_count;
-                let name = builder.device_name.clone();
+                let screens = screens.clone();
                 let toggle_screen = lua
-                    .create_function_mut(move |lua, _: ()| {
-                        *current.borrow_mut() += 1;
-                        if *current.borrow() >= count {
-                            *current.borrow_mut() = 0;
-                        }
-
-                        let mut script_handler = UserDataRef::<ScriptHandler>::load(lua);
-                        script_handler.get_mut().reset(&name);
-
+                    .create_function(move |_lua, _: ()| {
+                        navigate(&screens);
                         Ok(())
                     })
                     .unwrap();
@@ -469,31 +1453,126 @@ impl UserData for ScreenBuilder {
                 let mut shortcuts = UserDataRef::<Shortcuts>::load(lua);
                 shortcuts
                     .get_mut()
-                    .register(lua, builder.shortcut.clone(), toggle_screen)?;
+                    .register(lua, keys.clone(), toggle_screen)?;
             }
 
             if builder.screen_count == 0 {
                 warn!(
                     "Registering device '{}' with zero screens provided",
-                    builder.device_name
+                    builder.device_names.join(", ")
                 );
             }
 
+            let carousel = builder.carousel.clone().map(|settings| {
+                if builder.screen_count < 2 {
+                    warn!(
+                        "Registering a carousel for device '{}', but its screen count is {}",
+                        builder.device_names.join(", "),
+                        builder.screen_count
+                    );
+                }
+
+                Carousel::new(
+                    settings,
+                    builder.current_screen.clone(),
+                    builder.screen_predicates.clone(),
+                )
+            });
+
+            let mut layouts = builder.layouts.clone();
+            for layout in &mut layouts {
+                layout.transition = layout.transition.or(builder.transition);
+            }
+
+            let zone = Zone::new(
+                builder.zone,
+                layouts,
+                carousel,
+                builder.screen_changed.clone(),
+            );
+
             let mut script_handler = UserDataRef::<ScriptHandler>::load(lua);
             script_handler.get_mut().register(
                 lua,
-                builder.device_name.clone(),
-                builder.layouts.clone(),
+                builder.device_names.clone(),
+                zone,
+                builder.screensaver.clone(),
             )?;
 
+            Ok(screens)
+        });
+    }
+}
+
+type Navigate = fn(&Screens);
+
+#[derive(Clone, LuaName)]
+pub struct Screens {
+    device_name: String,
+    count: usize,
+    current: Rc<RefCell<usize>>,
+    changed: Rc<Cell<bool>>,
+}
+
+impl Screens {
+    fn select(&self, screen: usize) {
+        *self.current.borrow_mut() = screen;
+        self.changed.set(true);
+    }
+
+    fn next(&self) {
+        if self.count > 0 {
+            let current = *self.current.borrow();
+            self.select((current + 1) % self.count);
+        }
+    }
+
+    fn previous(&self) {
+        if self.count > 0 {
+            let current = *self.current.borrow();
+            self.select((current + self.count - 1) % self.count);
+        }
+    }
+}
+
+impl UserData for Screens {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("next", |_lua, screens, _: ()| {
+            screens.next();
             Ok(())
         });
+
+        methods.add_method("previous", |_lua, screens, _: ()| {
+            screens.previous();
+            Ok(())
+        });
+
+        // Screens are numbered from 1 in scripts, same as lua tables
+        methods.add_method("select", |_lua, screens, screen: usize| {
+            if screen == 0 || screen > screens.count {
+                return Err(mlua::Error::runtime(format!(
+                    "Screen {} is out of range, device '{}' has {} screens",
+                    screen, screens.device_name, screens.count
+                )));
+            }
+
+            screens.select(screen - 1);
+            Ok(())
+        });
+
+        methods.add_method("current", |_lua, screens, _: ()| {
+            Ok(*screens.current.borrow() + 1)
+        });
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::devices::virtual_device::virtual_device::{
+        add_virtual_device, load_virtual_devices,
+    };
+    use crate::renderer::bitmap_font::TEST_FONT;
     use crate::{create_table, events::cbor_to_lua::cbor_to_lua_value};
     use ciborium::Value as CborValue;
     use serde::{Deserialize, Serialize, de::DeserializeOwned};
@@ -730,4 +1809,410 @@ mod tests {
         };
         assert_tables_equal(&lua, &expected, &env, line!());
     }
+
+    // Functions shared by the scripts of the tests below
+    const TEST_PRELUDE: &str = r#"
+        -- Layout showing the widgets returned by `widgets`, run on 'TEST' unless `run_on` is given
+        function test_layout(widgets, run_on)
+            return {
+                layout = function()
+                    return { widgets = widgets(), duration = Duration.from_millis(100) }
+                end,
+                run_on = run_on or { 'TEST' },
+            }
+        end
+
+        -- Bar covering the whole screen, filled up to `value` percent
+        function fill(value)
+            return Widget.Bar { value = value, position = { x = 0, y = 0 }, size = SCREEN }
+        end
+    "#;
+
+    fn load_script(lua: &Lua, script: &str) {
+        ScriptHandler::load(lua, format!("{TEST_PRELUDE}{script}"), std::env::temp_dir()).unwrap();
+    }
+
+    fn update_once(lua: &Lua, event: &str) {
+        let mut handler = UserDataRef::<ScriptHandler>::load(lua);
+        handler.get_mut().mark_for_update(&event.to_string());
+        handler
+            .get_mut()
+            .update(lua, Duration::from_millis(100))
+            .unwrap();
+    }
+
+    fn size(width: usize, height: usize) -> Size {
+        Size { width, height }
+    }
+
+    fn row(buffer: &Buffer, y: usize) -> Vec<bool> {
+        (0..buffer.width()).map(|x| buffer.get(x, y)).collect()
+    }
+
+    #[test]
+    fn mirrored_devices() {
+        let lua = Lua::new();
+        let devices = load_virtual_devices(
+            &lua,
+            [("A", size(16, 2)), ("B", size(16, 2)), ("C", size(8, 2))],
+        );
+        load_script(
+            &lua,
+            r#"
+            CALLS = 0
+            ScreenBuilder.new({ 'A', 'B', 'C', 'A' })
+                :with_layout(test_layout(function()
+                    CALLS = CALLS + 1
+                    local size = { width = SCREEN.Width / 2, height = 1 }
+                    return { Widget.Bar { value = 100, position = { x = 0, y = 0 }, size = size } }
+                end))
+                :register()
+            "#,
+        );
+
+        update_once(&lua, "TEST");
+
+        // Layout is called once per distinct size, devices of the same size share the frame
+        let [a, b, c] = devices.map(|frame| frame.borrow_mut().take().unwrap());
+        assert_eq!(row(&a, 0), row(&b, 0));
+        assert_eq!(row(&a, 0), [vec![true; 8], vec![false; 8]].concat());
+        assert_eq!(row(&c, 0), [vec![true; 4], vec![false; 4]].concat());
+
+        let handler = UserDataRef::<ScriptHandler>::load(&lua);
+        assert_eq!(handler.get().environment.get::<usize>("CALLS").unwrap(), 2);
+        assert_eq!(handler.get().devices.len(), 1);
+        assert_eq!(handler.get().devices[0].targets.len(), 2);
+    }
+
+    #[test]
+    fn reopen_closed_device() {
+        let lua = Lua::new();
+        load_virtual_devices(&lua, [("A", size(8, 1))]);
+        load_script(
+            &lua,
+            "ScreenBuilder.new('A'):with_layout(test_layout(function() return { fill(100) } end)):register()",
+        );
+
+        // Virtual devices aren't part of the configuration, so reloading it removes the device
+        let changed = Devices::reload_config(&lua, String::new()).unwrap();
+        assert_eq!(changed, ["A"]);
+
+        let mut handler = UserDataRef::<ScriptHandler>::load(&lua);
+        let err = handler
+            .get_mut()
+            .reopen_devices(&lua, &changed)
+            .unwrap_err();
+        assert!(err.to_string().contains("Failed to reopen device 'A'"));
+        assert_eq!(handler.get().devices.len(), 1);
+        assert_eq!(handler.get().devices[0].closed, ["A"]);
+
+        update_once(&lua, "TEST");
+
+        // Closed devices are retried on any reload, keeping the layouts registered for them
+        let frame = add_virtual_device(&lua, "A", size(8, 1));
+        handler.get_mut().reopen_devices(&lua, &[]).unwrap();
+        assert!(handler.get().devices[0].closed.is_empty());
+
+        update_once(&lua, "TEST");
+
+        let buffer = frame.borrow_mut().take().unwrap();
+        assert_eq!(row(&buffer, 0), vec![true; 8]);
+    }
+
+    #[test]
+    fn mirrored_devices_scroll_in_sync() {
+        let lua = Lua::new();
+        let devices = load_virtual_devices(&lua, [("A", size(16, 8)), ("B", size(24, 8))]);
+        load_script(
+            &lua,
+            r#"
+            ScreenBuilder.new({ 'A', 'B' })
+                :with_layout(test_layout(function()
+                    return {
+                        Widget.Text {
+                            text = 'SCROLLING TEXT THAT DOES NOT FIT',
+                            scrolling = true,
+                            position = { x = 0, y = 0 },
+                            size = SCREEN,
+                        },
+                    }
+                end))
+                :register()
+            "#,
+        );
+
+        let shared_area = |buffer: &Buffer| -> Vec<bool> {
+            (0..8)
+                .flat_map(|y| (0..16).map(move |x| (x, y)))
+                .map(|(x, y)| buffer.get(x, y))
+                .collect()
+        };
+        let mut first = None;
+        let mut scrolled = false;
+        for _ in 0..200 {
+            update_once(&lua, "TEST");
+
+            // Devices differ in size, but both show the text scrolled by the same amount
+            let [a, b] = devices
+                .each_ref()
+                .map(|frame| frame.borrow_mut().take().unwrap());
+            assert_eq!(shared_area(&a), shared_area(&b));
+
+            let first = first.get_or_insert_with(|| shared_area(&a));
+            scrolled |= *first != shared_area(&a);
+        }
+        assert!(scrolled);
+
+        let handler = UserDataRef::<ScriptHandler>::load(&lua);
+        assert_eq!(handler.get().devices[0].targets.len(), 2);
+    }
+
+    #[test]
+    fn mirrored_devices_bitmap_font() {
+        let path = std::env::temp_dir().join("omni-led-mirrored-bitmap-font.bdf");
+        std::fs::write(&path, TEST_FONT).unwrap();
+
+        let lua = Lua::new();
+        let [a, b] = load_virtual_devices(&lua, [("A", size(16, 9)), ("B", size(24, 9))]);
+        let script = format!(
+            r#"
+            ScreenBuilder.new({{ 'A', 'B' }})
+                :with_layout(test_layout(function()
+                    return {{
+                        Widget.Text {{
+                            text = 'jé',
+                            font = FontSelector.Bitmap({{ path = '{}' }}),
+                            position = {{ x = 0, y = 0 }},
+                            size = SCREEN,
+                        }},
+                    }}
+                end))
+                :register()
+            "#,
+            path.display()
+        );
+        load_script(&lua, &script);
+
+        update_once(&lua, "TEST");
+
+        // Glyphs of bitmap fonts have a fixed size, so both devices show exactly the same pixels
+        let (a, b) = (a.take().unwrap(), b.take().unwrap());
+        let rows = |buffer: &Buffer| -> Vec<Vec<bool>> {
+            (0..9)
+                .map(|y| row(buffer, y).into_iter().take(16).collect())
+                .collect()
+        };
+        assert_eq!(rows(&a), rows(&b));
+        assert!(rows(&a).iter().flatten().any(|&pixel| pixel));
+        assert!((16..24).all(|x| (0..9).all(|y| !b.get(x, y))));
+    }
+
+    #[test]
+    fn reopen_devices_resets_animations() {
+        let lua = Lua::new();
+        load_virtual_devices(&lua, [("A", size(16, 8))]);
+        load_script(
+            &lua,
+            r#"
+            ScreenBuilder.new('A')
+                :with_layout(test_layout(function()
+                    return {
+                        Widget.Text {
+                            text = 'SCROLLING TEXT THAT DOES NOT FIT',
+                            scrolling = true,
+                            position = { x = 0, y = 0 },
+                            size = SCREEN,
+                        },
+                    }
+                end))
+                :register()
+            "#,
+        );
+
+        update_once(&lua, "TEST");
+
+        let mut handler = UserDataRef::<ScriptHandler>::load(&lua);
+        assert!(!handler.get().devices[0].zones[0].animation_groups[0].is_empty());
+
+        // Scrolling steps were computed for the old screen size
+        handler
+            .get_mut()
+            .reopen_devices(&lua, &[String::from("A")])
+            .unwrap();
+        assert!(handler.get().devices[0].zones[0].animation_groups[0].is_empty());
+    }
+
+    #[test]
+    fn tracked_graph_source() {
+        let lua = Lua::new();
+        let [frame] = load_virtual_devices(&lua, [("A", size(4, 4))]);
+        load_script(
+            &lua,
+            r#"
+            CALLS = 0
+            local layout = test_layout(function()
+                CALLS = CALLS + 1
+                return {
+                    Widget.Graph {
+                        source = 'SYSTEM.Load',
+                        style = GraphStyle.Bars,
+                        position = { x = 0, y = 0 },
+                        size = SCREEN,
+                    },
+                }
+            end, {})
+            layout.track_dependencies = true
+            ScreenBuilder.new('A'):with_layout(layout):register()
+            "#,
+        );
+
+        // Same as the event handler, without going through the event queue
+        let mut handler = UserDataRef::<ScriptHandler>::load(&lua);
+        let mut send = |key: &str, load: usize| {
+            let system = lua.create_table().unwrap();
+            system.set("Load", load).unwrap();
+            handler.get().environment.set("SYSTEM", system).unwrap();
+            handler
+                .get_mut()
+                .renderer
+                .graph_history()
+                .mark_for_sampling(key);
+            update_once(&lua, key);
+        };
+
+        // Graph source is known only after the first run, so the first event isn't sampled
+        send("SYSTEM.Load", 100);
+        send("SYSTEM.Load", 100);
+        send("SYSTEM.Load", 100);
+        send("OTHER", 100);
+
+        // Layout doesn't read `SYSTEM` itself, but runs again for every new sample of its graph
+        assert_eq!(handler.get().environment.get::<usize>("CALLS").unwrap(), 3);
+        let buffer = frame.borrow_mut().take().unwrap();
+        assert_eq!(row(&buffer, 0), [false, false, true, true]);
+    }
+
+    #[test]
+    fn zones() {
+        let lua = Lua::new();
+        let [frame] = load_virtual_devices(&lua, [("A", size(16, 4))]);
+        load_script(
+            &lua,
+            r#"
+            local function full() return { fill(100) } end
+
+            ScreenBuilder.new('A')
+                :with_zone({ position = { x = 0, y = 0 }, size = { width = 16, height = 1 } })
+                :with_layout(test_layout(full, { 'STATUS' }))
+                :register()
+
+            ScreenBuilder.new('A')
+                :with_zone({ position = { x = 12, y = 2 }, size = { width = 4, height = 2 } })
+                :with_layout(test_layout(full, { 'MAIN' }))
+                :register()
+            "#,
+        );
+
+        update_once(&lua, "STATUS");
+
+        let buffer = frame.borrow_mut().take().unwrap();
+        assert_eq!(row(&buffer, 0), vec![true; 16]);
+        assert_eq!(row(&buffer, 2), vec![false; 16]);
+
+        // Zones keep their content when others are updated
+        update_once(&lua, "MAIN");
+
+        let buffer = frame.borrow_mut().take().unwrap();
+        assert_eq!(row(&buffer, 0), vec![true; 16]);
+        assert_eq!(row(&buffer, 1), vec![false; 16]);
+        assert_eq!(row(&buffer, 3), [vec![false; 12], vec![true; 4]].concat());
+
+        let handler = UserDataRef::<ScriptHandler>::load(&lua);
+        assert_eq!(handler.get().devices.len(), 1);
+
+        // Zones have to fit on the screen
+        let env = handler.get().environment.clone();
+        let result = lua
+            .load(
+                "ScreenBuilder.new('A')
+                    :with_zone({ position = { x = 12, y = 0 }, size = { width = 8, height = 1 } })
+                    :with_layout(test_layout(function() return {} end))
+                    :register()",
+            )
+            .set_environment(env)
+            .exec();
+        assert!(result.unwrap_err().to_string().contains("doesn't fit"));
+        assert_eq!(handler.get().devices[0].zones.len(), 2);
+    }
+
+    #[test]
+    fn require_in_layout() {
+        let root = std::env::temp_dir().join("omni-led-require-in-layout");
+        _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("sizes.lua"), "return { full = SCREEN }").unwrap();
+
+        let lua = Lua::new();
+        let [frame] = load_virtual_devices(&lua, [("A", size(16, 1))]);
+        let script = r#"
+            ScreenBuilder.new('A')
+                :with_layout(test_layout(function()
+                    local sizes = require('sizes')
+                    return { Widget.Bar { value = 100, position = { x = 0, y = 0 }, size = sizes.full } }
+                end))
+                :register()
+        "#;
+        ScriptHandler::load(&lua, format!("{TEST_PRELUDE}{script}"), root).unwrap();
+
+        // Layouts run while the script handler is borrowed
+        update_once(&lua, "TEST");
+
+        let buffer = frame.borrow_mut().take().unwrap();
+        assert_eq!(row(&buffer, 0), vec![true; 16]);
+    }
+
+    #[test]
+    fn select_screen_in_layout() {
+        let lua = Lua::new();
+        let [frame] = load_virtual_devices(&lua, [("A", size(16, 1))]);
+        load_script(
+            &lua,
+            r#"
+            local screens
+            screens = ScreenBuilder.new('A')
+                :with_layout_group({ test_layout(function()
+                    screens:select(2)
+                    return { fill(50) }
+                end) })
+                :with_layout_group({ test_layout(function() return { fill(100) } end) })
+                :register()
+            "#,
+        );
+
+        update_once(&lua, "TEST");
+
+        let buffer = frame.borrow_mut().take().unwrap();
+        assert_eq!(row(&buffer, 0), [vec![true; 8], vec![false; 8]].concat());
+
+        // Selection is applied on the next update, after the first screen's layout has finished
+        update_once(&lua, "TEST");
+
+        let buffer = frame.borrow_mut().take().unwrap();
+        assert_eq!(row(&buffer, 0), vec![true; 16]);
+    }
+
+    #[test]
+    fn layout_without_trigger() {
+        let lua = Lua::new();
+        load_virtual_devices(&lua, [("A", size(16, 1))]);
+
+        let result = ScriptHandler::load(
+            &lua,
+            String::from(
+                "ScreenBuilder.new('A'):with_layout({ layout = function() return { widgets = {} } end }):register()",
+            ),
+            std::env::temp_dir(),
+        );
+        assert!(result.unwrap_err().to_string().contains("'run_on'"));
+    }
 }
@@ -0,0 +1,247 @@
+use log::{debug, error};
+use mlua::{Lua, UserData, UserDataMethods, Value};
+use omni_led_derive::LuaName;
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::common::encoding::{json_value_to_lua, lua_to_json_value};
+use crate::common::user_data::{UserDataRef, set_unique_user_data};
+use crate::constants::constants::Constants;
+
+const STORAGE_FILE: &str = "storage.json";
+const WRITE_DELAY: Duration = Duration::from_secs(1);
+
+pub const GLOBAL_NAMESPACE: &str = "global";
+
+type Namespaces = BTreeMap<String, BTreeMap<String, JsonValue>>;
+
+#[derive(LuaName)]
+pub struct Storage {
+    path: PathBuf,
+    namespaces: Namespaces,
+    modified: Option<Instant>,
+}
+
+impl Storage {
+    pub fn load(lua: &Lua) {
+        let path = Constants::data_dir().join(STORAGE_FILE);
+        set_unique_user_data(lua, Self::new(path));
+    }
+
+    pub fn new(path: PathBuf) -> Self {
+        let namespaces = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+                error!("Failed to parse '{}': {}", path.display(), err);
+                Namespaces::new()
+            }),
+            Err(_) => Namespaces::new(),
+        };
+
+        Self {
+            path,
+            namespaces,
+            modified: None,
+        }
+    }
+
+    // Writes are debounced, so that frequent changes don't hit the disk every update
+    pub fn update(&mut self) {
+        if self
+            .modified
+            .is_some_and(|modified| modified.elapsed() >= WRITE_DELAY)
+        {
+            self.flush();
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if self.modified.take().is_none() {
+            return;
+        }
+
+        match self.write() {
+            Ok(()) => debug!("Saved storage to '{}'", self.path.display()),
+            Err(err) => error!(
+                "Failed to save storage to '{}': {}",
+                self.path.display(),
+                err
+            ),
+        }
+    }
+
+    fn write(&self) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Write to a temporary file first, so that a crash mid-write doesn't corrupt stored data
+        let temp_path = self.path.with_extension("json.tmp");
+        let content = serde_json::to_string_pretty(&self.namespaces)?;
+        std::fs::write(&temp_path, content)?;
+        std::fs::rename(&temp_path, &self.path)
+    }
+
+    fn get(&self, namespace: &str, key: &str) -> Option<&JsonValue> {
+        self.namespaces.get(namespace)?.get(key)
+    }
+
+    fn set(&mut self, namespace: &str, key: String, value: JsonValue) {
+        let entries = self.namespaces.entry(namespace.to_string()).or_default();
+        match value {
+            JsonValue::Null => _ = entries.remove(&key),
+            value => _ = entries.insert(key, value),
+        }
+
+        if entries.is_empty() {
+            self.namespaces.remove(namespace);
+        }
+        self.modified = Some(Instant::now());
+    }
+}
+
+impl UserData for Storage {}
+
+#[derive(Clone, LuaName)]
+pub struct StorageNamespace {
+    name: String,
+}
+
+impl StorageNamespace {
+    pub fn new(name: String) -> Self {
+        Self { name }
+    }
+}
+
+impl UserData for StorageNamespace {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("get", |lua, namespace, key: String| {
+            let storage = UserDataRef::<Storage>::load(lua);
+            let storage = storage.get();
+            match storage.get(&namespace.name, &key) {
+                Some(value) => json_value_to_lua(lua, value.clone()),
+                None => Ok(Value::Nil),
+            }
+        });
+
+        methods.add_method("set", |lua, namespace, (key, value): (String, Value)| {
+            let value = lua_to_json_value(value)?;
+            let mut storage = UserDataRef::<Storage>::load(lua);
+            storage.get_mut().set(&namespace.name, key, value);
+            Ok(())
+        });
+
+        methods.add_method("delete", |lua, namespace, key: String| {
+            let mut storage = UserDataRef::<Storage>::load(lua);
+            storage.get_mut().set(&namespace.name, key, JsonValue::Null);
+            Ok(())
+        });
+
+        methods.add_method("namespace", |_lua, _namespace, name: String| {
+            Ok(StorageNamespace::new(name))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::cbor_to_lua::get_cleanup_entries_metatable;
+    use mlua::Table;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("omni-led-storage-{name}"));
+        _ = std::fs::remove_dir_all(&dir);
+        dir.join(STORAGE_FILE)
+    }
+
+    #[test]
+    fn round_trip() {
+        let lua = Lua::new();
+        let value: Value = lua
+            .load(
+                "return { count = 3, ratio = 0.5, name = 'test', list = { 1, 2, 3 }, empty = {} }",
+            )
+            .eval()
+            .unwrap();
+
+        // Empty tables can't be told apart from empty arrays, so they are stored as arrays
+        let json = lua_to_json_value(value).unwrap();
+        assert_eq!(json["empty"], JsonValue::Array(vec![]));
+
+        let value = json_value_to_lua(&lua, json.clone()).unwrap();
+        let table = value.as_table().unwrap();
+        assert_eq!(table.get::<i64>("count").unwrap(), 3);
+        assert_eq!(table.get::<f64>("ratio").unwrap(), 0.5);
+        assert_eq!(table.get::<String>("name").unwrap(), "test");
+        assert_eq!(table.get::<Vec<i64>>("list").unwrap(), vec![1, 2, 3]);
+        assert!(table.get::<Table>("empty").unwrap().is_empty());
+
+        // Loaded objects are marked as maps, so storing them again doesn't change them
+        assert!(get_cleanup_entries_metatable(table).unwrap().is_some());
+        assert_eq!(lua_to_json_value(value).unwrap(), json);
+
+        let empty_object = json_value_to_lua(&lua, JsonValue::Object(Default::default())).unwrap();
+        assert_eq!(
+            lua_to_json_value(empty_object).unwrap(),
+            JsonValue::Object(Default::default())
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_values() {
+        let lua = Lua::new();
+        for code in [
+            "return function() end",
+            "return { [true] = 1 }",
+            "local t = {}; t.t = t; return t",
+            "return 0 / 0",
+            "return { ratio = 1 / 0 }",
+            "return { -1 / 0 }",
+            "return '\\xff'",
+        ] {
+            let value: Value = lua.load(code).eval().unwrap();
+            assert!(lua_to_json_value(value).is_err(), "Accepted '{code}'");
+        }
+    }
+
+    #[test]
+    fn persists_namespaces() {
+        let path = temp_path("persist");
+
+        let mut storage = Storage::new(path.clone());
+        storage.set(GLOBAL_NAMESPACE, "key".to_string(), JsonValue::from(1));
+        storage.set("device", "key".to_string(), JsonValue::from(2));
+        storage.set("removed", "key".to_string(), JsonValue::from(3));
+        storage.set("removed", "key".to_string(), JsonValue::Null);
+        storage.flush();
+
+        let storage = Storage::new(path);
+        assert_eq!(
+            storage.get(GLOBAL_NAMESPACE, "key"),
+            Some(&JsonValue::from(1))
+        );
+        assert_eq!(storage.get("device", "key"), Some(&JsonValue::from(2)));
+        assert_eq!(storage.get("removed", "key"), None);
+        assert!(!storage.namespaces.contains_key("removed"));
+    }
+
+    #[test]
+    fn debounces_writes() {
+        let path = temp_path("debounce");
+
+        let mut storage = Storage::new(path.clone());
+        storage.set(GLOBAL_NAMESPACE, "key".to_string(), JsonValue::from(1));
+        storage.modified = Some(Instant::now() - WRITE_DELAY);
+
+        // Every change delays the write again
+        storage.set(GLOBAL_NAMESPACE, "key".to_string(), JsonValue::from(2));
+        storage.update();
+        assert!(!path.exists());
+
+        storage.modified = Some(Instant::now() - WRITE_DELAY);
+        storage.update();
+        assert!(path.exists());
+    }
+}
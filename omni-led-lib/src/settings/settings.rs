@@ -1,6 +1,9 @@
 use log::debug;
 use mlua::{Lua, UserData, chunk};
 use omni_led_derive::FromLuaValue;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 use std::time::Duration;
 
 use crate::common::lua_traits::LuaName;
@@ -9,6 +12,8 @@ use crate::constants::config::{ConfigType, load_config};
 use crate::create_table_with_defaults;
 use crate::logging::logger::{LevelFilter, Log};
 use crate::renderer::font_selector::FontSelector;
+use crate::renderer::screen_protection::ScreenProtectionSettings;
+use crate::script_handler::profiler::ProfilingSettings;
 use crate::script_handler::script_data_types::DurationWrapper;
 
 #[derive(Debug, Clone, FromLuaValue)]
@@ -22,6 +27,9 @@ pub struct Settings {
     #[mlua(default = FontSelector::Default)]
     pub font: FontSelector,
 
+    #[mlua(default)]
+    pub fonts: HashMap<String, FontSelector>,
+
     #[mlua(default = LevelFilter::Info)]
     pub log_level: LevelFilter,
 
@@ -31,27 +39,66 @@ pub struct Settings {
     #[mlua(default = 2)]
     pub keyboard_ticks_repeat_rate: usize,
 
+    #[mlua(default)]
+    pub profiling: ProfilingSettings,
+
+    #[mlua(default)]
+    pub screen_protection: HashMap<String, ScreenProtectionSettings>,
+
     #[mlua(transform = DurationWrapper::transform)]
     #[mlua(default = Duration::from_millis(100))]
     pub update_interval: Duration,
+
+    #[mlua(default = false)]
+    pub watch_config: bool,
 }
 
 impl Settings {
     pub fn load(lua: &Lua, config: String) {
-        let load_settings_fn = lua
-            .create_function(move |lua, settings: Settings| {
-                set_unique_user_data(lua, settings);
+        let settings = Self::evaluate(lua, config).unwrap();
+        Self::load_evaluated(lua, settings);
+    }
+
+    // Same as `load`, for settings evaluated separately to handle errors in the config
+    pub fn load_evaluated(lua: &Lua, settings: Settings) {
+        set_unique_user_data(lua, settings);
+
+        Self::apply(lua);
+    }
+
+    pub fn reload(lua: &Lua, config: String) -> mlua::Result<()> {
+        debug!("Reloading settings");
+
+        let settings = Self::evaluate(lua, config)?;
+        *UserDataRef::<Settings>::load(lua).get_mut() = settings;
+
+        Self::apply(lua);
+        Ok(())
+    }
+
+    pub fn evaluate(lua: &Lua, config: String) -> mlua::Result<Settings> {
+        let result = Rc::new(RefCell::new(None));
+        let load_settings_fn = lua.create_function({
+            let result = Rc::clone(&result);
+            move |_, settings: Settings| {
+                *result.borrow_mut() = Some(settings);
                 Ok(())
-            })
-            .unwrap();
+            }
+        })?;
 
         let env = create_table_with_defaults!(lua, {
             Log = Log,
             PLATFORM = PLATFORM,
             Settings = $load_settings_fn,
         });
-        load_config(lua, ConfigType::Settings, &config, env).unwrap();
+        load_config(lua, ConfigType::Settings, &config, env)?;
+
+        result
+            .take()
+            .ok_or_else(|| mlua::Error::runtime("Settings were not set in the config"))
+    }
 
+    fn apply(lua: &Lua) {
         let settings = UserDataRef::<Settings>::load(lua);
         let logger = UserDataRef::<Log>::load(lua);
         logger.get().set_level_filter(settings.get().log_level);
@@ -8,6 +8,9 @@ use crate::semaphore::semaphore::BinarySemaphore;
 use crate::ui::event::Event;
 use crate::ui::handler::{HandlerProxy, PROXY};
 
+// Emulator windows receive RGB888 data
+const BYTES_PER_PIXEL: usize = 3;
+
 pub struct Window {
     proxy: HandlerProxy,
     id: Arc<AtomicU64>,
@@ -119,12 +122,13 @@ impl WindowHandle {
                 let src_x = ((dst_x * x_ratio) >> 16).min(src_width - 1);
                 let src_y = ((dst_y * y_ratio) >> 16).min(src_height - 1);
 
-                let src_idx = src_y * src_width + src_x;
+                let src_idx = (src_y * src_width + src_x) * BYTES_PER_PIXEL;
                 let dst_idx = dst_y * dst_width + dst_x;
 
-                dst_buffer[dst_idx] = match src_buffer[src_idx] {
-                    0 => 0,
-                    _ => 0xFFFFFFFF,
+                dst_buffer[dst_idx] = match &src_buffer[src_idx..src_idx + BYTES_PER_PIXEL] {
+                    [0, 0, 0] => 0,
+                    [r, g, b] => 0xFF000000 | (*r as u32) << 16 | (*g as u32) << 8 | *b as u32,
+                    _ => unreachable!(),
                 }
             }
         }
@@ -138,7 +142,7 @@ struct DrawBuffer {
 impl DrawBuffer {
     fn with_size(size: Size) -> Self {
         Self {
-            data: UnsafeCell::new(vec![0; size.width * size.height]),
+            data: UnsafeCell::new(vec![0; size.width * size.height * BYTES_PER_PIXEL]),
         }
     }
 }
@@ -0,0 +1,41 @@
+use clap::Args;
+use mlua::Lua;
+use omni_led_lib::{common::type_definitions::generate, constants::constants::Constants};
+use std::path::PathBuf;
+
+use crate::render::read_events;
+
+const TYPES_FILE: &str = "omni_led_types.lua";
+
+#[derive(Args)]
+pub struct GenTypesOptions {
+    /// Output file, defaults to omni_led_types.lua in the config directory
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// Sample event data in JSON or CBOR format, used to add types of plugin events
+    #[clap(short, long)]
+    events: Option<PathBuf>,
+}
+
+pub fn run(options: GenTypesOptions) -> Result<(), String> {
+    let events = match &options.events {
+        Some(path) => read_events(path)?,
+        None => Vec::new(),
+    };
+
+    let lua = Lua::new();
+    let definitions = generate(&lua, &events).map_err(|err| err.to_string())?;
+
+    let output = options
+        .output
+        .unwrap_or_else(|| Constants::config_dir().join(TYPES_FILE));
+    if let Some(dir) = output.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    std::fs::write(&output, definitions)
+        .map_err(|err| format!("Failed to write '{}': {}", output.display(), err))?;
+
+    println!("Type definitions written to '{}'", output.display());
+    Ok(())
+}
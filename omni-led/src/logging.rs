@@ -1,4 +1,6 @@
 use log::{LevelFilter, error};
+use log4rs::append::Append;
+use log4rs::append::console::{ConsoleAppender, Target};
 use log4rs::append::file::FileAppender;
 use log4rs::config::runtime::ConfigBuilder;
 use log4rs::config::{Appender, Root};
@@ -8,14 +10,16 @@ use omni_led_lib::constants::constants::Constants;
 use omni_led_lib::logging::logger::LogHandle;
 use std::path::{Path, PathBuf};
 
+const PATTERN: &str = "[{d(%Y-%m-%d %H:%M:%S:%3f)}][{l}][{t}] {m}\n";
+
 pub struct OmniLedLogHandle {
     handle: Handle,
-    path: PathBuf,
+    path: Option<PathBuf>,
 }
 
 impl LogHandle for OmniLedLogHandle {
     fn set_level_filter(&self, level_filter: LevelFilter) {
-        let config = create_config(&self.path, level_filter);
+        let config = create_config(self.path.as_deref(), level_filter);
         self.handle.set_config(config);
     }
 }
@@ -26,7 +30,7 @@ pub fn init() -> OmniLedLogHandle {
 
     let path = Constants::data_dir().join("logging.log");
 
-    let config = create_config(&path, default_log_level());
+    let config = create_config(Some(&path), default_log_level());
     let handle = log4rs::init_config(config).unwrap();
 
     let default_hook = std::panic::take_hook();
@@ -35,18 +39,37 @@ pub fn init() -> OmniLedLogHandle {
         default_hook(panic_info);
     }));
 
-    OmniLedLogHandle { handle, path }
+    OmniLedLogHandle {
+        handle,
+        path: Some(path),
+    }
 }
 
-fn create_config(file_path: impl AsRef<Path>, level_filter: LevelFilter) -> Config {
+// Logs to stderr instead of the log file, for command line usage
+pub fn init_console() -> OmniLedLogHandle {
+    let config = create_config(None, default_log_level());
+    let handle = log4rs::init_config(config).unwrap();
+
+    OmniLedLogHandle { handle, path: None }
+}
+
+fn create_config(file_path: Option<&Path>, level_filter: LevelFilter) -> Config {
     const LOGFILE: &str = "logfile";
 
-    let logfile = FileAppender::builder()
-        .encoder(Box::new(PatternEncoder::new(
-            "[{d(%Y-%m-%d %H:%M:%S:%3f)}][{l}][{t}] {m}\n",
-        )))
-        .build(file_path)
-        .unwrap();
+    let logfile: Box<dyn Append> = match file_path {
+        Some(file_path) => Box::new(
+            FileAppender::builder()
+                .encoder(Box::new(PatternEncoder::new(PATTERN)))
+                .build(file_path)
+                .unwrap(),
+        ),
+        None => Box::new(
+            ConsoleAppender::builder()
+                .encoder(Box::new(PatternEncoder::new(PATTERN)))
+                .target(Target::Stderr)
+                .build(),
+        ),
+    };
 
     let add_config = |builder: ConfigBuilder, name: &'static str| -> ConfigBuilder {
         builder.logger(
@@ -57,7 +80,7 @@ fn create_config(file_path: impl AsRef<Path>, level_filter: LevelFilter) -> Conf
         )
     };
 
-    let builder = Config::builder().appender(Appender::builder().build(LOGFILE, Box::new(logfile)));
+    let builder = Config::builder().appender(Appender::builder().build(LOGFILE, logfile));
 
     // OmniLED implementation files
     let builder = add_config(builder, "omni_led");
@@ -1,22 +1,25 @@
 #![cfg_attr(not(feature = "dev"), windows_subsystem = "windows")]
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use log::debug;
 use mlua::Lua;
 use omni_led_lib::{
     common::common::load_internal_functions,
     common::user_data::UserDataRef,
     constants::config::{ConfigType, read_config, write_default_configs},
+    constants::config_watcher::ConfigWatcher,
     constants::constants::Constants,
     devices::devices::Devices,
     events::dispatcher::Dispatcher,
     events::event_loop::EventLoop,
+    events::event_queue::Event as QueuedEvent,
     events::events::Events,
     events::shortcuts::Shortcuts,
     keyboard::keyboard::process_events,
     logging::logger::Log,
     plugin_loader::plugin_loader::PluginLoader,
     script_handler::script_handler::ScriptHandler,
+    script_handler::storage::Storage,
     settings::settings::Settings,
     ui::event::Event,
     ui::handler::{HandlerBuilder, PROXY},
@@ -27,12 +30,14 @@ use std::time::Instant;
 
 #[cfg(target_os = "windows")]
 mod console;
+mod gen_types;
 mod logging;
+mod render;
 
 static RUNNING: AtomicBool = AtomicBool::new(true);
 
 fn main() {
-    let _options = match Options::try_parse() {
+    let options = match Options::try_parse() {
         Ok(options) => {
             #[cfg(target_os = "windows")]
             if options.attach_console {
@@ -49,6 +54,18 @@ fn main() {
         }
     };
 
+    if let Some(command) = options.command {
+        let result = match command {
+            Command::Render(render_options) => render::run(render_options),
+            Command::GenTypes(gen_types_options) => gen_types::run(gen_types_options),
+        };
+        if let Err(err) = result {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     set_panic_hook();
 
     let (ready_tx, ready_rx) = sync::mpsc::channel();
@@ -77,24 +94,43 @@ fn main() {
         let mut dispatcher = Dispatcher::load(&lua);
         Events::load(&lua);
         Shortcuts::load(&lua);
+        Storage::load(&lua);
         Devices::load(&lua, devices_config);
-        ScriptHandler::load(&lua, scripts_config);
+        ScriptHandler::load(&lua, scripts_config, Constants::config_dir()).unwrap();
         PluginLoader::load(&lua, plugins_config);
 
         let init_end = Instant::now();
         debug!("Initialized in {:?}", init_end - init_begin);
 
         let settings = UserDataRef::<Settings>::load(&lua);
-        let interval = settings.get().update_interval;
+        let mut config_watcher = ConfigWatcher::default();
+        config_watcher.set_enabled(settings.get().watch_config);
         let event_loop = EventLoop::new();
-        event_loop.run(interval, &RUNNING, |events| {
+        event_loop.run(&RUNNING, |events| {
+            let reload_settings = events
+                .iter()
+                .any(|event| matches!(event, QueuedEvent::ReloadSettings));
             for event in events {
                 dispatcher.dispatch(&lua, event).unwrap();
             }
 
+            if reload_settings {
+                config_watcher.set_enabled(settings.get().watch_config);
+            }
+
+            let interval = settings.get().update_interval;
             let mut script_handler = UserDataRef::<ScriptHandler>::load(&lua);
             script_handler.get_mut().update(&lua, interval).unwrap();
+
+            let mut storage = UserDataRef::<Storage>::load(&lua);
+            storage.get_mut().update();
+
+            interval
         });
+
+        UserDataRef::<Storage>::load(&lua).get_mut().flush();
+
+        config_watcher.stop();
     });
 
     let keyboard_thread = std::thread::spawn(|| process_events(&RUNNING));
@@ -128,4 +164,16 @@ struct Options {
     /// Attach console to the program. Applies only on Windows.
     #[clap(short, long, default_value = "false")]
     attach_console: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Render layouts without a device or a window, e.g. for testing scripts
+    Render(render::RenderOptions),
+
+    /// Generate LuaLS type definitions for the config files, to enable completion in editors
+    GenTypes(gen_types::GenTypesOptions),
 }
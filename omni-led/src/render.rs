@@ -0,0 +1,265 @@
+use ciborium::Value as CborValue;
+use clap::{Args, ValueEnum};
+use log::info;
+use mlua::Lua;
+use omni_led_lib::{
+    common::common::load_internal_functions,
+    common::user_data::{UserDataRef, set_unique_user_data},
+    constants::config::{ConfigType, read_config},
+    constants::constants::Constants,
+    devices::device::{Buffer, MemoryLayout, Size},
+    devices::devices::Devices,
+    devices::virtual_device::virtual_device::{VirtualDeviceSettings, compare, save_gif, to_image},
+    events::dispatcher::Dispatcher,
+    events::event_queue::{Event, EventQueue},
+    events::events::Events,
+    events::shortcuts::Shortcuts,
+    logging::logger::Log,
+    script_handler::script_handler::ScriptHandler,
+    script_handler::storage::Storage,
+    settings::settings::Settings,
+};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::logging;
+
+#[derive(Args)]
+pub struct RenderOptions {
+    /// Name of the device used in scripts.lua
+    #[clap(short, long, default_value = "Emulator")]
+    device: String,
+
+    /// Screen width in pixels
+    #[clap(long, default_value = "128")]
+    width: usize,
+
+    /// Screen height in pixels
+    #[clap(long, default_value = "40")]
+    height: usize,
+
+    /// Memory layout of the virtual device
+    #[clap(long, value_enum, default_value = "bit-per-pixel")]
+    memory_layout: Layout,
+
+    /// Number of updates to run
+    #[clap(short, long, default_value = "10")]
+    ticks: usize,
+
+    /// Scripts file, defaults to scripts.lua in the config directory
+    #[clap(long)]
+    scripts: Option<PathBuf>,
+
+    /// Settings file, defaults to settings.lua in the config directory
+    #[clap(long)]
+    settings: Option<PathBuf>,
+
+    /// Event data in JSON or CBOR format. An array provides separate data for each update
+    #[clap(short, long)]
+    events: Option<PathBuf>,
+
+    /// Output directory for PNG frames, or a path to an animated GIF
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// Directory with reference PNG frames, fails if any of the frames differ
+    #[clap(short, long)]
+    compare: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Layout {
+    BitPerPixel,
+    BytePerPixel,
+    BitPerPixelVertical,
+    TwoBitsPerPixel,
+    FourBitsPerPixel,
+    Rgb565,
+    Rgb888,
+}
+
+impl From<Layout> for MemoryLayout {
+    fn from(layout: Layout) -> Self {
+        match layout {
+            Layout::BitPerPixel => MemoryLayout::BitPerPixel,
+            Layout::BytePerPixel => MemoryLayout::BytePerPixel,
+            Layout::BitPerPixelVertical => MemoryLayout::BitPerPixelVertical,
+            Layout::TwoBitsPerPixel => MemoryLayout::TwoBitsPerPixel,
+            Layout::FourBitsPerPixel => MemoryLayout::FourBitsPerPixel,
+            Layout::Rgb565 => MemoryLayout::Rgb565,
+            Layout::Rgb888 => MemoryLayout::Rgb888,
+        }
+    }
+}
+
+pub fn run(options: RenderOptions) -> Result<(), String> {
+    let events = match &options.events {
+        Some(path) => read_events(path)?,
+        None => Vec::new(),
+    };
+
+    let lua = Lua::new();
+
+    load_internal_functions(&lua);
+    Constants::load(&lua);
+    Log::load(&lua, logging::init_console());
+
+    let settings_config = read_file(options.settings.as_deref(), ConfigType::Settings)?;
+    let scripts_config = read_file(options.scripts.as_deref(), ConfigType::Scripts)?;
+
+    // Modules are resolved next to the scripts file, same as for the config directory
+    let modules_root = match options.scripts.as_deref().and_then(Path::parent) {
+        Some(parent) => parent.to_path_buf(),
+        None => Constants::config_dir(),
+    };
+
+    let size = Size {
+        width: options.width,
+        height: options.height,
+    };
+    let memory_layout = options.memory_layout.into();
+    let frame = Rc::new(RefCell::new(None));
+
+    let settings = Settings::evaluate(&lua, settings_config).map_err(|err| err.to_string())?;
+    Settings::load_evaluated(&lua, settings);
+    let mut dispatcher = Dispatcher::load(&lua);
+    Events::load(&lua);
+    Shortcuts::load(&lua);
+    // Rendering must not depend on, or modify, data stored by the running application
+    let storage_path =
+        std::env::temp_dir().join(format!("omni-led-render-{}.json", std::process::id()));
+    set_unique_user_data(&lua, Storage::new(storage_path));
+    Devices::load(&lua, String::new());
+    UserDataRef::<Devices>::load(&lua)
+        .get_mut()
+        .add_virtual_device(
+            &lua,
+            VirtualDeviceSettings {
+                name: options.device.clone(),
+                size,
+                memory_layout,
+                frame: Rc::clone(&frame),
+            },
+        )
+        .map_err(|err| err.to_string())?;
+    ScriptHandler::load(&lua, scripts_config, modules_root).map_err(|err| err.to_string())?;
+
+    let interval = UserDataRef::<Settings>::load(&lua).get().update_interval;
+    let mut frames = Vec::with_capacity(options.ticks);
+    for tick in 0..options.ticks {
+        let event = match events.len() {
+            1 => (tick == 0).then(|| events[0].clone()),
+            _ => events.get(tick).cloned(),
+        };
+
+        let events = {
+            let event_queue = EventQueue::instance();
+            let mut event_queue = event_queue.lock().unwrap();
+            if let Some(event) = event.filter(|event| !event.is_null()) {
+                event_queue.push(Event::Application(event));
+            }
+            event_queue.get_events()
+        };
+
+        for event in events {
+            dispatcher
+                .dispatch(&lua, event)
+                .map_err(|err| err.to_string())?;
+        }
+
+        UserDataRef::<ScriptHandler>::load(&lua)
+            .get_mut()
+            .update(&lua, interval)
+            .map_err(|err| err.to_string())?;
+
+        // Devices are only updated when the frame changes, so keep showing the previous one
+        let buffer = frame
+            .borrow()
+            .clone()
+            .unwrap_or_else(|| Buffer::new(size, memory_layout));
+        frames.push(to_image(&buffer));
+    }
+
+    if let Some(output) = &options.output {
+        write_frames(output, &frames, interval)?;
+    }
+
+    match &options.compare {
+        Some(reference) => compare_frames(reference, &frames),
+        None => Ok(()),
+    }
+}
+
+fn read_file(path: Option<&Path>, config_type: ConfigType) -> Result<String, String> {
+    match path {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read '{}': {}", path.display(), err)),
+        None => read_config(config_type).map_err(|err| err.to_string()),
+    }
+}
+
+// Single event data is sent only once, arrays provide event data for each update separately
+pub fn read_events(path: &Path) -> Result<Vec<CborValue>, String> {
+    let content = std::fs::read(path)
+        .map_err(|err| format!("Failed to read '{}': {}", path.display(), err))?;
+
+    let value: CborValue = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => serde_json::from_slice(&content).map_err(|err| err.to_string())?,
+        _ => ciborium::from_reader(content.as_slice()).map_err(|err| err.to_string())?,
+    };
+
+    match value {
+        CborValue::Array(values) => Ok(values),
+        value => Ok(vec![value]),
+    }
+}
+
+fn frame_name(index: usize) -> String {
+    format!("frame_{index:04}.png")
+}
+
+fn write_frames(
+    output: &Path,
+    frames: &[image::DynamicImage],
+    interval: std::time::Duration,
+) -> Result<(), String> {
+    if output
+        .extension()
+        .is_some_and(|extension| extension == "gif")
+    {
+        save_gif(output, frames, interval).map_err(|err| err.to_string())?;
+        info!("Saved {} frames to '{}'", frames.len(), output.display());
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(output).map_err(|err| err.to_string())?;
+    for (index, frame) in frames.iter().enumerate() {
+        frame
+            .save(output.join(frame_name(index)))
+            .map_err(|err| err.to_string())?;
+    }
+    info!("Saved {} frames to '{}'", frames.len(), output.display());
+    Ok(())
+}
+
+fn compare_frames(reference: &Path, frames: &[image::DynamicImage]) -> Result<(), String> {
+    let mut failures = Vec::new();
+    for (index, frame) in frames.iter().enumerate() {
+        let path = reference.join(frame_name(index));
+        match compare(frame, &path) {
+            Ok(0) => {}
+            Ok(differences) => failures.push(format!(
+                "'{}' differs in {} pixels",
+                path.display(),
+                differences
+            )),
+            Err(err) => failures.push(format!("Failed to compare '{}': {}", path.display(), err)),
+        }
+    }
+
+    match failures.is_empty() {
+        true => Ok(()),
+        false => Err(failures.join("\n")),
+    }
+}